@@ -0,0 +1,28 @@
+// Feeds arbitrary FENs and move lists into `set_position`.
+// Run with: cargo +nightly fuzz run set_position
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xewali_chess_rs::engine;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    // First line is the FEN, the remaining whitespace-separated tokens are moves
+    let mut lines = text.splitn(2, '\n');
+    let fen = lines.next().unwrap_or("");
+    let moves: Vec<String> = lines
+        .next()
+        .unwrap_or("")
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+
+    if let Ok((board, history, _)) = engine::set_position(fen, &moves) {
+        assert_eq!(history.len(), moves.len() + 1);
+        assert_eq!(history.last(), Some(&board.get_hash()));
+    }
+});
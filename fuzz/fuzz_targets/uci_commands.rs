@@ -0,0 +1,51 @@
+// Feeds arbitrary command sequences into the UCI layer.
+// Run with: cargo +nightly fuzz run uci_commands
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xewali_chess_rs::book::Book;
+use xewali_chess_rs::uci::{self, Uci};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let mut session = Uci::new(Book::new());
+    for line in text.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.first() == Some(&"go") {
+            // Exercise the go parser on the raw input, but keep the search
+            // itself instant so the fuzzer spends its time on parsing
+            let _ = uci::parse_go_command(&tokens, &chess::Board::default(), 1, 0);
+            session.handle_command("go movetime 0");
+            continue;
+        }
+        if matches!(tokens.first(), Some(&"bench" | &"perft" | &"divide" | &"tournament" | &"selfplay" | &"match")) {
+            // Slow by design: their run time grows with their arguments
+            continue;
+        }
+        if touches_files(&tokens) {
+            // Would read, write or delete real paths in the working tree
+            continue;
+        }
+        if !session.handle_command(line) {
+            break;
+        }
+    }
+});
+
+/// Commands that read, write or delete files named by (or defaulting to
+/// paths next to) the session
+fn touches_files(tokens: &[&str]) -> bool {
+    match tokens.first() {
+        Some(&"saveparams" | &"export-config" | &"import-config" | &"tune") => true,
+        Some(&"setoption") => uci::parse_setoption_command(tokens).is_some_and(|(name, _)| {
+            ["Debug Log File", "EvalFile", "PersistOptions"]
+                .iter()
+                .any(|option| name.eq_ignore_ascii_case(option))
+        }),
+        _ => false,
+    }
+}
@@ -0,0 +1,220 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{Board, BoardStatus, Piece};
+
+use crate::evaluation::{self, GameResult};
+use crate::variant::Variant;
+
+/// Ends a game early once the evaluation has favoured the same side by at
+/// least `threshold` centipawns for `plies` consecutive evaluated plies
+#[derive(Clone, Debug)]
+pub struct MercyRule {
+    /// Minimum advantage in centipawns; 0 disables the rule
+    pub threshold: i32,
+    /// Number of consecutive evaluations the advantage must hold for
+    pub plies: u32,
+    streak: u32,
+    leader: GameResult,
+}
+
+impl MercyRule {
+    pub fn new(threshold: i32, plies: u32) -> Self {
+        MercyRule {
+            threshold,
+            plies,
+            streak: 0,
+            leader: GameResult::Ongoing,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.threshold > 0
+    }
+
+    /// Forget the current streak (e.g., at the start of a new game)
+    pub fn reset(&mut self) {
+        self.streak = 0;
+        self.leader = GameResult::Ongoing;
+    }
+
+    /// Record the evaluation (from White's point of view) of the latest ply.
+    /// Returns the adjudicated result once the rule triggers.
+    pub fn update(&mut self, eval: i32) -> Option<GameResult> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let leader = if eval >= self.threshold {
+            GameResult::WhiteWins
+        } else if eval <= -self.threshold {
+            GameResult::BlackWins
+        } else {
+            GameResult::Ongoing
+        };
+
+        if leader == GameResult::Ongoing || leader != self.leader {
+            self.streak = 0;
+        }
+        self.leader = leader;
+        if leader == GameResult::Ongoing {
+            return None;
+        }
+
+        self.streak += 1;
+        (self.streak >= self.plies).then_some(leader)
+    }
+}
+
+impl Default for MercyRule {
+    fn default() -> Self {
+        MercyRule::new(0, 10)
+    }
+}
+
+/// Whether the game is over by the rules alone: the variant's own ending
+/// (checkmate, stalemate, insufficient material), threefold repetition or
+/// the fifty-move rule. `history` holds the hashes of every position of the
+/// game up to and including `board`. Returns the result and the reason.
+pub fn rules_result(
+    board: &Board,
+    history: &[u64],
+    halfmove_clock: u32,
+    variant: Variant,
+) -> Option<(GameResult, &'static str)> {
+    let result = variant.game_result(board);
+    if result != GameResult::Ongoing {
+        let reason = match (variant, board.status()) {
+            (Variant::Standard, BoardStatus::Checkmate) => "checkmate",
+            (Variant::Standard, BoardStatus::Stalemate) => "stalemate",
+            (Variant::Standard, _) if evaluation::is_insufficient_material(board) => {
+                "insufficient material"
+            }
+            _ => "no legal moves",
+        };
+        return Some((result, reason));
+    }
+    if history.iter().filter(|&&h| h == board.get_hash()).count() >= 3 {
+        return Some((GameResult::Draw, "threefold repetition"));
+    }
+    if halfmove_clock >= 100 {
+        return Some((GameResult::Draw, "fifty-move rule"));
+    }
+    None
+}
+
+/// Whether the move from `before` to `after` was a capture or pawn move,
+/// which resets the fifty-move counter
+pub fn resets_halfmove_clock(before: &Board, after: &Board) -> bool {
+    after.combined().popcnt() < before.combined().popcnt()
+        || before.pieces(Piece::Pawn) != after.pieces(Piece::Pawn)
+}
+
+/// Halfmove clock field of a FEN string, 0 when it is missing
+pub fn fen_halfmove_clock(fen: &str) -> u32 {
+    fen.split_whitespace()
+        .nth(4)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Halfmove clock at the end of `boards` (every position of a game, first to
+/// current), given the clock in the first position
+pub fn halfmove_clock(start_clock: u32, boards: &[Board]) -> u32 {
+    boards.windows(2).fold(start_clock, |clock, pair| {
+        if resets_halfmove_clock(&pair[0], &pair[1]) {
+            0
+        } else {
+            clock + 1
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::MATE_EVAL;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_mercy_rule_needs_consecutive_plies() {
+        let mut rule = MercyRule::new(500, 3);
+        assert_eq!(rule.update(600), None);
+        assert_eq!(rule.update(700), None);
+        // Streak broken by a closer evaluation
+        assert_eq!(rule.update(100), None);
+        assert_eq!(rule.update(600), None);
+        assert_eq!(rule.update(600), None);
+        assert_eq!(rule.update(600), Some(GameResult::WhiteWins));
+    }
+
+    #[test]
+    fn test_mercy_rule_tracks_leader() {
+        let mut rule = MercyRule::new(500, 2);
+        assert_eq!(rule.update(-900), None);
+        // Switching sides restarts the count
+        assert_eq!(rule.update(900), None);
+        assert_eq!(rule.update(900), Some(GameResult::WhiteWins));
+
+        rule.reset();
+        assert_eq!(rule.update(-900), None);
+        assert_eq!(rule.update(-900), Some(GameResult::BlackWins));
+    }
+
+    #[test]
+    fn test_mercy_rule_disabled() {
+        let mut rule = MercyRule::default();
+        for _ in 0..100 {
+            assert_eq!(rule.update(MATE_EVAL), None);
+        }
+    }
+
+    #[test]
+    fn test_rules_result_reasons() {
+        let result = |fen: &str| {
+            let board = Board::from_str(fen).unwrap();
+            rules_result(&board, &[board.get_hash()], 0, Variant::Standard)
+        };
+        assert_eq!(
+            result("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"),
+            Some((GameResult::WhiteWins, "checkmate"))
+        );
+        assert_eq!(
+            result("k7/8/1QK5/8/8/8/8/8 b - - 0 1"),
+            Some((GameResult::Draw, "stalemate"))
+        );
+        assert_eq!(
+            result("k7/8/1NK5/8/8/8/8/8 b - - 0 1"),
+            Some((GameResult::Draw, "insufficient material"))
+        );
+        assert_eq!(result("k7/8/1RK5/8/8/8/8/8 b - - 0 1"), None);
+
+        let board = Board::default();
+        let hash = board.get_hash();
+        assert_eq!(
+            rules_result(&board, &[hash, 1, hash, 2, hash], 0, Variant::Standard),
+            Some((GameResult::Draw, "threefold repetition"))
+        );
+        assert_eq!(
+            rules_result(&board, &[hash], 100, Variant::Standard),
+            Some((GameResult::Draw, "fifty-move rule"))
+        );
+    }
+
+    #[test]
+    fn test_halfmove_clock() {
+        let mut boards = vec![Board::default()];
+        for mv in ["g1f3", "g8f6", "f3g1", "e7e5", "g1f3", "f6e4"] {
+            let board = boards.last().unwrap();
+            let mv = crate::moves::parse_uci_move(board, mv).unwrap();
+            boards.push(board.make_move_new(mv));
+        }
+        // Reset by 2... e5, then two quiet moves
+        assert_eq!(halfmove_clock(0, &boards), 2);
+        assert_eq!(halfmove_clock(10, &boards[..4]), 13);
+
+        assert_eq!(fen_halfmove_clock("8/8/8/4k3/8/8/4K3/8 w - - 37 80"), 37);
+        assert_eq!(fen_halfmove_clock("8/8/8/4k3/8/8/4K3/8 w - -"), 0);
+    }
+}
@@ -0,0 +1,246 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use std::time::Instant;
+
+use crate::book::Book;
+use crate::engine::{self, EngineOptions, EngineState, SearchLimits, SearchParams};
+use crate::tt::BUCKET_SIZES;
+use crate::uci_println;
+
+/// Depth searched by `bench` when none is given
+pub const DEFAULT_BENCH_DEPTH: i32 = 5;
+
+/// Fixed set of positions covering openings, middlegames and endgames
+const BENCH_POSITIONS: [&str; 20] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4Pp2/1BNP4/PPP2PPP/3R1RK1 w - - 2 14",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - 0 13",
+    "r1bq1rk1/ppp1nppp/4n3/3p3Q/3P4/1BP1B3/PP1N2PP/R4RK1 w - - 1 16",
+    "4r1k1/r1q2ppp/ppp2n2/4P3/5Rb1/1N1BQ3/PPP3PP/R5K1 w - - 1 17",
+    "2rqkb1r/ppp2p2/2npb1p1/1N1Nn2p/2P1PP2/8/PP2B1PP/R1BQK2R b KQ - 0 11",
+    "r1bq1r1k/b1p1npp1/p2p3p/1p6/3PP3/1B2NN2/PP3PPP/R2Q1RK1 w - - 1 16",
+    "3r1rk1/p5pp/bpp1pp2/8/q1PP1P2/b3P3/P2NQRPP/1R2B1K1 b - - 6 22",
+    "r1q2rk1/2p1bppp/2Pp4/p6b/Q1PNp3/4B3/PP1R1PPP/2K4R w - - 2 18",
+    "4k2r/1pb2ppp/1p2p3/1R1p4/3P4/2r1PN2/P4PPP/1R4K1 b - - 3 22",
+    "3q2k1/pb3p1p/4pbp1/2r5/PpN2N2/1P2P2P/5PP1/Q2R2K1 b - - 4 26",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
+    "3b4/5kp1/1p1p1p1p/pP1PpP1P/P1P1P3/3KN3/8/8 w - - 0 1",
+    "8/8/8/8/5kp1/P7/8/1K1N4 w - - 0 1",
+    "8/3k4/8/8/8/4B3/4KB2/2B5 w - - 0 1",
+];
+
+/// Bench positions searched by `calibrate`, and the time spent on each
+const CALIBRATION_POSITIONS: usize = 4;
+const CALIBRATION_MOVETIME: f64 = 0.05;
+
+/// Range of time check intervals `calibrate` picks from
+const MIN_TIME_CHECK_INTERVAL: u64 = 256;
+const MAX_TIME_CHECK_INTERVAL: u64 = 16384;
+
+/// Memory the latency probe reads from, much larger than any cache, and the
+/// number of buckets it reads at each size
+const PROBE_MB: usize = 64;
+const PROBE_READS: usize = 1 << 18;
+
+/// Slowdown over the smallest bucket accepted for a larger one, in percent
+const BUCKET_TOLERANCE: u128 = 25;
+
+/// Result of a benchmark run
+pub struct BenchResult {
+    pub positions: usize,
+    pub nodes: u64,
+    pub elapsed_ms: u128,
+}
+
+impl BenchResult {
+    pub fn nps(&self) -> u128 {
+        self.nodes as u128 * 1000 / self.elapsed_ms.max(1)
+    }
+}
+
+/// Search every bench position to a fixed depth with fresh tables and no
+/// book, so the total node count is a deterministic fingerprint of the search
+pub fn run_bench(depth: i32, params: &SearchParams) -> BenchResult {
+    let book = Book::new();
+    let options = EngineOptions {
+        own_book: false,
+        ..EngineOptions::default()
+    };
+    let limits = SearchLimits::depth(depth);
+
+    let start = Instant::now();
+    let mut nodes = 0;
+
+    for (i, fen) in BENCH_POSITIONS.iter().enumerate() {
+        uci_println!(
+            "info string bench position {}/{}: {}",
+            i + 1,
+            BENCH_POSITIONS.len(),
+            fen
+        );
+        let (board, history, halfmove_clock) =
+            engine::set_position(fen, &[]).expect("valid bench position");
+        let mut engine_state = EngineState::new();
+        engine_state.params = *params;
+        engine::play_move(
+            &board,
+            &book,
+            &limits,
+            &history,
+            halfmove_clock,
+            &mut engine_state,
+            &options,
+        );
+        nodes += engine_state.nodes;
+    }
+
+    BenchResult {
+        positions: BENCH_POSITIONS.len(),
+        nodes,
+        elapsed_ms: start.elapsed().as_millis(),
+    }
+}
+
+/// Speed of this machine and the time check interval and transposition
+/// table bucket size chosen for it
+pub struct Calibration {
+    pub nps: u128,
+    pub time_check_interval: u64,
+    /// Average time in nanoseconds to read a random bucket of each of
+    /// `BUCKET_SIZES`
+    pub bucket_latency_ns: [u128; BUCKET_SIZES.len()],
+    pub tt_bucket_size: usize,
+}
+
+/// Measure the search speed on a few bench positions and pick a time check
+/// interval of about one check per millisecond: slow machines (e.g. a
+/// Raspberry Pi) check more often, so short time controls stay responsive,
+/// while fast ones do not waste time reading the clock
+pub fn calibrate(params: &SearchParams) -> Calibration {
+    let book = Book::new();
+    let options = EngineOptions {
+        own_book: false,
+        silent: true,
+        ..EngineOptions::default()
+    };
+    let limits = SearchLimits::time(CALIBRATION_MOVETIME);
+
+    let start = Instant::now();
+    let mut nodes = 0;
+    for fen in &BENCH_POSITIONS[..CALIBRATION_POSITIONS] {
+        let (board, history, halfmove_clock) =
+            engine::set_position(fen, &[]).expect("valid bench position");
+        let mut engine_state = EngineState::new();
+        engine_state.params = *params;
+        engine::play_move(
+            &board,
+            &book,
+            &limits,
+            &history,
+            halfmove_clock,
+            &mut engine_state,
+            &options,
+        );
+        nodes += engine_state.nodes;
+    }
+
+    let nps = nodes as u128 * 1000 / start.elapsed().as_millis().max(1);
+    let per_ms = ((nps / 1000) as u64).clamp(MIN_TIME_CHECK_INTERVAL, MAX_TIME_CHECK_INTERVAL);
+    let bucket_latency_ns = bucket_latency();
+    Calibration {
+        nps,
+        // Rounded down, so checks come at least once per millisecond
+        time_check_interval: 1 << per_ms.ilog2(),
+        bucket_latency_ns,
+        tt_bucket_size: pick_bucket_size(&bucket_latency_ns),
+    }
+}
+
+/// Time in nanoseconds to read a random bucket of each of `BUCKET_SIZES`
+/// from memory laid out like the transposition table (two words an entry)
+fn bucket_latency() -> [u128; BUCKET_SIZES.len()] {
+    let words = PROBE_MB * 1024 * 1024 / std::mem::size_of::<u64>();
+    let memory: Vec<u64> = (0..words as u64).collect();
+    let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+
+    BUCKET_SIZES.map(|bucket_size| {
+        let bucket_words = 2 * bucket_size;
+        let buckets = (words / bucket_words) as u64;
+        let start = Instant::now();
+        let mut sum = 0u64;
+        for _ in 0..PROBE_READS {
+            // xorshift, so the reads defeat the prefetcher like hash keys do
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let first = (seed % buckets) as usize * bucket_words;
+            for &word in &memory[first..first + bucket_words] {
+                sum = sum.wrapping_add(word);
+            }
+        }
+        std::hint::black_box(sum);
+        start.elapsed().as_nanos() / PROBE_READS as u128
+    })
+}
+
+/// The largest bucket, and so the best replacement, read at most
+/// `BUCKET_TOLERANCE` percent slower than the smallest: on machines whose
+/// memory fetches a bucket's extra cache lines cheaply the table gets more
+/// entries to choose from, while slow memory keeps small buckets
+fn pick_bucket_size(latency_ns: &[u128; BUCKET_SIZES.len()]) -> usize {
+    let limit = latency_ns[0] * (100 + BUCKET_TOLERANCE) / 100;
+    BUCKET_SIZES
+        .iter()
+        .zip(latency_ns)
+        .filter(|&(_, &ns)| ns <= limit)
+        .map(|(&size, _)| size)
+        .max()
+        .unwrap_or(BUCKET_SIZES[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_positions_are_valid() {
+        for fen in BENCH_POSITIONS {
+            assert!(engine::set_position(fen, &[]).is_ok(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_bench_is_deterministic() {
+        let params = SearchParams::default();
+        let first = run_bench(1, &params);
+        let second = run_bench(1, &params);
+        assert_eq!(first.nodes, second.nodes);
+        assert!(first.nodes > 0);
+    }
+
+    #[test]
+    fn test_calibrate_picks_power_of_two() {
+        let calibration = calibrate(&SearchParams::default());
+        let interval = calibration.time_check_interval;
+        assert!(calibration.nps > 0);
+        assert!(interval.is_power_of_two());
+        assert!((MIN_TIME_CHECK_INTERVAL..=MAX_TIME_CHECK_INTERVAL).contains(&interval));
+        assert!(BUCKET_SIZES.contains(&calibration.tt_bucket_size));
+    }
+
+    #[test]
+    fn test_pick_bucket_size() {
+        // Extra cache lines are almost free: the largest bucket
+        assert_eq!(pick_bucket_size(&[80, 82, 95]), 8);
+        // The second cache line of an 8-entry bucket costs a memory access
+        assert_eq!(pick_bucket_size(&[80, 84, 150]), 4);
+        assert_eq!(pick_bucket_size(&[80, 120, 200]), 2);
+    }
+}
@@ -2,81 +2,16 @@
 // Rust port: 2024
 // email: himangshu.saikia.iitg@gmail.com
 
-use chess::{Board, ChessMove, MoveGen};
-use std::collections::{HashMap, HashSet};
+use chess::{Board, ChessMove, ALL_PIECES, ALL_SQUARES};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 
-/// Opening book: maps position hash to set of possible moves
-pub type Book = HashMap<u64, HashSet<ChessMove>>;
+use crate::moves::parse_uci_move;
 
-/// Parse a UCI format move string (e.g., "e2e4", "e7e8q")
-fn parse_uci_move(board: &Board, move_str: &str) -> Option<ChessMove> {
-    use chess::{File as ChessFile, Piece, Rank, Square};
-
-    let move_str = move_str.trim();
-    if move_str.len() < 4 {
-        return None;
-    }
-
-    let chars: Vec<char> = move_str.chars().collect();
-
-    // Parse source square
-    let from_file_idx = (chars[0] as u8).checked_sub(b'a')?;
-    let from_rank_idx = (chars[1] as u8).checked_sub(b'1')?;
-    if from_file_idx > 7 || from_rank_idx > 7 {
-        return None;
-    }
-
-    // Parse destination square
-    let to_file_idx = (chars[2] as u8).checked_sub(b'a')?;
-    let to_rank_idx = (chars[3] as u8).checked_sub(b'1')?;
-    if to_file_idx > 7 || to_rank_idx > 7 {
-        return None;
-    }
-
-    let from_file = ChessFile::from_index(from_file_idx as usize);
-    let from_rank = Rank::from_index(from_rank_idx as usize);
-    let to_file = ChessFile::from_index(to_file_idx as usize);
-    let to_rank = Rank::from_index(to_rank_idx as usize);
-
-    let from = Square::make_square(from_rank, from_file);
-    let to = Square::make_square(to_rank, to_file);
-
-    // Check for promotion
-    let promotion = if move_str.len() >= 5 {
-        match chars[4] {
-            'q' | 'Q' => Some(Piece::Queen),
-            'r' | 'R' => Some(Piece::Rook),
-            'b' | 'B' => Some(Piece::Bishop),
-            'n' | 'N' => Some(Piece::Knight),
-            _ => None,
-        }
-    } else {
-        None
-    };
-
-    // Find matching legal move
-    let movegen = MoveGen::new_legal(board);
-    for mv in movegen {
-        if mv.get_source() == from && mv.get_dest() == to {
-            match (promotion, mv.get_promotion()) {
-                (Some(p), Some(mp)) if p == mp => return Some(mv),
-                (None, None) => return Some(mv),
-                (None, Some(_)) => {
-                    // Move string didn't specify promotion but move has one
-                    // This can happen if the move string is incomplete
-                    // Accept it if it's the only matching move
-                }
-                _ => continue,
-            }
-        }
-    }
-
-    // Fallback: find any move matching from/to
-    let mut movegen = MoveGen::new_legal(board);
-    movegen.find(|&mv| mv.get_source() == from && mv.get_dest() == to)
-}
+/// Opening book: maps position hash to the possible moves and the number of
+/// book games each move was played in
+pub type Book = HashMap<u64, HashMap<ChessMove, u32>>;
 
 /// Load opening book from a UCI games file
 /// Each line in the file should be a sequence of UCI moves (e.g., "e2e4 e7e5 g1f3 ...")
@@ -102,9 +37,9 @@ pub fn load_games(game_file: &str) -> Book {
         let mut board = Board::default();
 
         for move_str in line.split_whitespace() {
-            if let Some(mv) = parse_uci_move(&board, move_str) {
+            if let Ok(mv) = parse_uci_move(&board, move_str) {
                 let key = board.get_hash();
-                book.entry(key).or_default().insert(mv);
+                *book.entry(key).or_default().entry(mv).or_default() += 1;
                 board = board.make_move_new(mv);
             } else {
                 // Invalid move, skip rest of line
@@ -116,26 +51,138 @@ pub fn load_games(game_file: &str) -> Book {
     book
 }
 
+/// Header identifying a binary book file
+const BINARY_BOOK_MAGIC: &[u8; 8] = b"XWBOOK1\n";
+
+/// Write the book in binary form.
+///
+/// Layout (little endian): magic, position count (u64), then per position the
+/// hash (u64), move count (u16) and per move source square, destination square,
+/// promotion piece index + 1 or 0 (u8 each) and game count (u32). Positions are
+/// sorted by hash so the file is byte-for-byte reproducible.
+pub fn save_binary(book: &Book, path: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(BINARY_BOOK_MAGIC)?;
+    out.write_all(&(book.len() as u64).to_le_bytes())?;
+
+    let mut keys: Vec<&u64> = book.keys().collect();
+    keys.sort();
+    for key in keys {
+        let mut moves: Vec<(&ChessMove, &u32)> = book[key].iter().collect();
+        moves.sort_by_key(|(mv, _)| (mv.get_source(), mv.get_dest(), mv.get_promotion()));
+
+        out.write_all(&key.to_le_bytes())?;
+        out.write_all(&(moves.len() as u16).to_le_bytes())?;
+        for (mv, count) in moves {
+            let promotion = mv.get_promotion().map_or(0, |p| p.to_index() as u8 + 1);
+            out.write_all(&[mv.get_source().to_int(), mv.get_dest().to_int(), promotion])?;
+            out.write_all(&count.to_le_bytes())?;
+        }
+    }
+
+    out.flush()
+}
+
+/// Read a book written by `save_binary`
+pub fn load_binary(path: &str) -> io::Result<Book> {
+    let mut input = BufReader::new(File::open(path)?);
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a binary book file");
+
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != BINARY_BOOK_MAGIC {
+        return Err(invalid());
+    }
+
+    let mut u64_buf = [0u8; 8];
+    let mut u16_buf = [0u8; 2];
+    let mut u32_buf = [0u8; 4];
+    let mut move_buf = [0u8; 3];
+
+    input.read_exact(&mut u64_buf)?;
+    let positions = u64::from_le_bytes(u64_buf);
+
+    let mut book = Book::new();
+    for _ in 0..positions {
+        input.read_exact(&mut u64_buf)?;
+        let key = u64::from_le_bytes(u64_buf);
+        input.read_exact(&mut u16_buf)?;
+        let moves = book.entry(key).or_default();
+
+        for _ in 0..u16::from_le_bytes(u16_buf) {
+            input.read_exact(&mut move_buf)?;
+            input.read_exact(&mut u32_buf)?;
+            let [source, dest, promotion] = move_buf.map(usize::from);
+            if source >= 64 || dest >= 64 || promotion > ALL_PIECES.len() {
+                return Err(invalid());
+            }
+            let promotion = promotion.checked_sub(1).map(|p| ALL_PIECES[p]);
+            let mv = ChessMove::new(ALL_SQUARES[source], ALL_SQUARES[dest], promotion);
+            moves.insert(mv, u32::from_le_bytes(u32_buf));
+        }
+    }
+
+    Ok(book)
+}
+
+/// The `count` most frequently played book moves for a position, most popular first
+pub fn top_moves(book: &Book, key: u64, count: usize) -> Vec<(ChessMove, u32)> {
+    let mut moves: Vec<(ChessMove, u32)> = book
+        .get(&key)
+        .map(|moves| moves.iter().map(|(&mv, &n)| (mv, n)).collect())
+        .unwrap_or_default();
+    // Break ties by move text so the output is deterministic
+    moves.sort_by(|a, b| {
+        b.1.cmp(&a.1)
+            .then_with(|| a.0.to_string().cmp(&b.0.to_string()))
+    });
+    moves.truncate(count);
+    moves
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::str::FromStr;
 
     #[test]
-    fn test_parse_uci_move() {
+    fn test_top_moves() {
         let board = Board::default();
-        let mv = parse_uci_move(&board, "e2e4");
-        assert!(mv.is_some());
-        let mv = mv.unwrap();
-        assert_eq!(format!("{}", mv), "e2e4");
+        let e4 = parse_uci_move(&board, "e2e4").unwrap();
+        let d4 = parse_uci_move(&board, "d2d4").unwrap();
+        let c4 = parse_uci_move(&board, "c2c4").unwrap();
+
+        let mut book = Book::new();
+        let moves = book.entry(board.get_hash()).or_default();
+        moves.insert(c4, 1);
+        moves.insert(e4, 5);
+        moves.insert(d4, 3);
+
+        let top = top_moves(&book, board.get_hash(), 2);
+        assert_eq!(top, vec![(e4, 5), (d4, 3)]);
+        assert!(top_moves(&book, 0, 2).is_empty());
     }
 
     #[test]
-    fn test_parse_uci_promotion() {
-        // Position where a pawn can promote
-        let board = Board::from_str("8/P7/8/8/8/8/8/4K2k w - - 0 1").unwrap();
-        let mv = parse_uci_move(&board, "a7a8q");
-        assert!(mv.is_some());
+    fn test_binary_roundtrip() {
+        let mut book = Book::new();
+        let board = Board::default();
+        let e4 = parse_uci_move(&board, "e2e4").unwrap();
+        book.entry(board.get_hash()).or_default().insert(e4, 7);
+        let promo_board = Board::from_str("8/P7/8/8/8/8/8/4K2k w - - 0 1").unwrap();
+        let promo = parse_uci_move(&promo_board, "a7a8n").unwrap();
+        book.entry(promo_board.get_hash())
+            .or_default()
+            .insert(promo, 1);
+
+        let path = std::env::temp_dir().join("xewali_test_book.bin");
+        let path = path.to_str().unwrap();
+        save_binary(&book, path).unwrap();
+        let loaded = load_binary(path).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(loaded, book);
+        assert!(load_binary("nonexistent_file.bin").is_err());
     }
 
     #[test]
@@ -0,0 +1,189 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use std::sync::OnceLock;
+
+use chess::{Board, BoardBuilder, Color, Piece, Square, ALL_SQUARES, EMPTY};
+
+/// Slots in the table, which both hash functions index
+const TABLE_SIZE: usize = 8192;
+
+/// Evictions after which an insertion is taken to be cycling, the table too
+/// full or the hash functions too weak for the keys
+const MAX_EVICTIONS: usize = TABLE_SIZE;
+
+/// Hash keys of every reversible move (a non-pawn piece moving between two
+/// squares on an empty board) with the squares it connects, stored by cuckoo
+/// hashing so a key is found in one of two slots. Marcel van Kervinck's
+/// method for spotting, from the hash keys alone, a position one move away
+/// from an earlier one.
+struct Cuckoo {
+    keys: Vec<u64>,
+    moves: Vec<Option<(Square, Square)>>,
+}
+
+fn h1(key: u64) -> usize {
+    (key as usize) & (TABLE_SIZE - 1)
+}
+
+fn h2(key: u64) -> usize {
+    ((key >> 16) as usize) & (TABLE_SIZE - 1)
+}
+
+impl Cuckoo {
+    fn build() -> Self {
+        let mut table = Cuckoo {
+            keys: vec![0; TABLE_SIZE],
+            moves: vec![None; TABLE_SIZE],
+        };
+        for color in [Color::White, Color::Black] {
+            for piece in [
+                Piece::Knight,
+                Piece::Bishop,
+                Piece::Rook,
+                Piece::Queen,
+                Piece::King,
+            ] {
+                for from in ALL_SQUARES {
+                    for to in empty_board_moves(piece, from) {
+                        if from.to_index() < to.to_index() {
+                            let key = move_key(piece, color, from, to);
+                            table.insert(key, (from, to));
+                        }
+                    }
+                }
+            }
+        }
+        table
+    }
+
+    fn insert(&mut self, mut key: u64, mv: (Square, Square)) {
+        let mut mv = Some(mv);
+        let mut slot = h1(key);
+        for _ in 0..MAX_EVICTIONS {
+            std::mem::swap(&mut self.keys[slot], &mut key);
+            std::mem::swap(&mut self.moves[slot], &mut mv);
+            // Kicked out nothing: done. Otherwise the evicted entry moves to
+            // its other slot.
+            if mv.is_none() {
+                return;
+            }
+            slot = if slot == h1(key) { h2(key) } else { h1(key) };
+        }
+        panic!(
+            "cuckoo table of {} slots cannot place key {:#x} after {} evictions",
+            TABLE_SIZE, key, MAX_EVICTIONS
+        );
+    }
+
+    fn get(&self, key: u64) -> Option<(Square, Square)> {
+        [h1(key), h2(key)]
+            .into_iter()
+            .find(|&slot| self.keys[slot] == key)
+            .and_then(|slot| self.moves[slot])
+    }
+}
+
+/// Squares `piece` attacks from `square` on an otherwise empty board
+fn empty_board_moves(piece: Piece, square: Square) -> chess::BitBoard {
+    match piece {
+        Piece::Knight => chess::get_knight_moves(square),
+        Piece::Bishop => chess::get_bishop_moves(square, EMPTY),
+        Piece::Rook => chess::get_rook_moves(square, EMPTY),
+        Piece::Queen => {
+            chess::get_bishop_moves(square, EMPTY) | chess::get_rook_moves(square, EMPTY)
+        }
+        _ => chess::get_king_moves(square),
+    }
+}
+
+/// The hash key difference a `color` `piece` moving between `from` and `to`
+/// makes, side to move included. The chess crate keeps its Zobrist keys to
+/// itself, so this is read off the hashes of two positions that differ only
+/// by that move, with the kings placed wherever both positions are legal.
+fn move_key(piece: Piece, color: Color, from: Square, to: Square) -> u64 {
+    let hash = |square: Square, king: Square, their_king: Square, side: Color| {
+        let mut builder = BoardBuilder::new();
+        builder.piece(their_king, Piece::King, !color);
+        if piece != Piece::King {
+            builder.piece(king, Piece::King, color);
+        }
+        builder.piece(square, piece, color).side_to_move(side);
+        Board::try_from(&builder).ok().map(|board| board.get_hash())
+    };
+    for king in ALL_SQUARES {
+        for their_king in ALL_SQUARES {
+            // Both of the moving piece's squares must be free of kings
+            let kings: &[Square] = if piece == Piece::King {
+                &[their_king]
+            } else {
+                &[king, their_king]
+            };
+            if kings.contains(&from) || kings.contains(&to) {
+                continue;
+            }
+            let before = hash(from, king, their_king, !color);
+            let after = hash(to, king, their_king, color);
+            if let (Some(before), Some(after)) = (before, after) {
+                return before ^ after;
+            }
+        }
+    }
+    unreachable!("no legal setup for {piece:?} {from} {to}")
+}
+
+fn table() -> &'static Cuckoo {
+    static TABLE: OnceLock<Cuckoo> = OnceLock::new();
+    TABLE.get_or_init(Cuckoo::build)
+}
+
+/// The squares of the reversible move, if any, whose hash key difference is
+/// `key`: the XOR of the hashes of two positions, one move of a non-pawn
+/// piece apart. Which end of it the piece is on tells the direction.
+pub(crate) fn reversible_move(key: u64) -> Option<(Square, Square)> {
+    table().get(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::parse_uci_move;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_every_reversible_move_is_stored() {
+        let stored = table().moves.iter().filter(|mv| mv.is_some()).count();
+        assert_eq!(stored, 3668);
+    }
+
+    #[test]
+    fn test_reversible_move_from_hashes() {
+        let board =
+            Board::from_str("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        let mv = parse_uci_move(&board, "f3g1").unwrap();
+        let after = board.make_move_new(mv);
+        assert_eq!(
+            reversible_move(board.get_hash() ^ after.get_hash()),
+            Some((Square::G1, Square::F3))
+        );
+        // A pawn move is not reversible
+        let mv = parse_uci_move(&board, "a2a3").unwrap();
+        let after = board.make_move_new(mv);
+        assert_eq!(reversible_move(board.get_hash() ^ after.get_hash()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot place key")]
+    fn test_insert_gives_up_on_a_cycle() {
+        let mut table = Cuckoo {
+            keys: vec![0; TABLE_SIZE],
+            moves: vec![None; TABLE_SIZE],
+        };
+        // Keys whose two slots are both slot 0 evict each other forever
+        for i in 1..=3 {
+            table.insert(i << 40, (Square::A1, Square::B1));
+        }
+    }
+}
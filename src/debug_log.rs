@@ -0,0 +1,90 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// File named by the `Debug Log File` option, if one is open
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Print a line to stdout and mirror it into the debug log file
+#[macro_export]
+macro_rules! uci_println {
+    () => {
+        $crate::debug_log::send("")
+    };
+    ($($arg:tt)*) => {
+        $crate::debug_log::send(&format!($($arg)*))
+    };
+}
+
+/// Start appending protocol traffic to `path`. An empty path or the
+/// `<empty>` placeholder some GUIs send closes the log instead.
+pub fn open(path: &str) -> io::Result<()> {
+    let path = path.trim();
+    let file = if path.is_empty() || path == "<empty>" {
+        None
+    } else {
+        Some(OpenOptions::new().create(true).append(true).open(path)?)
+    };
+    *LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()) = file;
+    Ok(())
+}
+
+/// Whether a debug log file is currently open
+pub fn is_open() -> bool {
+    LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+fn write_line(prefix: &str, line: &str) {
+    let mut guard = LOG_FILE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(file) = guard.as_mut() {
+        let _ = writeln!(file, "{} {}", prefix, line);
+    }
+}
+
+/// Log a line received from the GUI
+pub fn received(line: &str) {
+    write_line("<<", line);
+}
+
+/// Print a line for the GUI and log it
+pub fn send(line: &str) {
+    println!("{}", line);
+    write_line(">>", line);
+}
+
+/// Log a note that is not part of the protocol, such as a search summary
+pub fn note(line: &str) {
+    write_line("##", line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_mirrors_traffic() {
+        let path = std::env::temp_dir().join(format!("xewali-debug-log-{}", std::process::id()));
+        let path_str = path.to_string_lossy().to_string();
+        let _ = std::fs::remove_file(&path);
+
+        open(&path_str).unwrap();
+        assert!(is_open());
+        received("isready");
+        send("readyok");
+        note("depth 1 nodes 20");
+        open("<empty>").unwrap();
+        assert!(!is_open());
+        send("not logged");
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(text.contains("<< isready\n"));
+        assert!(text.contains(">> readyok\n"));
+        assert!(text.contains("## depth 1 nodes 20\n"));
+        assert!(!text.contains("not logged"));
+    }
+}
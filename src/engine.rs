@@ -2,157 +2,1008 @@
 // Rust port: 2024
 // email: himangshu.saikia.iitg@gmail.com
 
-use chess::{Board, ChessMove, Color, MoveGen, Piece, EMPTY};
-use std::collections::HashMap;
+use chess::{
+    between, BitBoard, Board, CacheTable, ChessMove, Color, MoveGen, Piece, Rank, Square, EMPTY,
+};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crate::book::Book;
-use crate::evaluation::{eval, MATE_EVAL};
+use crate::adjudication;
+use crate::book::{self, Book};
+use crate::cuckoo;
+use crate::debug_log;
+use crate::evaluation::{self, is_mate_score, Accumulator, GameResult, MATE_EVAL};
+use crate::history::{MoveHistory, PriorMove, HISTORY_MAX};
+use crate::movepick::{MovePicker, Stage};
+use crate::moves;
+use crate::nnue::{self, Network};
+use crate::tt::{score_from_tt, score_to_tt, TTEntry, TTFlag, TranspositionTable};
+use crate::uci_println;
+use crate::variant::Variant;
 
-/// Maximum number of entries in the transposition table to cap memory usage.
-const MAX_TT_ENTRIES: usize = 1_000_000;
+/// Default transposition table size in megabytes (the `Hash` option).
+pub const DEFAULT_HASH_MB: usize = 32;
+
+/// Largest `Hash` value accepted, in megabytes.
+pub const MAX_HASH_MB: usize = 4096;
+
+/// Largest table `AutoHash` will pick, in megabytes.
+const AUTO_HASH_MAX_MB: usize = 1024;
+
+/// Alpha-beta bound beyond every score, mates included
+const INFINITE_SCORE: i32 = MATE_EVAL + 1;
+
+/// Nodes searched between two checks of the clock and the stop signal, unless
+/// calibrated for the machine
+pub const DEFAULT_TIME_CHECK_INTERVAL: u64 = 4096;
 
 /// Maximum depth for quiescence search to prevent infinite capture chains.
 const MAX_QUIESCENCE_DEPTH: i32 = 8;
 
-/// Null-move pruning reduction
-const NULL_MOVE_R: i32 = 2;
+/// Captures in quiescence that lose more than this by static exchange
+/// evaluation, in centipawns, are not searched.
+const QS_SEE_MARGIN: i32 = 50;
+
+/// Minimum remaining depth at which internal iterative deepening is used.
+const IID_MIN_DEPTH: i32 = 5;
+
+/// Halfmoves without a capture or pawn move after which the fifty-move rule
+/// draws the game
+const FIFTY_MOVE_PLIES: u32 = 100;
+
+/// Halfmove clock from which static evaluations fade towards the draw
+/// score, reaching it at `FIFTY_MOVE_PLIES`.
+const FIFTY_MOVE_FADE_START: u32 = 20;
+
+/// (name, default, min, max) of every tunable search parameter, in the order
+/// they are advertised as UCI options and written to the parameter file
+const SEARCH_PARAM_SPECS: [(&str, i32, i32, i32); 7] = [
+    ("NullMoveR", 2, 1, 4),
+    ("StaticNullMargin", 120, 0, 1000),
+    ("StaticNullMaxDepth", 3, 0, 8),
+    ("QSearchFutilityMargin", 200, 0, 1000),
+    ("LmrMinMoveIndex", 4, 1, 20),
+    ("FutilityMargin", 150, 0, 1000),
+    ("LmpMaxDepth", 2, 0, 8),
+];
+
+/// Pruning margins and reductions used by the search, tunable via UCI options
+/// and the parameter file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchParams {
+    /// Null-move pruning depth reduction
+    pub null_move_r: i32,
+    /// Static null-move (reverse futility) margin per ply of remaining depth, in centipawns
+    pub static_null_margin: i32,
+    /// Deepest remaining depth at which static null-move pruning is tried
+    pub static_null_max_depth: i32,
+    /// Quiescence futility (delta) margin added to the captured piece value, in centipawns
+    pub qsearch_futility_margin: i32,
+    /// Number of moves searched at full depth before late move reductions kick in
+    pub lmr_min_move_index: i32,
+    /// Futility pruning margin per ply of remaining depth (depths 1 and 2), in centipawns
+    pub futility_margin: i32,
+    /// Deepest remaining depth at which late quiet moves are pruned outright
+    pub lmp_max_depth: i32,
+}
 
-/// Transposition table bound type
-#[derive(Clone, Copy, PartialEq)]
-enum TTFlag {
-    Exact,
-    LowerBound,
-    UpperBound,
+impl Default for SearchParams {
+    fn default() -> Self {
+        let mut params = SearchParams {
+            null_move_r: 0,
+            static_null_margin: 0,
+            static_null_max_depth: 0,
+            qsearch_futility_margin: 0,
+            lmr_min_move_index: 0,
+            futility_margin: 0,
+            lmp_max_depth: 0,
+        };
+        for (name, default, _, _) in SEARCH_PARAM_SPECS {
+            params.set(name, default);
+        }
+        params
+    }
 }
 
-/// Transposition table entry
-#[derive(Clone)]
-struct TTEntry {
-    depth: i32,
-    eval: f64,
-    flag: TTFlag,
-    best_move: Option<ChessMove>,
+impl SearchParams {
+    fn field_mut(&mut self, name: &str) -> Option<&mut i32> {
+        match name {
+            "NullMoveR" => Some(&mut self.null_move_r),
+            "StaticNullMargin" => Some(&mut self.static_null_margin),
+            "StaticNullMaxDepth" => Some(&mut self.static_null_max_depth),
+            "QSearchFutilityMargin" => Some(&mut self.qsearch_futility_margin),
+            "LmrMinMoveIndex" => Some(&mut self.lmr_min_move_index),
+            "FutilityMargin" => Some(&mut self.futility_margin),
+            "LmpMaxDepth" => Some(&mut self.lmp_max_depth),
+            _ => None,
+        }
+    }
+
+    /// Set a parameter by name (case-insensitive), clamping to its allowed range.
+    /// Returns false if there is no parameter with that name.
+    pub fn set(&mut self, name: &str, value: i32) -> bool {
+        let Some(&(name, _, min, max)) = SEARCH_PARAM_SPECS
+            .iter()
+            .find(|(n, _, _, _)| n.eq_ignore_ascii_case(name))
+        else {
+            return false;
+        };
+        match self.field_mut(name) {
+            Some(field) => {
+                *field = value.clamp(min, max);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// UCI `option` declarations for all parameters
+    pub fn uci_options() -> Vec<String> {
+        SEARCH_PARAM_SPECS
+            .iter()
+            .map(|(name, default, min, max)| {
+                format!(
+                    "option name {} type spin default {} min {} max {}",
+                    name, default, min, max
+                )
+            })
+            .collect()
+    }
+
+    /// Serialize as `name value` lines for the parameter file
+    pub fn to_param_string(mut self) -> String {
+        SEARCH_PARAM_SPECS
+            .iter()
+            .filter_map(|(name, _, _, _)| {
+                let value = *self.field_mut(name)?;
+                Some(format!("{} {}\n", name, value))
+            })
+            .collect()
+    }
+
+    /// Apply `name value` lines from a parameter file. Unknown names, blank
+    /// lines and `#` comments are ignored.
+    pub fn load_param_string(&mut self, text: &str) {
+        for line in text.lines() {
+            let mut parts = line.split_whitespace();
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                if let Ok(value) = value.parse() {
+                    self.set(name, value);
+                }
+            }
+        }
+    }
+}
+
+/// Expected node type, used to decide how aggressively a node may be pruned
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum NodeType {
+    /// Searched with an open window; its score is exact
+    Pv,
+    /// Expected to fail high on one of its first moves
+    Cut,
+    /// Expected to fail low: every move has to be searched
+    All,
+}
+
+impl NodeType {
+    /// Expected type of the `index`-th child searched from a node of this type
+    fn child(self, index: usize) -> NodeType {
+        match (self, index) {
+            (NodeType::Pv, 0) => NodeType::Pv,
+            (NodeType::Pv, _) => NodeType::Cut,
+            (NodeType::Cut, 0) => NodeType::All,
+            (NodeType::Cut, _) => NodeType::Cut,
+            (NodeType::All, _) => NodeType::Cut,
+        }
+    }
+}
+
+/// Diagnostic counters collected during a search (reported in debug mode)
+#[derive(Default)]
+struct SearchStats {
+    tt_probes: u64,
+    tt_hits: u64,
+    null_move_tries: u64,
+    null_move_cutoffs: u64,
+    lmr_reductions: u64,
+    lmr_researches: u64,
+    /// Null window searches at PV nodes that had to be repeated with the full window
+    pvs_researches: u64,
+    /// Moves searched one ply deeper (pawn pushes to the 7th rank, promotions)
+    extensions: u64,
+    iid_searches: u64,
+    futility_prunes: u64,
+    late_move_prunes: u64,
+    /// Nodes of the main search that failed high, and those where the first
+    /// move already did, a measure of move ordering
+    beta_cutoffs: u64,
+    first_move_cutoffs: u64,
+    /// Nodes visited by quiescence search, evasions included
+    qs_nodes: u64,
+    /// Static evaluations asked for, and those found in the eval cache
+    eval_probes: u64,
+    eval_cache_hits: u64,
+}
+
+/// Entries in the eval cache, a power of two
+const EVAL_CACHE_ENTRIES: usize = 1 << 16;
+
+/// Static evaluations by position hash, so that a position reached again
+/// (by a transposition, or as the stand-pat score of quiescence after the
+/// main search evaluated it) isn't evaluated from scratch
+struct EvalCache {
+    table: CacheTable<i32>,
+    /// Variant whose rules the cached evaluations follow
+    variant: Variant,
+}
+
+impl EvalCache {
+    fn new() -> Self {
+        EvalCache {
+            table: CacheTable::new(EVAL_CACHE_ENTRIES, 0),
+            variant: Variant::Standard,
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = EvalCache {
+            variant: self.variant,
+            ..EvalCache::new()
+        };
+    }
+
+    /// The cached evaluation of `board` under `variant`'s rules, if any
+    fn get(&mut self, board: &Board, variant: Variant) -> Option<i32> {
+        if variant != self.variant {
+            self.clear();
+            self.variant = variant;
+            return None;
+        }
+        self.table.get(board.get_hash())
+    }
+
+    fn insert(&mut self, board: &Board, eval: i32) {
+        self.table.add(board.get_hash(), eval);
+    }
+}
+
+/// Search data that persists between moves of the same game
+pub struct EngineState {
+    transposition_table: TranspositionTable,
+    /// Quiet move histories, aged at the start of every search
+    history: MoveHistory,
+    pub params: SearchParams,
+    /// Nodes searched by the most recent call to `play_move`
+    pub nodes: u64,
+    /// Set from another thread to make a running search return its best move
+    stop_signal: Arc<AtomicBool>,
+    /// Nodes between time checks, a power of two
+    time_check_interval: u64,
+    eval_cache: EvalCache,
+    /// Network evaluating positions in place of the hand-written terms, from
+    /// the `EvalFile` option
+    network: Option<Arc<Network>>,
+    /// Path `network` was loaded from, empty without one
+    eval_file: String,
+}
+
+impl Default for EngineState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EngineState {
+    pub fn new() -> Self {
+        EngineState {
+            transposition_table: TranspositionTable::new(DEFAULT_HASH_MB),
+            history: MoveHistory::new(),
+            params: SearchParams::default(),
+            nodes: 0,
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            time_check_interval: DEFAULT_TIME_CHECK_INTERVAL,
+            eval_cache: EvalCache::new(),
+            network: None,
+            eval_file: String::new(),
+        }
+    }
+
+    /// Stand-in for this state while it is moved to a search thread: the
+    /// same settings and network, for the commands answered meanwhile, but
+    /// no transposition table allocated
+    pub fn placeholder(&self) -> Self {
+        EngineState {
+            transposition_table: TranspositionTable::unallocated(
+                self.hash_mb(),
+                self.tt_bucket_size(),
+            ),
+            history: MoveHistory::new(),
+            params: self.params,
+            nodes: 0,
+            stop_signal: Arc::clone(&self.stop_signal),
+            time_check_interval: self.time_check_interval,
+            eval_cache: EvalCache::new(),
+            network: self.network.clone(),
+            eval_file: self.eval_file.clone(),
+        }
+    }
+
+    /// Flag that stops a running search when set. It stays set until the
+    /// owner clears it, so clear it before starting the next search.
+    pub fn stop_signal(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop_signal)
+    }
+
+    /// Forget everything learned so far (called on `ucinewgame`)
+    pub fn clear(&mut self) {
+        self.transposition_table.clear();
+        self.history.clear();
+        self.eval_cache.clear();
+    }
+
+    pub fn network(&self) -> Option<&Arc<Network>> {
+        self.network.as_ref()
+    }
+
+    /// Static evaluation of `board` under `variant`'s rules, from White's
+    /// point of view, with the network if one is loaded as the search does
+    pub fn static_eval(&self, board: &Board, variant: Variant) -> i32 {
+        match &self.network {
+            Some(network) => variant.eval_network(
+                board,
+                network,
+                &nnue::Accumulator::new(network, board),
+                &Accumulator::new(board),
+                0,
+            ),
+            None => variant.eval(board, 0),
+        }
+    }
+
+    /// Path of the network set by the `EvalFile` option, empty without one
+    pub fn eval_file(&self) -> &str {
+        &self.eval_file
+    }
+
+    /// Evaluate with `network` from now on, or with the hand-written terms if
+    /// None. Cached evaluations and the transposition table's scores are
+    /// dropped, as they came from the other.
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.network = network;
+        self.eval_cache.clear();
+        self.transposition_table.clear();
+    }
+
+    /// Transposition table size in megabytes
+    pub fn hash_mb(&self) -> usize {
+        self.transposition_table.size_mb()
+    }
+
+    /// Resize the transposition table to `mb` megabytes, dropping its contents
+    pub fn set_hash_mb(&mut self, mb: usize) {
+        self.transposition_table = TranspositionTable::with_bucket_size(
+            mb.clamp(1, MAX_HASH_MB),
+            self.transposition_table.bucket_size(),
+        );
+    }
+
+    /// Entries sharing one index of the transposition table
+    pub fn tt_bucket_size(&self) -> usize {
+        self.transposition_table.bucket_size()
+    }
+
+    /// Rebuild the transposition table with buckets of `entries`, one of
+    /// `tt::BUCKET_SIZES`, dropping its contents if the size changes
+    pub fn set_tt_bucket_size(&mut self, entries: usize) {
+        if entries != self.tt_bucket_size() {
+            self.transposition_table =
+                TranspositionTable::with_bucket_size(self.hash_mb(), entries);
+        }
+    }
+
+    /// Transposition table occupancy in permille, as UCI's `hashfull`
+    pub fn hashfull(&self) -> usize {
+        hashfull(
+            self.transposition_table.len(),
+            self.transposition_table.capacity(),
+        )
+    }
+
+    pub fn time_check_interval(&self) -> u64 {
+        self.time_check_interval
+    }
+
+    /// Check the clock every `nodes` nodes, rounded down to a power of two
+    pub fn set_time_check_interval(&mut self, nodes: u64) {
+        let nodes = nodes.clamp(1, 1 << 20);
+        self.time_check_interval = 1 << nodes.ilog2();
+    }
+}
+
+/// Permille of a table of `capacity` entries holding `len` of them
+fn hashfull(len: usize, capacity: usize) -> usize {
+    (len * 1000 / capacity.max(1)).min(1000)
+}
+
+/// Table size `AutoHash` picks given the available memory: a quarter of it,
+/// capped so a large machine doesn't spend ages filling the table
+pub fn auto_hash_mb(available_mb: usize) -> usize {
+    (available_mb / 4).clamp(1, AUTO_HASH_MAX_MB)
+}
+
+/// Memory available to new processes in megabytes, read from
+/// `/proc/meminfo`. None where that isn't available.
+pub fn available_memory_mb() -> Option<usize> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb / 1024)
 }
 
 /// Shared search state passed through recursion
-struct SearchState {
-    transposition_table: HashMap<u64, TTEntry>,
+struct SearchState<'a> {
+    transposition_table: &'a TranspositionTable,
+    history: &'a mut MoveHistory,
+    eval_cache: &'a mut EvalCache,
+    stop_signal: &'a AtomicBool,
+    /// Hashes of the positions since the last irreversible move, from the
+    /// game up to the root and then along the current search path
     position_history: Vec<u64>,
+    /// Length of `position_history` at the root; entries from here on were
+    /// reached inside the search tree
+    root_history_len: usize,
+    /// Length of `position_history` at the last null move on the current
+    /// path, which no position beyond it can repeat
+    null_move_floor: usize,
+    /// Moves made since the root, for continuation history
+    move_stack: Vec<PriorMove>,
+    /// Material and piece-square sums of each position on the current path,
+    /// from the root down, updated move by move
+    accumulators: Vec<Accumulator>,
+    /// Network evaluating positions, if one is loaded
+    network: Option<&'a Network>,
+    /// Its hidden layer values of each position on the current path, kept
+    /// alongside `accumulators` (empty without a network)
+    nnue_accumulators: Vec<nnue::Accumulator>,
+    /// Halfmoves since the last capture or pawn move, for the fifty-move rule
+    halfmove_clock: u32,
+    /// Two most recent quiet moves per ply that caused a beta cutoff
+    killers: Vec<[Option<ChessMove>; 2]>,
+    /// Triangular PV table: the best line found from each ply of the current
+    /// path, built bottom-up as PV nodes raise alpha
+    pv_table: Vec<Vec<ChessMove>>,
+    /// Principal variation of the last completed iteration, tried first
+    /// along the path it describes
+    previous_pv: Vec<ChessMove>,
+    /// Whether every move on the current path so far is from `previous_pv`
+    follow_pv: bool,
     start: Instant,
+    /// Hard time limit, past which the search is aborted mid-iteration
     time_limit: Duration,
+    /// Node count at which the search is aborted mid-iteration
+    node_limit: u64,
+    /// Whether the limits and `stop` may abort the search, only once the
+    /// first iteration has completed so there is always a searched move to
+    /// play
+    abortable: bool,
+    /// Nodes per millisecond that the time limits are measured in (0 to
+    /// measure them in real time)
+    nodes_per_ms: u64,
     nodes: u64,
+    /// `time_check_interval - 1`, masking the node count between time checks
+    time_check_mask: u64,
+    /// Deepest ply reached, quiescence included
+    seldepth: i32,
     stopped: bool,
+    stats: SearchStats,
+    params: SearchParams,
+    /// Score of a repetition or stalemate (from White's point of view), which
+    /// includes the contempt of the side the engine is playing
+    draw_score: i32,
+    /// The engine's side and the percentage of its positional evaluation
+    /// the `Handicap` option takes away, if any
+    handicap: Option<(Color, i32)>,
+    variant: Variant,
+    output_format: OutputFormat,
+    /// Check every evaluation against the mirrored position's
+    check_symmetry: bool,
 }
 
-impl SearchState {
+impl SearchState<'_> {
+    /// Distance from the root of a node `qs_depth` plies into quiescence
+    /// below the current search node, null moves included
+    fn ply(&self, qs_depth: i32) -> i32 {
+        self.move_stack.len() as i32 + qs_depth
+    }
+
+    /// Record a node `qs_depth` plies into quiescence below the current
+    /// search node
+    fn note_ply(&mut self, qs_depth: i32) {
+        self.seldepth = self.seldepth.max(self.ply(qs_depth));
+    }
+
+    /// Material and piece-square sums of the current node
+    fn accumulator(&self) -> Accumulator {
+        *self
+            .accumulators
+            .last()
+            .expect("the root accumulator is never popped")
+    }
+
+    /// Update the material and piece-square sums for `mv` made on `board`,
+    /// undone by `pop_accumulator`
+    fn push_accumulator(&mut self, board: &Board, mv: ChessMove) {
+        let accumulator = self.accumulator().after_move(board, mv);
+        self.accumulators.push(accumulator);
+        if let Some(network) = self.network {
+            let nnue = self.nnue_accumulator().after_move(network, board, mv);
+            self.nnue_accumulators.push(nnue);
+        }
+    }
+
+    /// Keep the sums for a null move, which changes no piece
+    fn push_null_accumulator(&mut self) {
+        let accumulator = self.accumulator();
+        self.accumulators.push(accumulator);
+        if self.network.is_some() {
+            let nnue = *self.nnue_accumulator();
+            self.nnue_accumulators.push(nnue);
+        }
+    }
+
+    fn pop_accumulator(&mut self) {
+        self.accumulators.pop();
+        self.nnue_accumulators.pop();
+    }
+
+    /// Network hidden layer values of the current node
+    fn nnue_accumulator(&self) -> &nnue::Accumulator {
+        self.nnue_accumulators
+            .last()
+            .expect("kept from the root while a network is loaded")
+    }
+
+    /// Static evaluation under the variant's rules, including the handicap,
+    /// from the side to move's point of view, of a node `qs_depth` plies into
+    /// quiescence below the current search node
+    fn relative_eval(&mut self, board: &Board, qs_depth: i32) -> i32 {
+        // Mates are brought closer to zero the further they are from the
+        // root, so the shortest mate is preferred
+        let draw_eval = self.draw_score;
+        self.stats.eval_probes += 1;
+        let eval = if let Some(eval) = self.eval_cache.get(board, self.variant) {
+            self.stats.eval_cache_hits += 1;
+            eval
+        } else {
+            let eval = match self.network {
+                Some(network) => self.variant.eval_network(
+                    board,
+                    network,
+                    self.nnue_accumulator(),
+                    &self.accumulator(),
+                    draw_eval,
+                ),
+                None => self
+                    .variant
+                    .eval_accumulated(board, &self.accumulator(), draw_eval),
+            };
+            // Draw scores depend on the options of the search, which may
+            // change by the next one
+            if eval != draw_eval {
+                self.eval_cache.insert(board, eval);
+            }
+            if self.check_symmetry && self.variant == Variant::Standard {
+                if let Err(e) = evaluation::check_symmetry(board) {
+                    uci_println!("info string eval asymmetry in {}: {}", board, e);
+                }
+            }
+            eval
+        };
+        let eval = if eval.abs() >= MATE_EVAL {
+            eval - eval.signum() * self.ply(qs_depth)
+        } else if eval == draw_eval {
+            eval
+        } else {
+            let eval = match self.handicap {
+                Some(handicap) => handicapped(board, &self.accumulator(), eval, handicap),
+                None => eval,
+            };
+            self.fade_to_draw(eval, draw_eval, qs_depth)
+        };
+        if board.side_to_move() == Color::White {
+            eval
+        } else {
+            -eval
+        }
+    }
+
+    /// `eval` blended towards `draw_eval` as the halfmove clock runs on
+    /// towards the fifty-move rule, so the side that is better makes progress
+    /// (a pawn move or an exchange) while it still can rather than shuffle
+    /// until the draw. Positions in quiescence were reached by captures,
+    /// which reset the clock.
+    fn fade_to_draw(&self, eval: i32, draw_eval: i32, qs_depth: i32) -> i32 {
+        let clock = if qs_depth > 0 { 0 } else { self.halfmove_clock };
+        let span = (FIFTY_MOVE_PLIES - FIFTY_MOVE_FADE_START) as i32;
+        let remaining = FIFTY_MOVE_PLIES.saturating_sub(clock) as i32;
+        if remaining >= span {
+            eval
+        } else {
+            draw_eval + (eval - draw_eval) * remaining / span
+        }
+    }
+
+    /// Score of a repetition from the side to move's point of view
+    fn relative_draw_score(&self, board: &Board) -> i32 {
+        if board.side_to_move() == Color::White {
+            self.draw_score
+        } else {
+            -self.draw_score
+        }
+    }
+
+    /// Whether the position `key` reached at the current search node counts
+    /// as a draw by repetition. Only positions since the last capture, pawn
+    /// move or null move can recur. A single repetition of a position from
+    /// inside the search tree is a draw, as the side that could avoid it
+    /// would have done so, while a position from the game needs to have
+    /// occurred twice before for a true threefold repetition
+    fn is_repetition(&self, key: u64) -> bool {
+        let len = self.position_history.len();
+        let start = len
+            .saturating_sub(self.halfmove_clock as usize)
+            .max(self.null_move_floor);
+        let mut game_repeats = 0;
+        for (i, &h) in self.position_history.iter().enumerate().skip(start) {
+            if h == key {
+                if i >= self.root_history_len {
+                    return true;
+                }
+                game_repeats += 1;
+            }
+        }
+        game_repeats >= 2
+    }
+
+    /// Whether the side to move in `board` can reach a repetition with its
+    /// next move, with the same positions counting as in `is_repetition`
+    fn has_upcoming_repetition(&self, board: &Board) -> bool {
+        let len = self.position_history.len();
+        let start = len
+            .saturating_sub(self.halfmove_clock as usize)
+            .max(self.null_move_floor);
+        upcoming_repetition(
+            board,
+            &self.position_history[start..],
+            self.root_history_len.saturating_sub(start),
+        )
+    }
+
+    /// Killer moves of the current search node's ply
+    fn killers(&self) -> [Option<ChessMove>; 2] {
+        let ply = self.ply(0) as usize;
+        self.killers.get(ply).copied().unwrap_or_default()
+    }
+
+    /// Remember the quiet move `mv` that caused a beta cutoff at the current
+    /// search node, pushing out the older of the ply's two killers
+    fn store_killer(&mut self, mv: ChessMove) {
+        let ply = self.ply(0) as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+    }
+
+    /// Forget the line from the current search node, which has yet to find one
+    fn clear_pv(&mut self) {
+        let ply = self.ply(0) as usize;
+        if self.pv_table.len() <= ply + 1 {
+            self.pv_table.resize(ply + 2, Vec::new());
+        }
+        self.pv_table[ply].clear();
+    }
+
+    /// Make `mv` followed by the line of the node it leads to the line from
+    /// the current search node
+    fn update_pv(&mut self, mv: ChessMove) {
+        let ply = self.ply(0) as usize;
+        let (line, rest) = self.pv_table[ply..].split_at_mut(1);
+        line[0].clear();
+        line[0].push(mv);
+        line[0].extend_from_slice(&rest[0]);
+    }
+
+    /// The line from the root when playing `mv` there, after searching it
+    fn root_pv(&self, mv: ChessMove) -> Vec<ChessMove> {
+        let mut pv = vec![mv];
+        pv.extend(self.pv_table.get(1).into_iter().flatten());
+        pv
+    }
+
+    /// The previous iteration's move at the current search node, if the path
+    /// here has followed its principal variation
+    fn previous_pv_move(&mut self, board: &Board) -> Option<ChessMove> {
+        if !self.follow_pv {
+            return None;
+        }
+        let ply = self.ply(0) as usize;
+        let mv = self
+            .previous_pv
+            .get(ply)
+            .copied()
+            .filter(|&mv| board.legal(mv));
+        self.follow_pv = mv.is_some();
+        mv
+    }
+
+    /// Time spent searching, as counted against the time limits: real time,
+    /// or with `nodes_per_ms` set, the nodes searched converted to time
+    fn elapsed(&self) -> Duration {
+        match (self.nodes * 1000).checked_div(self.nodes_per_ms) {
+            Some(micros) => Duration::from_micros(micros),
+            None => self.start.elapsed(),
+        }
+    }
+
     fn check_time(&mut self) {
         self.nodes += 1;
-        if self.nodes & 4095 == 0 && self.start.elapsed() > self.time_limit {
+        if self.abortable
+            && (self.nodes >= self.node_limit
+                || (self.nodes & self.time_check_mask == 0
+                    && (self.elapsed() > self.time_limit
+                        || self.stop_signal.load(Ordering::Relaxed))))
+        {
             self.stopped = true;
         }
     }
 }
 
+/// `eval` of `board` (from White's point of view) with the engine playing
+/// `color` and missing `percent` of its positional evaluation: its side's
+/// piece placement, mobility, king safety and pawn structure count for less,
+/// while the opponent's count in full, so it still plays coherent plans,
+/// only more crudely
+fn handicapped(
+    board: &Board,
+    accumulator: &Accumulator,
+    eval: i32,
+    (color, percent): (Color, i32),
+) -> i32 {
+    let positional = evaluation::accumulated_breakdown(board, accumulator).positional(color);
+    let penalty = positional * percent / 100;
+    if color == Color::White {
+        eval - penalty
+    } else {
+        eval + penalty
+    }
+}
+
+/// Square a pawn of the side to move lands on when capturing en passant.
+/// `Board::en_passant` gives the square of the pawn to be taken instead.
+pub(crate) fn en_passant_target(board: &Board) -> Option<Square> {
+    board
+        .en_passant()
+        .and_then(|sq| sq.forward(board.side_to_move()))
+}
+
+/// Whether `mv` is an en passant capture (called BEFORE making the move)
+fn is_en_passant(board: &Board, mv: ChessMove) -> bool {
+    en_passant_target(board) == Some(mv.get_dest())
+        && board.piece_on(mv.get_source()) == Some(Piece::Pawn)
+}
+
+/// Piece `mv` captures, a pawn for en passant (called BEFORE making the move)
+pub(crate) fn captured_piece(board: &Board, mv: ChessMove) -> Option<Piece> {
+    board
+        .piece_on(mv.get_dest())
+        .or_else(|| is_en_passant(board, mv).then_some(Piece::Pawn))
+}
+
 /// Check if a move is a capture (called BEFORE making the move)
-fn is_capture(board: &Board, mv: ChessMove) -> bool {
-    if board.piece_on(mv.get_dest()).is_some() {
-        return true;
-    }
-    if let Some(ep_sq) = board.en_passant() {
-        if mv.get_dest() == ep_sq {
-            if let Some(piece) = board.piece_on(mv.get_source()) {
-                if piece == Piece::Pawn {
-                    return true;
-                }
-            }
+pub(crate) fn is_capture(board: &Board, mv: ChessMove) -> bool {
+    captured_piece(board, mv).is_some()
+}
+
+/// Optimistic material gain of a capture (captured piece plus any promotion)
+fn capture_gain(board: &Board, mv: ChessMove) -> i32 {
+    let victim = board.piece_on(mv.get_dest()).unwrap_or(Piece::Pawn);
+    let promotion = mv
+        .get_promotion()
+        .map_or(0, |p| piece_order_value(p) - piece_order_value(Piece::Pawn));
+    piece_order_value(victim) + promotion
+}
+
+/// Legal moves that capture an enemy piece. The iterator mask keeps movegen
+/// from producing quiet moves at all, bar pieces other than pawns moving to
+/// the empty square an en passant capture lands on.
+fn capture_moves(board: &Board) -> impl Iterator<Item = ChessMove> + '_ {
+    let mut mask = *board.color_combined(!board.side_to_move());
+    let en_passant = en_passant_target(board);
+    if let Some(sq) = en_passant {
+        mask |= BitBoard::from_square(sq);
+    }
+    let mut movegen = MoveGen::new_legal(board);
+    movegen.set_iterator_mask(mask);
+    movegen.filter(move |&mv| Some(mv.get_dest()) != en_passant || is_en_passant(board, mv))
+}
+
+/// Pieces of either color attacking `sq` given the pieces in `occupied`, so
+/// that sliders behind pieces already removed are seen
+fn attackers_to(board: &Board, sq: Square, occupied: BitBoard) -> BitBoard {
+    let white = *board.color_combined(Color::White);
+    let black = *board.color_combined(Color::Black);
+    let pawns = *board.pieces(Piece::Pawn);
+    let bishops_queens = *board.pieces(Piece::Bishop) | *board.pieces(Piece::Queen);
+    let rooks_queens = *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+
+    let attackers = chess::get_pawn_attacks(sq, Color::Black, pawns & white)
+        | chess::get_pawn_attacks(sq, Color::White, pawns & black)
+        | chess::get_knight_moves(sq) & *board.pieces(Piece::Knight)
+        | chess::get_bishop_moves(sq, occupied) & bishops_queens
+        | chess::get_rook_moves(sq, occupied) & rooks_queens
+        | chess::get_king_moves(sq) & *board.pieces(Piece::King);
+    attackers & occupied
+}
+
+/// Static exchange evaluation: material the side to move wins (negative if
+/// it loses) when both sides keep recapturing on the destination of `mv`
+/// with their cheapest piece, each free to stop when that is better. Pins
+/// are ignored.
+fn see(board: &Board, mv: ChessMove) -> i32 {
+    const ORDER: [Piece; 6] = [
+        Piece::Pawn,
+        Piece::Knight,
+        Piece::Bishop,
+        Piece::Rook,
+        Piece::Queen,
+        Piece::King,
+    ];
+    let to = mv.get_dest();
+    let mut gains = vec![board.piece_on(to).map_or(0, piece_order_value)];
+    let mut on_square = board.piece_on(mv.get_source()).unwrap_or(Piece::Pawn);
+    let mut occupied = *board.combined() ^ BitBoard::from_square(mv.get_source());
+    let mut side = !board.side_to_move();
+
+    loop {
+        let ours = attackers_to(board, to, occupied) & *board.color_combined(side);
+        let Some((piece, from)) = ORDER.iter().find_map(|&piece| {
+            let bb = ours & *board.pieces(piece);
+            (bb != EMPTY).then(|| (piece, bb.to_square()))
+        }) else {
+            break;
+        };
+        // Gain of this recapture, assuming the opponent then replies in turn
+        let gain = piece_order_value(on_square) - gains.last().unwrap();
+        gains.push(gain);
+        occupied ^= BitBoard::from_square(from);
+        on_square = piece;
+        side = !side;
+    }
+
+    // Each side only recaptures if that beats stopping
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap();
+        let previous = gains.last_mut().unwrap();
+        *previous = -(-*previous).max(last);
+    }
+    gains[0]
+}
+
+/// Whether a capture clearly loses material. Taking a piece worth at least
+/// the capturer can't, which spares the exchange evaluation.
+pub(crate) fn loses_exchange(board: &Board, mv: ChessMove) -> bool {
+    let victim = captured_piece(board, mv).map_or(0, piece_order_value);
+    let attacker = board.piece_on(mv.get_source()).map_or(0, piece_order_value);
+    victim < attacker && see(board, mv) < -QS_SEE_MARGIN
+}
+
+/// Whether a capture wins material, with every recapture played out
+pub(crate) fn wins_exchange(board: &Board, mv: ChessMove) -> bool {
+    see(board, mv) > 0
+}
+
+/// Quiescence search of a position in check: all evasions are searched, and
+/// having none is scored by the variant's rules (checkmate in chess)
+fn quiescence_evasions(
+    board: &Board,
+    mut alpha: i32,
+    beta: i32,
+    qs_depth: i32,
+    state: &mut SearchState,
+) -> i32 {
+    let moves = state.variant.legal_moves(board);
+    if moves.is_empty() {
+        return state.relative_eval(board, qs_depth);
+    }
+    let mut scored_moves: Vec<(ChessMove, i32)> = moves
+        .into_iter()
+        .map(|mv| (mv, score_evasion(board, mv, None)))
+        .collect();
+    scored_moves.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+
+    for (mv, _) in scored_moves {
+        let new_board = board.make_move_new(mv);
+        state.push_accumulator(board, mv);
+        let score = -quiescence(&new_board, -beta, -alpha, qs_depth + 1, state);
+        state.pop_accumulator();
+        if state.stopped {
+            return 0;
+        }
+        if score >= beta {
+            return beta;
         }
+        alpha = alpha.max(score);
     }
-    false
+    alpha
 }
 
 /// Quiescence search: only evaluate captures to avoid horizon effect
 fn quiescence(
     board: &Board,
-    mut alpha: f64,
-    beta: f64,
+    mut alpha: i32,
+    beta: i32,
     qs_depth: i32,
     state: &mut SearchState,
-) -> f64 {
+) -> i32 {
     if state.stopped {
-        return 0.0;
+        return 0;
     }
     state.check_time();
     if state.stopped {
-        return 0.0;
+        return 0;
+    }
+
+    state.note_ply(qs_depth);
+    state.stats.qs_nodes += 1;
+    // In check there is no standing pat: every evasion has to be looked at
+    if qs_depth < MAX_QUIESCENCE_DEPTH && *board.checkers() != EMPTY {
+        return quiescence_evasions(board, alpha, beta, qs_depth, state);
     }
 
-    let stand_pat = eval(board);
+    let stand_pat = state.relative_eval(board, qs_depth);
 
     if qs_depth >= MAX_QUIESCENCE_DEPTH {
         return stand_pat;
     }
+    if stand_pat >= beta {
+        return beta;
+    }
+    alpha = alpha.max(stand_pat);
 
-    let white_to_move = board.side_to_move() == Color::White;
-
-    if white_to_move {
-        if stand_pat >= beta {
-            return beta;
-        }
-        if stand_pat > alpha {
-            alpha = stand_pat;
-        }
-
-        let movegen = MoveGen::new_legal(board);
-        for mv in movegen {
-            if !is_capture(board, mv) {
-                continue;
-            }
-            let new_board = board.make_move_new(mv);
-            let score = quiescence(&new_board, alpha, beta, qs_depth + 1, state);
-            if state.stopped {
-                return 0.0;
-            }
-            if score >= beta {
-                return beta;
-            }
-            if score > alpha {
-                alpha = score;
-            }
+    let futility_margin = state.params.qsearch_futility_margin;
+    for mv in capture_moves(board) {
+        // Futility (delta) pruning: skip captures that can't raise alpha
+        if stand_pat + capture_gain(board, mv) + futility_margin < alpha {
+            continue;
         }
-        alpha
-    } else {
-        let mut beta = beta;
-        if stand_pat <= alpha {
-            return alpha;
+        if loses_exchange(board, mv) {
+            continue;
         }
-        if stand_pat < beta {
-            beta = stand_pat;
+        let new_board = board.make_move_new(mv);
+        state.push_accumulator(board, mv);
+        let score = -quiescence(&new_board, -beta, -alpha, qs_depth + 1, state);
+        state.pop_accumulator();
+        if state.stopped {
+            return 0;
         }
-
-        let movegen = MoveGen::new_legal(board);
-        for mv in movegen {
-            if !is_capture(board, mv) {
-                continue;
-            }
-            let new_board = board.make_move_new(mv);
-            let score = quiescence(&new_board, alpha, beta, qs_depth + 1, state);
-            if state.stopped {
-                return 0.0;
-            }
-            if score <= alpha {
-                return alpha;
-            }
-            if score < beta {
-                beta = score;
-            }
+        if score >= beta {
+            return beta;
         }
-        beta
+        alpha = alpha.max(score);
     }
+    alpha
 }
 
 /// Get the material value of a piece for move ordering
-fn piece_order_value(piece: Piece) -> i32 {
+pub(crate) fn piece_order_value(piece: Piece) -> i32 {
     match piece {
         Piece::Pawn => 100,
         Piece::Knight => 320,
@@ -163,8 +1014,15 @@ fn piece_order_value(piece: Piece) -> i32 {
     }
 }
 
-/// Score a move for ordering. Higher scores are searched first.
-fn score_move(board: &Board, mv: ChessMove, tt_move: Option<ChessMove>) -> i32 {
+/// Score a move for ordering. Higher scores are searched first. Quiet moves
+/// score at most 0, ordered among themselves by their history.
+pub(crate) fn score_move(
+    board: &Board,
+    mv: ChessMove,
+    tt_move: Option<ChessMove>,
+    history: &MoveHistory,
+    priors: &[PriorMove],
+) -> i32 {
     // TT best move gets highest priority
     if tt_move == Some(mv) {
         return 100_000;
@@ -178,22 +1036,133 @@ fn score_move(board: &Board, mv: ChessMove, tt_move: Option<ChessMove>) -> i32 {
     }
 
     // Captures scored by MVV-LVA
-    if let Some(victim) = board.piece_on(mv.get_dest()) {
+    if let Some(victim) = captured_piece(board, mv) {
         let attacker = board.piece_on(mv.get_source()).unwrap_or(Piece::Pawn);
         score += piece_order_value(victim) * 10 - piece_order_value(attacker);
-    } else if let Some(ep_sq) = board.en_passant() {
-        if mv.get_dest() == ep_sq {
-            if let Some(piece) = board.piece_on(mv.get_source()) {
-                if piece == Piece::Pawn {
-                    score += 100 * 10 - 100; // pawn captures pawn
-                }
-            }
-        }
     }
 
+    if mv.get_promotion().is_none() && !is_capture(board, mv) {
+        score = history.quiet_score(board, mv, priors) - HISTORY_MAX;
+    }
     score
 }
 
+/// Kinds of check evasion, in the order they are tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Evasion {
+    /// Captures the checking piece
+    Capture,
+    /// Interposes on a square the opponent does not attack
+    Block,
+    /// Moves the king
+    KingMove,
+    /// Interposes a piece the opponent can simply take
+    LosingBlock,
+}
+
+fn classify_evasion(board: &Board, mv: ChessMove) -> Evasion {
+    let takes_checker = *board.checkers() & BitBoard::from_square(mv.get_dest()) != EMPTY;
+    if board.piece_on(mv.get_source()) == Some(Piece::King) {
+        return if takes_checker {
+            Evasion::Capture
+        } else {
+            Evasion::KingMove
+        };
+    }
+    // En passant evades by taking the checking pawn, bar rare discovered checks
+    if takes_checker || is_capture(board, mv) {
+        return Evasion::Capture;
+    }
+
+    // Judged after the move, so the blocker does not count as its own defender
+    let after = board.make_move_new(mv);
+    let us = board.side_to_move();
+    if evaluation::attacks_square(&after, mv.get_dest(), !us)
+        && !evaluation::attacks_square(&after, mv.get_dest(), us)
+    {
+        Evasion::LosingBlock
+    } else {
+        Evasion::Block
+    }
+}
+
+/// Score a check evasion for ordering. Captures of the checker come first
+/// (cheapest capturing piece first, the king counting as cheapest since a
+/// legal king capture is safe), then blocks by the cheapest piece, then king
+/// moves (those that win material first), and finally blocks that drop the
+/// interposed piece.
+pub(crate) fn score_evasion(board: &Board, mv: ChessMove, tt_move: Option<ChessMove>) -> i32 {
+    if tt_move == Some(mv) {
+        return 100_000;
+    }
+    let mover = board.piece_on(mv.get_source()).unwrap_or(Piece::Pawn);
+    let mover_value = if mover == Piece::King {
+        0
+    } else {
+        piece_order_value(mover)
+    };
+    let victim_value = board.piece_on(mv.get_dest()).map_or(0, piece_order_value);
+    let promotion = mv.get_promotion().map_or(0, piece_order_value);
+
+    match classify_evasion(board, mv) {
+        Evasion::Capture => 60_000 + victim_value * 10 - mover_value + promotion,
+        Evasion::Block => 40_000 - mover_value + promotion,
+        Evasion::KingMove => 20_000 + victim_value,
+        Evasion::LosingBlock => -mover_value + promotion,
+    }
+}
+
+/// Every legal move in the order the search would try it, with its ordering
+/// score and where that score comes from
+pub fn ordered_moves(
+    board: &Board,
+    engine_state: &EngineState,
+    variant: Variant,
+) -> Vec<(ChessMove, i32, &'static str)> {
+    let tt_move = engine_state
+        .transposition_table
+        .get(board.get_hash())
+        .and_then(|entry| entry.best_move);
+    let in_check = *board.checkers() != EMPTY;
+    let history = &engine_state.history;
+    let mut picker = MovePicker::new(board, variant, tt_move, [None; 2], None, history, &[]);
+    let mut moves = Vec::new();
+    while let Some((mv, score)) = picker.next(board, history, &[]) {
+        let source = match picker.last_stage() {
+            Stage::TtMove => "tt move",
+            Stage::Killers => "killer",
+            Stage::LosingCaptures => "losing capture",
+            _ if tt_move == Some(mv) => "tt move",
+            _ if in_check => match classify_evasion(board, mv) {
+                Evasion::Capture => "evasion: capture checker",
+                Evasion::Block => "evasion: block",
+                Evasion::KingMove => "evasion: king move",
+                Evasion::LosingBlock => "evasion: losing block",
+            },
+            _ => match (mv.get_promotion().is_some(), is_capture(board, mv)) {
+                (true, true) => "promotion + mvv-lva",
+                (true, false) => "promotion",
+                (false, true) => "mvv-lva",
+                (false, false) if history.quiet_score(board, mv, &[]) > 0 => "history",
+                (false, false) => "quiet",
+            },
+        };
+        moves.push((mv, score, source));
+    }
+    moves
+}
+
+/// Whether `mv` pushes a pawn to the side to move's 7th rank. Such a pawn is
+/// always passed, as no enemy pawn can stand in front of it.
+fn pushes_pawn_to_seventh(board: &Board, mv: ChessMove) -> bool {
+    let seventh = if board.side_to_move() == Color::White {
+        Rank::Seventh
+    } else {
+        Rank::Second
+    };
+    board.piece_on(mv.get_source()) == Some(Piece::Pawn) && mv.get_dest().get_rank() == seventh
+}
+
 /// Check if a side has non-pawn material (used for null-move pruning safety)
 fn has_non_pawn_material(board: &Board, color: Color) -> bool {
     let our_pieces = *board.color_combined(color);
@@ -204,261 +1173,1074 @@ fn has_non_pawn_material(board: &Board, color: Color) -> bool {
     (knights | bishops | rooks | queens) != EMPTY
 }
 
-/// Negamax search with alpha-beta pruning, null-move pruning, and LMR
+/// Halfmove clock after `mv`, which resets it if it is a capture or pawn move
+fn next_halfmove_clock(board: &Board, mv: ChessMove, clock: u32) -> u32 {
+    if is_capture(board, mv) || board.piece_on(mv.get_source()) == Some(Piece::Pawn) {
+        0
+    } else {
+        clock + 1
+    }
+}
+
+/// Whether some position in `history` (the hashes of the positions that
+/// led to `board`, since the last irreversible move) an odd number of plies
+/// back is one reversible move away from `board`, so the side to move can
+/// repeat it. Positions from `root_len` on were reached in the search tree,
+/// where a repetition is a draw; one from the game must have occurred twice
+/// already, and be reached by a move of the side to move.
+fn upcoming_repetition(board: &Board, history: &[u64], root_len: usize) -> bool {
+    let key = board.get_hash();
+    let occupied = *board.combined();
+    // The last entry is the parent, one ply back; look from three plies back
+    for index in (0..history.len().saturating_sub(2)).rev().step_by(2) {
+        let earlier = history[index];
+        let Some((from, to)) = cuckoo::reversible_move(key ^ earlier) else {
+            continue;
+        };
+        if between(from, to) & occupied != EMPTY {
+            continue;
+        }
+        if index >= root_len {
+            return true;
+        }
+        let square = if board.piece_on(from).is_some() {
+            from
+        } else {
+            to
+        };
+        if board.color_on(square) == Some(board.side_to_move())
+            && history[..index].contains(&earlier)
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// Number of moves searched at a node `depth` plies from the horizon before
+/// late quiet moves are pruned
+fn late_move_count(depth: i32) -> usize {
+    (3 + depth * depth) as usize
+}
+
+/// Negamax search with alpha-beta pruning, null-move pruning, and LMR. Scores
+/// are from the side to move's point of view.
 fn search(
     board: &Board,
-    mut alpha: f64,
-    mut beta: f64,
+    mut alpha: i32,
+    beta: i32,
     depth: i32,
     allow_null: bool,
+    node_type: NodeType,
     state: &mut SearchState,
-) -> f64 {
+) -> i32 {
     if state.stopped {
-        return 0.0;
+        return 0;
     }
     state.check_time();
     if state.stopped {
-        return 0.0;
+        return 0;
     }
 
+    state.note_ply(0);
+    state.clear_pv();
     let key = board.get_hash();
 
-    // Repetition detection: need position to appear 2+ times in history for 3-fold
-    if state.position_history.iter().filter(|&&h| h == key).count() >= 2 {
-        return 0.0;
+    if state.is_repetition(key) {
+        return state.relative_draw_score(board);
+    }
+
+    // Fifty-move rule, unless the move that completed it mated
+    if state.halfmove_clock >= FIFTY_MOVE_PLIES
+        && state.variant.game_result(board) == GameResult::Ongoing
+    {
+        return state.relative_draw_score(board);
+    }
+
+    // Neither side can mate, however the game goes on
+    if state.variant == Variant::Standard && evaluation::is_insufficient_material(board) {
+        return state.relative_draw_score(board);
+    }
+
+    // A side that can force a repetition with its next move is sure of a
+    // draw, unless the variant's rules (compulsory captures) forbid the move
+    let draw_score = state.relative_draw_score(board);
+    if alpha < draw_score
+        && state.variant == Variant::Standard
+        && state.has_upcoming_repetition(board)
+    {
+        alpha = draw_score;
+        if alpha >= beta {
+            return alpha;
+        }
     }
 
     // Probe transposition table
     let mut tt_move: Option<ChessMove> = None;
-    if let Some(entry) = state.transposition_table.get(&key) {
+    state.stats.tt_probes += 1;
+    if let Some(entry) = state.transposition_table.get(key) {
+        state.stats.tt_hits += 1;
         tt_move = entry.best_move;
-        if entry.depth >= depth {
+        // PV nodes are searched anyway, so the reported line is not cut short
+        if entry.depth >= depth && node_type != NodeType::Pv {
+            let eval = score_from_tt(entry.eval, state.ply(0));
             match entry.flag {
-                TTFlag::Exact => return entry.eval,
+                TTFlag::Exact => return eval,
                 TTFlag::LowerBound => {
-                    if entry.eval >= beta {
-                        return entry.eval;
+                    if eval >= beta {
+                        return eval;
                     }
                 }
                 TTFlag::UpperBound => {
-                    if entry.eval <= alpha {
-                        return entry.eval;
+                    if eval <= alpha {
+                        return eval;
                     }
                 }
             }
         }
     }
+    // Along the previous iteration's principal variation, its move is
+    // searched first
+    if let Some(mv) = state.previous_pv_move(board) {
+        tt_move = Some(mv);
+    }
 
     // At depth 0, enter quiescence search
     if depth <= 0 {
         return quiescence(board, alpha, beta, 0, state);
     }
 
-    let white_to_move = board.side_to_move() == Color::White;
     let in_check = *board.checkers() != EMPTY;
 
-    // Null-move pruning
-    if allow_null && !in_check && depth >= 3 && has_non_pawn_material(board, board.side_to_move()) {
+    // Static null-move (reverse futility) pruning: if the static eval beats the
+    // bound by a depth-scaled margin, assume the opponent can't recover. Not
+    // at PV nodes, whose score and best move make up the reported line.
+    if !in_check
+        && node_type != NodeType::Pv
+        && depth <= state.params.static_null_max_depth
+        && !is_mate_score(beta)
+    {
+        let margin = state.params.static_null_margin * depth;
+        if state.relative_eval(board, 0) - margin >= beta {
+            return beta;
+        }
+    }
+
+    // Null-move pruning, never at PV nodes where the exact score matters
+    let mut threat = None;
+    if allow_null
+        && node_type != NodeType::Pv
+        && !in_check
+        && depth >= 3
+        && has_non_pawn_material(board, board.side_to_move())
+    {
         if let Some(null_board) = board.null_move() {
-            let null_score = search(
+            state.stats.null_move_tries += 1;
+            state.move_stack.push(None);
+            state.push_null_accumulator();
+            state.halfmove_clock += 1;
+            let parent_null_move_floor = state.null_move_floor;
+            state.null_move_floor = state.position_history.len();
+            let null_score = -search(
                 &null_board,
-                alpha,
-                beta,
-                depth - 1 - NULL_MOVE_R,
+                -beta,
+                -alpha,
+                depth - 1 - state.params.null_move_r,
                 false,
+                NodeType::All,
                 state,
             );
+            state.move_stack.pop();
+            state.pop_accumulator();
+            state.halfmove_clock -= 1;
+            state.null_move_floor = parent_null_move_floor;
             if state.stopped {
-                return 0.0;
+                return 0;
             }
             // Beta cutoff: if even passing gives a score >= beta, this position is too good
-            if white_to_move && null_score >= beta {
+            if null_score >= beta {
+                state.stats.null_move_cutoffs += 1;
                 return beta;
             }
-            if !white_to_move && null_score <= alpha {
-                return alpha;
-            }
+            // The opponent's refutation of passing, stored by the null
+            // search, is a threat: if it wins one of our pieces, moves that
+            // take that piece out of the way are tried early
+            threat = state
+                .transposition_table
+                .get(null_board.get_hash())
+                .and_then(|entry| entry.best_move)
+                .filter(|&mv| captured_piece(&null_board, mv).is_some());
         }
     }
 
-    let movegen = MoveGen::new_legal(board);
-    let mut moves: Vec<ChessMove> = movegen.collect();
-
-    // No legal moves: checkmate or stalemate
-    if moves.is_empty() {
-        return eval(board);
+    // Internal iterative deepening: without a TT move at a PV or cut node, a
+    // shallower search finds one to try first
+    if tt_move.is_none() && node_type != NodeType::All && depth >= IID_MIN_DEPTH {
+        state.stats.iid_searches += 1;
+        search(board, alpha, beta, depth - 2, false, node_type, state);
+        if state.stopped {
+            return 0;
+        }
+        tt_move = state
+            .transposition_table
+            .get(key)
+            .and_then(|entry| entry.best_move);
     }
 
-    // Move ordering: score and sort moves
-    let mut scored_moves: Vec<(ChessMove, i32)> = moves
-        .iter()
-        .map(|&mv| (mv, score_move(board, mv, tt_move)))
-        .collect();
-    scored_moves.sort_by(|a, b| b.1.cmp(&a.1));
-    moves = scored_moves.into_iter().map(|(mv, _)| mv).collect();
+    // Moves come in stages, each generated and ordered only when reached
+    state.clear_pv();
+    let mut picker = MovePicker::new(
+        board,
+        state.variant,
+        tt_move,
+        state.killers(),
+        threat,
+        state.history,
+        &state.move_stack,
+    );
 
     let original_alpha = alpha;
-    let original_beta = beta;
-    let mut best_eval = if white_to_move {
-        f64::NEG_INFINITY
+    let mut best_eval = -INFINITE_SCORE;
+    let mut best_move = None;
+
+    // Futility pruning: at frontier nodes, the score a quiet move can be
+    // expected to reach at most (static eval plus a depth-scaled margin)
+    let futility_eval = if depth <= 2
+        && !in_check
+        && node_type != NodeType::Pv
+        && !is_mate_score(alpha)
+        && !is_mate_score(beta)
+    {
+        let margin = state.params.futility_margin * depth;
+        Some(state.relative_eval(board, 0) + margin)
     } else {
-        f64::INFINITY
+        None
     };
-    let mut best_move = moves[0];
 
-    for (i, mv) in moves.iter().enumerate() {
-        let capture = is_capture(board, *mv);
+    let mut move_count = 0;
+    while let Some((mv, _)) = picker.next(board, state.history, &state.move_stack) {
+        let i = move_count;
+        move_count += 1;
+        let capture = is_capture(board, mv);
         let is_promotion = mv.get_promotion().is_some();
-        let new_board = board.make_move_new(*mv);
-        state.position_history.push(key);
-
-        // Late Move Reductions
-        let mut score;
+        let piece = board.piece_on(mv.get_source()).unwrap_or(Piece::Pawn);
+        let quiet_history = if capture || is_promotion {
+            0
+        } else {
+            state.history.quiet_score(board, mv, &state.move_stack)
+        };
+        let new_board = board.make_move_new(mv);
         let gives_check = *new_board.checkers() != EMPTY;
-        let do_lmr = i >= 4 && depth >= 3 && !capture && !in_check && !is_promotion && !gives_check;
+        let pawn_push = pushes_pawn_to_seventh(board, mv);
+
+        // Quiet moves may be pruned below, but never the first move, so the
+        // node always has a searched score
+        let prunable_quiet = i > 0 && !capture && !is_promotion && !gives_check && !pawn_push;
+
+        // Late move pruning: well-ordered quiet moves this late are unlikely
+        // to matter near the horizon
+        if prunable_quiet
+            && !in_check
+            && node_type != NodeType::Pv
+            && depth <= state.params.lmp_max_depth
+            && i >= late_move_count(depth)
+        {
+            state.stats.late_move_prunes += 1;
+            continue;
+        }
 
-        if do_lmr {
-            // Reduced depth search
-            score = search(&new_board, alpha, beta, depth - 2, true, state);
-            if state.stopped {
-                state.position_history.pop();
-                return 0.0;
-            }
-            // Re-search at full depth if reduced search improves alpha
-            let needs_research = if white_to_move {
-                score > alpha
-            } else {
-                score < beta
-            };
-            if needs_research {
-                score = search(&new_board, alpha, beta, depth - 1, true, state);
+        // Skip quiet moves that cannot reach the bound
+        if let Some(futility_eval) = futility_eval {
+            if futility_eval <= alpha && prunable_quiet {
+                state.stats.futility_prunes += 1;
+                best_eval = best_eval.max(futility_eval);
+                continue;
             }
-        } else {
-            score = search(&new_board, alpha, beta, depth - 1, true, state);
         }
 
+        state.position_history.push(key);
+        state.move_stack.push(Some((piece, mv.get_dest())));
+        state.push_accumulator(board, mv);
+        let parent_halfmove_clock = state.halfmove_clock;
+        state.halfmove_clock = next_halfmove_clock(board, mv, parent_halfmove_clock);
+
+        // Promotion races are searched a ply deeper, so the queen appearing
+        // is not cut off at the horizon, where quiescence only sees captures
+        let extension = i32::from(pawn_push || is_promotion);
+        state.stats.extensions += extension as u64;
+        let new_depth = depth - 1 + extension;
+
+        let child_type = node_type.child(i);
+        let score = if i == 0 {
+            // The first move is searched with the full window
+            -search(
+                &new_board, -beta, -alpha, new_depth, true, child_type, state,
+            )
+        } else {
+            // Principal variation search: later moves only have to be shown
+            // not to beat alpha, with a null window around it
+            let do_lmr = i >= state.params.lmr_min_move_index as usize
+                && depth >= 3
+                && !capture
+                && !in_check
+                && !is_promotion
+                && !gives_check
+                && !pawn_push;
+
+            let mut score = alpha + 1;
+            if do_lmr {
+                state.stats.lmr_reductions += 1;
+                // Reduced depth search, one ply shallower still at expected cut
+                // nodes and for moves that have never caused a cutoff in a
+                // similar spot; PV nodes only take the base reduction
+                let mut reduction = 1;
+                if node_type == NodeType::Cut && depth >= 5 {
+                    reduction += 1;
+                }
+                if node_type != NodeType::Pv && quiet_history == 0 && depth >= 4 {
+                    reduction += 1;
+                }
+                score = -search(
+                    &new_board,
+                    -alpha - 1,
+                    -alpha,
+                    new_depth - reduction,
+                    true,
+                    child_type,
+                    state,
+                );
+                // Re-search at full depth if reduced search improves alpha
+                if score > alpha {
+                    state.stats.lmr_researches += 1;
+                }
+            }
+            if score > alpha {
+                score = -search(
+                    &new_board,
+                    -alpha - 1,
+                    -alpha,
+                    new_depth,
+                    true,
+                    child_type,
+                    state,
+                );
+            }
+            // At a PV node, a move that lands inside the window needs its
+            // exact score
+            if node_type == NodeType::Pv && score > alpha && score < beta {
+                state.stats.pvs_researches += 1;
+                score = -search(
+                    &new_board,
+                    -beta,
+                    -alpha,
+                    new_depth,
+                    true,
+                    NodeType::Pv,
+                    state,
+                );
+            }
+            score
+        };
+
         state.position_history.pop();
+        state.move_stack.pop();
+        state.pop_accumulator();
+        state.halfmove_clock = parent_halfmove_clock;
+        state.follow_pv = false;
 
         if state.stopped {
-            return 0.0;
+            return 0;
         }
 
-        if white_to_move {
-            if score > best_eval {
-                best_eval = score;
-                best_move = *mv;
-            }
-            alpha = alpha.max(score);
-        } else {
-            if score < best_eval {
-                best_eval = score;
-                best_move = *mv;
-            }
-            beta = beta.min(score);
+        if score > best_eval {
+            best_eval = score;
+            best_move = Some(mv);
+        }
+        if node_type == NodeType::Pv && score > alpha {
+            state.update_pv(mv);
         }
+        alpha = alpha.max(score);
 
-        if beta <= alpha {
+        if alpha >= beta {
+            state.stats.beta_cutoffs += 1;
+            if i == 0 {
+                state.stats.first_move_cutoffs += 1;
+            }
+            if !capture && !is_promotion {
+                state.history.reward(board, mv, &state.move_stack, depth);
+                state.store_killer(mv);
+            }
             break;
         }
     }
 
+    // No legal moves: the game is over, scored by the variant's rules. The
+    // first move is never pruned, so any legal move would have been searched.
+    let Some(best_move) = best_move else {
+        return state.relative_eval(board, 0);
+    };
+
     // Determine TT flag based on relationship to original alpha/beta window
-    let tt_flag = if white_to_move {
-        if best_eval <= original_alpha {
-            TTFlag::UpperBound
-        } else if best_eval >= original_beta {
-            TTFlag::LowerBound
-        } else {
-            TTFlag::Exact
-        }
-    } else if best_eval >= original_beta {
+    let tt_flag = if best_eval <= original_alpha {
         TTFlag::UpperBound
-    } else if best_eval <= original_alpha {
+    } else if best_eval >= beta {
         TTFlag::LowerBound
     } else {
         TTFlag::Exact
     };
 
-    // Store in transposition table
-    if state.transposition_table.len() < MAX_TT_ENTRIES {
-        state.transposition_table.insert(
-            key,
-            TTEntry {
-                depth,
-                eval: best_eval,
-                flag: tt_flag,
-                best_move: Some(best_move),
-            },
-        );
+    // Store in transposition table
+    state.transposition_table.insert(
+        key,
+        TTEntry {
+            depth,
+            eval: score_to_tt(best_eval, state.ply(0)),
+            flag: tt_flag,
+            best_move: Some(best_move),
+        },
+    );
+
+    best_eval
+}
+
+/// How a reported score relates to the true value of the position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreBound {
+    Exact,
+    /// The position is worth at least this much to the side to move
+    Lower,
+    /// The position is worth at most this much to the side to move
+    Upper,
+}
+
+impl ScoreBound {
+    /// Qualifier appended to `score cp X` in UCI output
+    fn uci_tag(self) -> &'static str {
+        match self {
+            ScoreBound::Exact => "",
+            ScoreBound::Lower => " lowerbound",
+            ScoreBound::Upper => " upperbound",
+        }
+    }
+
+    /// `bound` field of JSON info objects; exact scores have none
+    fn json_field(self) -> &'static str {
+        match self {
+            ScoreBound::Exact => "",
+            ScoreBound::Lower => ",\"bound\":\"lower\"",
+            ScoreBound::Upper => ",\"bound\":\"upper\"",
+        }
+    }
+}
+
+/// Print a UCI `info` line for the current best root move. `eval` is from
+/// the side to move's point of view.
+fn print_info(state: &SearchState<'_>, depth: i32, score: i32, bound: ScoreBound, pv: &str) {
+    let elapsed_ms = state.start.elapsed().as_millis();
+    let nps = state.nodes as u128 * 1000 / elapsed_ms.max(1);
+    let hashfull = hashfull(
+        state.transposition_table.len(),
+        state.transposition_table.capacity(),
+    );
+    match state.output_format {
+        OutputFormat::Uci => uci_println!(
+            "info depth {} seldepth {} score {}{} nodes {} nps {} hashfull {} time {} pv {}",
+            depth,
+            state.seldepth,
+            match evaluation::mate_in(score) {
+                Some(moves) => format!("mate {}", moves),
+                None => format!("cp {}", score),
+            },
+            bound.uci_tag(),
+            state.nodes,
+            nps,
+            hashfull,
+            elapsed_ms,
+            pv
+        ),
+        OutputFormat::Json => {
+            let pv: Vec<String> = pv
+                .split_whitespace()
+                .map(|m| format!("\"{}\"", m))
+                .collect();
+            let mate = evaluation::mate_in(score)
+                .map(|moves| format!(",\"mate\":{}", moves))
+                .unwrap_or_default();
+            uci_println!(
+                "{{\"type\":\"info\",\"depth\":{},\"seldepth\":{},\"score_cp\":{}{}{},\"nodes\":{},\"nps\":{},\"hashfull\":{},\"time_ms\":{},\"pv\":[{}]}}",
+                depth,
+                state.seldepth,
+                score,
+                mate,
+                bound.json_field(),
+                state.nodes,
+                nps,
+                hashfull,
+                elapsed_ms,
+                pv.join(",")
+            )
+        }
+    }
+    let _ = std::io::stdout().flush();
+}
+
+/// Print the search diagnostics gathered so far as UCI `info string` lines
+fn print_debug_stats(state: &SearchState<'_>, depth: i32) {
+    let stats = &state.stats;
+    let percent = |part: u64, total: u64| {
+        if total > 0 {
+            100.0 * part as f64 / total as f64
+        } else {
+            0.0
+        }
+    };
+
+    uci_println!(
+        "info string debug depth {} nodes {} time {} ms",
+        depth,
+        state.nodes,
+        state.start.elapsed().as_millis()
+    );
+    uci_println!(
+        "info string debug tt probes {} hits {} ({:.1}%) entries {}",
+        stats.tt_probes,
+        stats.tt_hits,
+        percent(stats.tt_hits, stats.tt_probes),
+        state.transposition_table.len()
+    );
+    uci_println!(
+        "info string debug null-move tries {} cutoffs {} ({:.1}%)",
+        stats.null_move_tries,
+        stats.null_move_cutoffs,
+        percent(stats.null_move_cutoffs, stats.null_move_tries)
+    );
+    uci_println!(
+        "info string debug lmr reductions {} re-searches {} ({:.1}%)",
+        stats.lmr_reductions,
+        stats.lmr_researches,
+        percent(stats.lmr_researches, stats.lmr_reductions)
+    );
+    uci_println!("info string debug pvs re-searches {}", stats.pvs_researches);
+    uci_println!("info string debug extensions {}", stats.extensions);
+    uci_println!("info string debug iid searches {}", stats.iid_searches);
+    uci_println!(
+        "info string debug futility pruned moves {} late move pruned {}",
+        stats.futility_prunes,
+        stats.late_move_prunes
+    );
+    uci_println!(
+        "info string debug beta cutoffs {} on first move {} ({:.1}%)",
+        stats.beta_cutoffs,
+        stats.first_move_cutoffs,
+        percent(stats.first_move_cutoffs, stats.beta_cutoffs)
+    );
+    uci_println!(
+        "info string debug qsearch nodes {} ({:.1}% of all)",
+        stats.qs_nodes,
+        percent(stats.qs_nodes, state.nodes)
+    );
+    uci_println!(
+        "info string debug evals {} cache hits {} ({:.1}%)",
+        stats.eval_probes,
+        stats.eval_cache_hits,
+        percent(stats.eval_cache_hits, stats.eval_probes)
+    );
+}
+
+/// Centipawns by which a move from an aborted iteration must beat the last
+/// completed iteration's score to be played instead of its best move
+const PARTIAL_ITERATION_MARGIN: i32 = 30;
+
+/// Completed iterations in a row the best move must have led for the search
+/// to stop early on an easy move
+const EASY_MOVE_ITERATIONS: i32 = 4;
+
+/// Centipawns by which an easy move must beat every other root move, checked
+/// with a search at half the depth
+const EASY_MOVE_MARGIN: i32 = 150;
+
+/// Centipawns the previous best move's score must fall by, compared to the
+/// last completed iteration of the same parity (odd or even depth, since
+/// scores swing with the side that moves last), for the search to take up to
+/// its maximum time
+const TIME_EXTENSION_DROP: i32 = 50;
+
+/// Shallowest iteration whose score drop extends the search
+const TIME_EXTENSION_MIN_DEPTH: i32 = 6;
+
+/// Fraction of the time budget spent before an easy move is played, so that
+/// even a quick search looks a few plies deeper than the recapture
+const EASY_MOVE_MIN_TIME_FRACTION: f64 = 0.1;
+
+/// Number of book moves reported as hints in analysis mode
+const BOOK_HINT_COUNT: usize = 5;
+
+/// Default per-move latency allowance in milliseconds
+pub const DEFAULT_MOVE_OVERHEAD_MS: i64 = 30;
+
+/// How search information and the best move are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Plain UCI text
+    #[default]
+    Uci,
+    /// One JSON object per line, for bots and web front-ends
+    Json,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 2] = [OutputFormat::Uci, OutputFormat::Json];
+
+    /// Name used for the `OutputFormat` option
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Uci => "uci",
+            OutputFormat::Json => "json",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        OutputFormat::ALL
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// User-facing switches that change how a move is chosen
+#[derive(Clone)]
+pub struct EngineOptions {
+    /// Print search diagnostics as `info string` lines
+    pub debug: bool,
+    /// Analysis mode: report book moves as hints and always search
+    pub analyse_mode: bool,
+    /// Play moves from the opening book when available
+    pub own_book: bool,
+    /// Time in milliseconds lost per move to GUI/network latency
+    pub move_overhead_ms: i64,
+    /// Nodes searched per millisecond of thinking time, so that searches
+    /// on the clock are reproducible on any hardware (0 uses real time)
+    pub nodes_time: u64,
+    /// Centipawns the engine's side gives up to avoid a draw (negative values
+    /// make it seek draws)
+    pub contempt: i32,
+    /// Percentage of the positional evaluation of the engine's side taken
+    /// away, so it plays as if it understood its own position that much less
+    pub handicap: i32,
+    /// Rules to play by
+    pub variant: Variant,
+    /// Don't print search info, for games the engine plays against itself
+    pub silent: bool,
+    /// Write castling moves as the king capturing its own rook
+    pub chess960: bool,
+    pub output_format: OutputFormat,
+    /// Evaluate every position searched with colors swapped as well, and
+    /// report any whose scores aren't exact negatives (a debug mode, slow)
+    pub check_symmetry: bool,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions {
+            debug: false,
+            analyse_mode: false,
+            own_book: true,
+            move_overhead_ms: DEFAULT_MOVE_OVERHEAD_MS,
+            nodes_time: 0,
+            contempt: 0,
+            handicap: 0,
+            variant: Variant::Standard,
+            silent: false,
+            chess960: false,
+            output_format: OutputFormat::Uci,
+            check_symmetry: false,
+        }
+    }
+}
+
+/// Apply a UCI option that changes how the engine searches, as opposed to
+/// session settings such as adjudication or logging. Returns false if `name`
+/// is not such an option.
+pub fn set_engine_option(
+    options: &mut EngineOptions,
+    engine_state: &mut EngineState,
+    name: &str,
+    value: &str,
+) -> bool {
+    if name.eq_ignore_ascii_case("Hash") {
+        if let Ok(mb) = value.parse::<usize>() {
+            engine_state.set_hash_mb(mb);
+        }
+    } else if name.eq_ignore_ascii_case("Move Overhead") {
+        if let Ok(ms) = value.parse::<i64>() {
+            options.move_overhead_ms = ms.clamp(0, 5000);
+        }
+    } else if name.eq_ignore_ascii_case("nodestime") {
+        if let Ok(nodes) = value.parse::<u64>() {
+            options.nodes_time = nodes.min(100_000);
+        }
+    } else if name.eq_ignore_ascii_case("Contempt") {
+        if let Ok(cp) = value.parse::<i32>() {
+            options.contempt = cp.clamp(-500, 500);
+        }
+    } else if name.eq_ignore_ascii_case("Handicap") {
+        if let Ok(percent) = value.parse::<i32>() {
+            options.handicap = percent.clamp(0, 100);
+        }
+    } else if name.eq_ignore_ascii_case("OwnBook") {
+        options.own_book = value == "true";
+    } else if name.eq_ignore_ascii_case("UCI_Variant") {
+        match Variant::from_name(value) {
+            Some(variant) if variant != options.variant => {
+                // Scores learned under other rules are meaningless
+                options.variant = variant;
+                engine_state.clear();
+            }
+            Some(_) => {}
+            None => uci_println!("info string unknown variant {}", value),
+        }
+    } else if name.eq_ignore_ascii_case("UCI_AnalyseMode") {
+        options.analyse_mode = value == "true";
+    } else if name.eq_ignore_ascii_case("UCI_Chess960") {
+        options.chess960 = value == "true";
+    } else if name.eq_ignore_ascii_case("EvalFile") {
+        // An empty path, or the `<empty>` placeholder, unloads the network
+        let path = value.trim();
+        if path.is_empty() || path == "<empty>" {
+            engine_state.set_network(None);
+            engine_state.eval_file.clear();
+        } else {
+            match Network::load(path) {
+                Ok(network) => {
+                    engine_state.set_network(Some(Arc::new(network)));
+                    engine_state.eval_file = path.to_string();
+                    uci_println!("info string NNUE evaluation using {}", path);
+                }
+                Err(e) => uci_println!("info string {}", e),
+            }
+        }
+    } else if name.eq_ignore_ascii_case("EvalSymmetryCheck") {
+        options.check_symmetry = value == "true";
+    } else if name.eq_ignore_ascii_case("OutputFormat") {
+        if let Some(format) = OutputFormat::from_name(value) {
+            options.output_format = format;
+        }
+    } else {
+        return value
+            .parse()
+            .is_ok_and(|value| engine_state.params.set(name, value));
+    }
+    true
+}
+
+/// Limits on how long a search may run
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    /// Soft time limit in seconds, past which no new iteration is started
+    /// (unlimited if None)
+    pub time: Option<f64>,
+    /// Deepest iteration to search (unlimited if None)
+    pub depth: Option<i32>,
+    /// Whether the time comes from a clock, so the search may stop before it
+    /// is used up when the best move is clear (a fixed move time is used in
+    /// full)
+    pub flexible_time: bool,
+    /// Hard time limit in seconds, at which the search is aborted (`time` if
+    /// None). Iterations started before `time` may run on until it, and
+    /// when the best move's score drops sharply, new ones are started until
+    /// it as well.
+    pub max_time: Option<f64>,
+    /// Nodes to search at most, aborting the search once they are used up
+    /// (unlimited if None)
+    pub nodes: Option<u64>,
+}
+
+impl SearchLimits {
+    pub fn time(seconds: f64) -> Self {
+        SearchLimits {
+            time: Some(seconds),
+            depth: None,
+            flexible_time: false,
+            max_time: None,
+            nodes: None,
+        }
+    }
+
+    pub fn depth(depth: i32) -> Self {
+        SearchLimits {
+            time: None,
+            depth: Some(depth),
+            flexible_time: false,
+            max_time: None,
+            nodes: None,
+        }
+    }
+}
+
+/// What `play_move` found
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchResult {
+    /// Move to play, None when there are no legal moves
+    pub best_move: Option<ChessMove>,
+    /// Expected reply, the second move of the principal variation
+    pub ponder: Option<ChessMove>,
+    /// Score in centipawns from White's point of view
+    pub score: i32,
+    /// Last completed iteration, 0 for book moves and forced moves
+    pub depth: i32,
+    /// Deepest ply reached, quiescence included
+    pub seldepth: i32,
+    pub nodes: u64,
+    /// Principal variation, starting with `best_move`
+    pub pv: Vec<ChessMove>,
+    pub time: Duration,
+}
+
+impl SearchResult {
+    /// A move chosen without searching, e.g. from the book
+    fn unsearched(mv: ChessMove, score: i32, start: Instant) -> Self {
+        SearchResult {
+            best_move: Some(mv),
+            score,
+            pv: vec![mv],
+            time: start.elapsed(),
+            ..SearchResult::default()
+        }
+    }
+}
+
+/// A line of moves in UCI notation, separated by spaces
+fn format_pv(board: &Board, pv: &[ChessMove], chess960: bool) -> String {
+    let mut board = *board;
+    let mut text = Vec::with_capacity(pv.len());
+    for &mv in pv {
+        text.push(moves::format_uci_move(&board, mv, chess960));
+        board = board.make_move_new(mv);
     }
+    text.join(" ")
+}
 
-    best_eval
+/// Whether every one of `moves` scores below `bound` (from the side to move's
+/// point of view) in a null window search to `depth` from the root
+fn others_fall_short(
+    board: &Board,
+    moves: &[(ChessMove, i32)],
+    bound: i32,
+    depth: i32,
+    halfmove_clock: u32,
+    state: &mut SearchState,
+) -> bool {
+    moves.iter().all(|&(mv, _)| {
+        let piece = board.piece_on(mv.get_source()).unwrap_or(Piece::Pawn);
+        state.move_stack.push(Some((piece, mv.get_dest())));
+        state.push_accumulator(board, mv);
+        state.halfmove_clock = next_halfmove_clock(board, mv, halfmove_clock);
+        let score = -search(
+            &board.make_move_new(mv),
+            -bound,
+            -bound + 1,
+            depth - 1,
+            true,
+            NodeType::Cut,
+            state,
+        );
+        state.move_stack.pop();
+        state.pop_accumulator();
+        !state.stopped && score < bound
+    })
 }
 
-/// Play the best move for the current position
-/// Returns the best move in UCI format and the evaluation
-pub fn play_move(board: &Board, book: &Book, time_to_move: f64, history: &[u64]) -> (String, f64) {
-    // Try to find a random move from the book
+/// Find the best move for the current position, from the book or by search
+pub fn play_move(
+    board: &Board,
+    book: &Book,
+    limits: &SearchLimits,
+    history: &[u64],
+    halfmove_clock: u32,
+    engine_state: &mut EngineState,
+    options: &EngineOptions,
+) -> SearchResult {
+    let debug = options.debug;
     let pos_key = board.get_hash();
+    let start = Instant::now();
+    engine_state.nodes = 0;
+
+    if debug {
+        let book_moves = book.get(&pos_key).map_or(0, |moves| moves.len());
+        uci_println!(
+            "info string debug book probe {:016x}: {} move(s) among {} positions",
+            pos_key,
+            book_moves,
+            book.len()
+        );
+    }
 
-    if let Some(book_moves) = book.get(&pos_key) {
-        if book_moves.len() > 1 {
-            use rand::seq::SliceRandom;
-            let moves: Vec<_> = book_moves.iter().collect();
-            if let Some(&&chosen_move) = moves.choose(&mut rand::thread_rng()) {
-                return (format!("{}", chosen_move), 0.0);
-            }
-        } else if let Some(&mv) = book_moves.iter().next() {
-            return (format!("{}", mv), 0.0);
+    if options.analyse_mode {
+        // Show theory alongside the engine's own opinion instead of playing it
+        let hints = book::top_moves(book, pos_key, BOOK_HINT_COUNT);
+        if !hints.is_empty() {
+            let hints: Vec<String> = hints
+                .iter()
+                .map(|(mv, count)| format!("{} ({})", mv, count))
+                .collect();
+            uci_println!("info string book suggests {}", hints.join(" "));
+        }
+    } else if let Some(book_moves) = book
+        .get(&pos_key)
+        .filter(|_| options.own_book && options.variant == Variant::Standard)
+    {
+        // Try to find a random move from the book
+        use rand::seq::IteratorRandom;
+        if let Some(&chosen_move) = book_moves.keys().choose(&mut rand::thread_rng()) {
+            return SearchResult::unsearched(chosen_move, 0, start);
         }
     }
 
     // Generate legal moves at root
-    let movegen = MoveGen::new_legal(board);
-    let mut moves: Vec<(ChessMove, f64)> = movegen.map(|mv| (mv, 0.0)).collect();
+    let mut moves: Vec<(ChessMove, i32)> = options
+        .variant
+        .legal_moves(board)
+        .into_iter()
+        .map(|mv| (mv, 0))
+        .collect();
 
     if moves.is_empty() {
-        return (String::new(), 0.0);
+        return SearchResult::default();
     }
 
     if moves.len() == 1 {
-        return (format!("{}", moves[0].0), eval(board));
+        return SearchResult::unsearched(moves[0].0, options.variant.eval(board, 0), start);
     }
 
     // Iterative deepening
-    let start = Instant::now();
-    let time_limit = Duration::from_secs_f64(time_to_move);
+    // No iteration is started past the soft limit, and the search is aborted
+    // at the hard limit, which leaves deeper iterations time to complete
+    let time_limit = limits.time.map_or(Duration::MAX, Duration::from_secs_f64);
+    let mut soft_limit = time_limit;
+    let hard_limit = limits
+        .max_time
+        .map_or(time_limit, Duration::from_secs_f64)
+        .max(time_limit);
     let white_to_move = board.side_to_move() == Color::White;
 
     let mut best_move = moves[0].0;
-    let mut best_eval = 0.0;
+    let mut best_pv = vec![best_move];
+    let mut best_eval = 0;
+    let mut completed_depth = 0;
+    // Completed iterations in a row that ended with the same best move
+    let mut stable_iterations = 0;
+    // Best score of each completed iteration
+    let mut iteration_evals = Vec::new();
+    engine_state.transposition_table.new_search();
+    engine_state.history.age();
+
+    // Only standard chess is evaluated by the network, so under other rules
+    // its accumulators aren't kept up to date either
+    let network = engine_state
+        .network
+        .as_deref()
+        .filter(|_| options.variant == Variant::Standard);
+
+    // Positions before the last irreversible move can never recur
+    let root_history = &history[history.len().saturating_sub(halfmove_clock as usize + 1)..];
     let mut state = SearchState {
-        transposition_table: HashMap::new(),
-        position_history: history.to_vec(),
+        transposition_table: &engine_state.transposition_table,
+        history: &mut engine_state.history,
+        eval_cache: &mut engine_state.eval_cache,
+        stop_signal: &engine_state.stop_signal,
+        position_history: root_history.to_vec(),
+        root_history_len: root_history.len(),
+        null_move_floor: 0,
+        move_stack: Vec::new(),
+        accumulators: vec![Accumulator::new(board)],
+        network,
+        nnue_accumulators: network
+            .iter()
+            .map(|network| nnue::Accumulator::new(network, board))
+            .collect(),
+        halfmove_clock,
+        killers: Vec::new(),
+        pv_table: Vec::new(),
+        previous_pv: Vec::new(),
+        follow_pv: false,
         start,
-        time_limit,
+        time_limit: hard_limit,
+        node_limit: limits.nodes.unwrap_or(u64::MAX),
+        abortable: false,
+        nodes_per_ms: options.nodes_time,
         nodes: 0,
+        time_check_mask: engine_state.time_check_interval - 1,
+        seldepth: 0,
         stopped: false,
+        stats: SearchStats::default(),
+        params: engine_state.params,
+        draw_score: if white_to_move {
+            -options.contempt
+        } else {
+            options.contempt
+        },
+        // The handcrafted terms it scales mean nothing in other variants
+        handicap: (options.handicap != 0 && options.variant == Variant::Standard)
+            .then_some((board.side_to_move(), options.handicap)),
+        variant: options.variant,
+        output_format: options.output_format,
+        check_symmetry: options.check_symmetry,
     };
 
-    for depth in 1.. {
+    if debug {
+        uci_println!(
+            "info string debug time limit {} ms (hard {} ms) for {} root moves",
+            soft_limit.as_millis(),
+            hard_limit.as_millis(),
+            moves.len()
+        );
+    }
+
+    // Root scores are from the side to move's point of view
+    for depth in 1..=limits.depth.unwrap_or(i32::MAX) {
         let mut depth_best_move = moves[0].0;
-        let mut depth_best_eval = if white_to_move {
-            f64::NEG_INFINITY
-        } else {
-            f64::INFINITY
-        };
+        let mut depth_best_pv = vec![depth_best_move];
+        let mut depth_best_eval = -INFINITE_SCORE;
 
-        for (mv, mv_eval) in &mut moves {
+        for (i, (mv, mv_eval)) in moves.iter_mut().enumerate() {
+            let piece = board.piece_on(mv.get_source()).unwrap_or(Piece::Pawn);
             let new_board = board.make_move_new(*mv);
-            let score = search(
-                &new_board,
-                f64::NEG_INFINITY,
-                f64::INFINITY,
-                depth - 1,
-                true,
-                &mut state,
-            );
+            state.move_stack.push(Some((piece, mv.get_dest())));
+            state.push_accumulator(board, *mv);
+            state.halfmove_clock = next_halfmove_clock(board, *mv, halfmove_clock);
+            state.follow_pv = state.previous_pv.first() == Some(mv);
+            // Extended as in `search`
+            let new_depth = depth - 1
+                + i32::from(pushes_pawn_to_seventh(board, *mv) || mv.get_promotion().is_some());
+            // Principal variation search, as in `search`: after the first
+            // move, a move has to beat the best score on a null window before
+            // it gets a full window search for its exact score
+            let alpha = depth_best_eval;
+            let mut score = alpha + 1;
+            if i > 0 {
+                score = -search(
+                    &new_board,
+                    -alpha - 1,
+                    -alpha,
+                    new_depth,
+                    true,
+                    NodeType::Pv.child(i),
+                    &mut state,
+                );
+            }
+            if score > alpha {
+                score = -search(
+                    &new_board,
+                    -INFINITE_SCORE,
+                    -alpha,
+                    new_depth,
+                    true,
+                    NodeType::Pv,
+                    &mut state,
+                );
+            }
+            state.move_stack.pop();
+            state.pop_accumulator();
+            state.follow_pv = false;
 
             if state.stopped {
                 break;
@@ -466,145 +2248,378 @@ pub fn play_move(board: &Board, book: &Book, time_to_move: f64, history: &[u64])
 
             *mv_eval = score;
 
-            if white_to_move {
-                if score > depth_best_eval {
-                    depth_best_eval = score;
-                    depth_best_move = *mv;
+            // The previous best move fails low: keep starting iterations up
+            // to the hard limit to look for a better one rather than commit
+            // to it
+            let same_parity_eval = iteration_evals.iter().rev().nth(1).copied();
+            if let Some(previous_eval) = same_parity_eval {
+                if i == 0
+                    && depth >= TIME_EXTENSION_MIN_DEPTH
+                    && score < previous_eval - TIME_EXTENSION_DROP
+                    && soft_limit < hard_limit
+                {
+                    soft_limit = hard_limit;
+                    if debug {
+                        uci_println!(
+                            "info string debug depth {} score dropped {} cp, extending to {} ms",
+                            depth,
+                            previous_eval - score,
+                            hard_limit.as_millis()
+                        );
+                    }
+                }
+            }
+
+            if score > depth_best_eval {
+                depth_best_pv = state.root_pv(*mv);
+                // Stream the new idea right away unless it's just the previous
+                // iteration's best move being confirmed. Moves still to come
+                // can only raise the iteration's score, so it is a lower bound.
+                if depth > 1 && *mv != best_move && !options.silent {
+                    let pv = format_pv(board, &depth_best_pv, options.chess960);
+                    print_info(&state, depth, score, ScoreBound::Lower, &pv);
                 }
-            } else if score < depth_best_eval {
                 depth_best_eval = score;
                 depth_best_move = *mv;
             }
         }
 
+        if debug {
+            print_debug_stats(&state, depth);
+        }
+
         // Only update best move if this depth completed
         if !state.stopped {
+            stable_iterations = if depth_best_move == best_move {
+                stable_iterations + 1
+            } else {
+                1
+            };
             best_move = depth_best_move;
             best_eval = depth_best_eval;
+            best_pv = depth_best_pv;
+            state.previous_pv.clone_from(&best_pv);
+            completed_depth = depth;
+            state.abortable = true;
+            iteration_evals.push(best_eval);
+            if !options.silent {
+                let pv = format_pv(board, &best_pv, options.chess960);
+                print_info(&state, depth, best_eval, ScoreBound::Exact, &pv);
+            }
 
             // Sort moves by eval for next iteration (best first for better pruning)
-            if white_to_move {
-                moves.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-            } else {
-                moves.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+            moves.sort_by_key(|&(_, eval)| std::cmp::Reverse(eval));
+
+            // Stop once a mate is found within the full-width depth: no
+            // deeper iteration can find a shorter one
+            if is_mate_score(best_eval) && MATE_EVAL - best_eval.abs() <= depth {
+                break;
+            }
+
+            // Stop early on an easy move, one that has stayed best for a few
+            // iterations while every other move scores clearly worse
+            if limits.flexible_time
+                && stable_iterations >= EASY_MOVE_ITERATIONS
+                && state.elapsed().as_secs_f64()
+                    >= time_limit.as_secs_f64() * EASY_MOVE_MIN_TIME_FRACTION
+                && others_fall_short(
+                    board,
+                    &moves[1..],
+                    best_eval - EASY_MOVE_MARGIN,
+                    depth / 2,
+                    halfmove_clock,
+                    &mut state,
+                )
+            {
+                if debug {
+                    uci_println!(
+                        "info string debug easy move {} after depth {} ({} ms)",
+                        best_move,
+                        depth,
+                        state.start.elapsed().as_millis()
+                    );
+                }
+                break;
             }
 
-            // If mate found, stop
-            if best_eval.abs() == MATE_EVAL {
+            // Past the soft limit, don't start another iteration
+            if state.elapsed() >= soft_limit {
                 break;
             }
         } else {
+            // The best move of an aborted iteration got a full window search,
+            // so one that clearly beats the previous iteration's score is
+            // worth playing over the older best move
+            let gain = depth_best_eval - best_eval;
+            if depth > 1 && depth_best_move != best_move && gain > PARTIAL_ITERATION_MARGIN {
+                if debug {
+                    uci_println!(
+                        "info string debug depth {} aborted, playing {} ({:+} cp)",
+                        depth,
+                        depth_best_move,
+                        gain
+                    );
+                }
+                best_move = depth_best_move;
+                best_eval = depth_best_eval;
+                best_pv = depth_best_pv;
+                if !options.silent {
+                    // The moves left unsearched could still have done better
+                    let pv = format_pv(board, &best_pv, options.chess960);
+                    print_info(&state, depth, best_eval, ScoreBound::Lower, &pv);
+                }
+            }
             break;
         }
     }
 
-    (format!("{}", best_move), best_eval)
-}
-
-/// Set up the position from a FEN string and list of moves
-/// Returns the board and a history of position hashes (for repetition detection)
-pub fn set_position(fen: &str, moves: &[String]) -> (Board, Vec<u64>) {
-    use std::str::FromStr;
-
-    let mut board = Board::from_str(fen).unwrap_or_default();
-    let mut history = vec![board.get_hash()];
-
-    for move_str in moves {
-        if let Ok(mv) = ChessMove::from_str(move_str) {
-            if MoveGen::new_legal(&board).any(|m| m == mv) {
-                board = board.make_move_new(mv);
-                history.push(board.get_hash());
-            }
-        } else if let Some(mv) = parse_uci_move(&board, move_str) {
-            board = board.make_move_new(mv);
-            history.push(board.get_hash());
-        }
+    if debug_log::is_open() {
+        debug_log::note(&format!(
+            "search summary: best {} nodes {} time {} ms tt entries {} null-move cutoffs {} lmr re-searches {}",
+            best_move,
+            state.nodes,
+            state.start.elapsed().as_millis(),
+            state.transposition_table.len(),
+            state.stats.null_move_cutoffs,
+            state.stats.lmr_researches
+        ));
     }
 
-    (board, history)
+    engine_state.nodes = state.nodes;
+    SearchResult {
+        best_move: Some(best_move),
+        ponder: best_pv.get(1).copied(),
+        score: if white_to_move { best_eval } else { -best_eval },
+        depth: completed_depth,
+        seldepth: state.seldepth,
+        nodes: state.nodes,
+        pv: best_pv,
+        time: state.start.elapsed(),
+    }
 }
 
-/// Parse a UCI format move string (e.g., "e2e4", "e7e8q")
-fn parse_uci_move(board: &Board, move_str: &str) -> Option<ChessMove> {
-    use chess::{File, Rank, Square};
-
-    if move_str.len() < 4 {
-        return None;
+/// Rewrite the castling field of a FEN, which may use Chess960 notation
+/// (Shredder-FEN rook files or X-FEN), as the KQkq rights the chess crate
+/// understands. The crate only castles with the king on the e-file and the
+/// rooks in the corners, so a Chess960 start position that can still castle
+/// any other way is rejected. Rights whose king or rook has left the back
+/// rank are dropped.
+fn standard_castling_fen(fen: &str) -> Result<String, String> {
+    let mut fields: Vec<&str> = fen.split_whitespace().collect();
+    let ranks: Vec<&str> = fields
+        .first()
+        .map_or(Vec::new(), |p| p.split('/').collect());
+    if fields.len() < 3 || ranks.len() != 8 {
+        return Ok(fen.to_string());
     }
 
-    let chars: Vec<char> = move_str.chars().collect();
-
-    let from_file = File::from_index((chars[0] as u8 - b'a') as usize);
-    let from_rank = Rank::from_index((chars[1] as u8 - b'1') as usize);
-    let to_file = File::from_index((chars[2] as u8 - b'a') as usize);
-    let to_rank = Rank::from_index((chars[3] as u8 - b'1') as usize);
-
-    let from = Square::make_square(from_rank, from_file);
-    let to = Square::make_square(to_rank, to_file);
-
-    let promotion = if move_str.len() >= 5 {
-        match chars[4] {
-            'q' | 'Q' => Some(Piece::Queen),
-            'r' | 'R' => Some(Piece::Rook),
-            'b' | 'B' => Some(Piece::Bishop),
-            'n' | 'N' => Some(Piece::Knight),
-            _ => None,
+    // Piece letters of a back rank by file, '.' for empty squares
+    let expand = |rank: &str| -> Vec<char> {
+        let mut squares = Vec::new();
+        for c in rank.chars() {
+            match c.to_digit(10) {
+                Some(n) => squares.extend(std::iter::repeat_n('.', n as usize)),
+                None => squares.push(c),
+            }
         }
-    } else {
-        None
+        squares
     };
+    let (white_rank, black_rank) = (expand(ranks[7]), expand(ranks[0]));
 
-    let movegen = MoveGen::new_legal(board);
-    for mv in movegen {
-        if mv.get_source() == from && mv.get_dest() == to {
-            if let Some(promo) = promotion {
-                if mv.get_promotion() == Some(promo) {
-                    return Some(mv);
-                }
-            } else if mv.get_promotion().is_none() {
-                return Some(mv);
+    let mut rights = Vec::new();
+    for c in fields[2].chars() {
+        let (rank, king, rook) = if c.is_ascii_uppercase() {
+            (&white_rank, 'K', 'R')
+        } else {
+            (&black_rank, 'k', 'r')
+        };
+        let Some(king_file) = rank.iter().position(|&p| p == king) else {
+            continue;
+        };
+        // The rook a right refers to: the outermost one on that side of the
+        // king for KQkq, or the one on the named file
+        let rook_file = match c.to_ascii_lowercase() {
+            'k' => rank
+                .iter()
+                .rposition(|&p| p == rook)
+                .filter(|&f| f > king_file),
+            'q' => rank
+                .iter()
+                .position(|&p| p == rook)
+                .filter(|&f| f < king_file),
+            file @ 'a'..='h' => {
+                Some(file as usize - 'a' as usize).filter(|&f| rank.get(f) == Some(&rook))
             }
-        }
+            _ => None,
+        };
+        let Some(rook_file) = rook_file else {
+            continue;
+        };
+        let right = match (king_file, rook_file) {
+            (4, 7) => 'k',
+            (4, 0) => 'q',
+            // A standard king with a stale right to a rook that moved
+            (4, _) if matches!(c, 'K' | 'Q' | 'k' | 'q') => continue,
+            _ => {
+                return Err(format!(
+                    "castling right '{}' needs Chess960 castling, which is not supported",
+                    c
+                ))
+            }
+        };
+        rights.push(if c.is_ascii_uppercase() {
+            right.to_ascii_uppercase()
+        } else {
+            right
+        });
     }
 
-    let movegen = MoveGen::new_legal(board);
-    let matching: Vec<_> = movegen
-        .filter(|mv| mv.get_source() == from && mv.get_dest() == to)
-        .collect();
+    let castling: String = "KQkq".chars().filter(|c| rights.contains(c)).collect();
+    let castling = if castling.is_empty() {
+        "-".to_string()
+    } else {
+        castling
+    };
+    fields[2] = &castling;
+    Ok(fields.join(" "))
+}
+
+/// Set up the position from a FEN string and list of moves
+/// Returns the board, a history of position hashes (for repetition detection)
+/// and the halfmove clock (for the fifty-move rule)
+pub fn set_position(fen: &str, moves: &[String]) -> Result<(Board, Vec<u64>, u32), String> {
+    let boards = position_boards(fen, moves)?;
+    let history = boards.iter().map(|b| b.get_hash()).collect();
+    let halfmove_clock =
+        adjudication::halfmove_clock(adjudication::fen_halfmove_clock(fen), &boards);
+    Ok((
+        *boards.last().expect("at least the start position"),
+        history,
+        halfmove_clock,
+    ))
+}
 
-    if matching.len() == 1 {
-        return Some(matching[0]);
+/// Every position of the game given by a FEN string and list of moves,
+/// starting with the FEN position and ending with the current one
+pub fn position_boards(fen: &str, moves: &[String]) -> Result<Vec<Board>, String> {
+    use std::str::FromStr;
+
+    // The chess crate looks up both kings before it validates the board, so a
+    // FEN without exactly one king per side must be rejected up front
+    let placement = fen.split_whitespace().next().unwrap_or("");
+    let kings_ok = placement.matches('K').count() == 1 && placement.matches('k').count() == 1;
+    if !kings_ok {
+        return Err(format!("invalid FEN '{}'", fen));
+    }
+    let mut board = Board::from_str(&standard_castling_fen(fen)?)
+        .map_err(|_| format!("invalid FEN '{}'", fen))?;
+    let mut boards = vec![board];
+
+    for move_str in moves {
+        let mv = moves::parse_uci_move(&board, move_str)
+            .map_err(|e| format!("{} in position {}", e, board))?;
+        board = board.make_move_new(mv);
+        boards.push(board);
     }
 
-    None
+    Ok(boards)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::uci::START_POSITION;
+    use chess::ALL_SQUARES;
     use std::str::FromStr;
 
+    /// Search `fen` with fresh tables, without book and output
+    fn search_fen(fen: &str, limits: &SearchLimits, options: &EngineOptions) -> SearchResult {
+        let (board, history, halfmove_clock) = set_position(fen, &[]).unwrap();
+        play_move(
+            &board,
+            &Book::new(),
+            limits,
+            &history,
+            halfmove_clock,
+            &mut EngineState::new(),
+            &EngineOptions {
+                own_book: false,
+                silent: true,
+                ..options.clone()
+            },
+        )
+    }
+
     #[test]
     fn test_set_position_startpos() {
-        let (board, history) = set_position(
+        let (board, history, halfmove_clock) = set_position(
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
             &[],
-        );
+        )
+        .unwrap();
         assert_eq!(board, Board::default());
         assert_eq!(history.len(), 1);
+        assert_eq!(halfmove_clock, 0);
     }
 
     #[test]
     fn test_set_position_with_moves() {
-        let (board, history) = set_position(
+        let (board, history, halfmove_clock) = set_position(
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
             &["e2e4".to_string(), "e7e5".to_string()],
-        );
+        )
+        .unwrap();
         let expected =
             Board::from_str("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2")
                 .unwrap();
         assert_eq!(board, expected);
         assert_eq!(history.len(), 3);
+        assert_eq!(halfmove_clock, 0);
+
+        // The FEN's clock counts on through quiet moves
+        let (_, _, halfmove_clock) = set_position(
+            "4k3/8/8/8/8/8/8/4K1N1 w - - 10 40",
+            &["g1f3".to_string(), "e8d7".to_string()],
+        )
+        .unwrap();
+        assert_eq!(halfmove_clock, 12);
+    }
+
+    #[test]
+    fn test_set_position_chess960_castling_field() {
+        let standard = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+        let (expected, _, _) = set_position(standard, &[]).unwrap();
+        let (shredder, _, _) =
+            set_position("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w HAha - 0 1", &[]).unwrap();
+        assert_eq!(shredder, expected);
+
+        // Rights whose rook has left its corner are dropped
+        let (board, _, _) =
+            set_position("1r2k2r/pppppppp/8/8/8/8/PPPPPPPP/1R2K2R w KQkq - 0 1", &[]).unwrap();
+        assert!(board.castle_rights(Color::White).has_kingside());
+        assert!(!board.castle_rights(Color::White).has_queenside());
+        assert!(!board.castle_rights(Color::Black).has_queenside());
+
+        // A Chess960 start position that could castle can't be played
+        let error =
+            set_position("1r2k2r/pppppppp/8/8/8/8/PPPPPPPP/1R2K2R w HBhb - 0 1", &[]).unwrap_err();
+        assert!(error.contains("Chess960"), "{}", error);
+        assert!(set_position(
+            "nrkbbqrn/pppppppp/8/8/8/8/PPPPPPPP/NRKBBQRN w KQkq - 0 1",
+            &[]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_set_position_rejects_bad_input() {
+        assert!(set_position("not a fen", &[]).is_err());
+        assert!(set_position("8/8/8/8/8/8/8/8 w - - 0 1", &[]).is_err());
+
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        for bad in ["e2e5", "é2é4", "e9e4", "\u{0}\u{0}\u{0}\u{0}", "a"] {
+            assert!(set_position(start, &[bad.to_string()]).is_err(), "{}", bad);
+        }
     }
 
     #[test]
@@ -612,7 +2627,689 @@ mod tests {
         let board = Board::default();
         let book = Book::new();
         let history = vec![board.get_hash()];
-        let (mv, _eval) = play_move(&board, &book, 0.5, &history);
-        assert!(!mv.is_empty(), "Should find a move");
+        let result = play_move(
+            &board,
+            &book,
+            &SearchLimits::time(0.5),
+            &history,
+            0,
+            &mut EngineState::new(),
+            &EngineOptions::default(),
+        );
+        assert!(result.best_move.is_some(), "Should find a move");
+    }
+
+    #[test]
+    fn test_search_result_fields() {
+        let (board, history, halfmove_clock) = set_position(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            &[],
+        )
+        .unwrap();
+        let mut engine_state = EngineState::new();
+        let options = EngineOptions {
+            own_book: false,
+            silent: true,
+            ..EngineOptions::default()
+        };
+        let result = play_move(
+            &board,
+            &Book::new(),
+            &SearchLimits::depth(4),
+            &history,
+            halfmove_clock,
+            &mut engine_state,
+            &options,
+        );
+        assert_eq!(result.depth, 4);
+        assert!(result.seldepth >= 4);
+        assert_eq!(result.nodes, engine_state.nodes);
+        assert_eq!(result.pv.first().copied(), result.best_move);
+        assert_eq!(result.pv.get(1).copied(), result.ponder);
+        // The line reaches the horizon, not just as far as the table recalls
+        assert!(result.pv.len() >= 4);
+
+        // The principal variation is a legal line
+        let mut line = board;
+        for &mv in &result.pv {
+            assert!(line.legal(mv));
+            line = line.make_move_new(mv);
+        }
+    }
+
+    #[test]
+    fn test_search_params_roundtrip() {
+        let mut params = SearchParams::default();
+        assert!(params.set("staticnullmargin", 150));
+        assert!(params.set("NullMoveR", 99));
+        assert!(!params.set("NoSuchParam", 1));
+        assert_eq!(params.static_null_margin, 150);
+        assert_eq!(params.null_move_r, 4, "values are clamped to the range");
+        assert!(params.set("FutilityMargin", 90));
+        assert_eq!(params.futility_margin, 90);
+        assert!(params.set("LmpMaxDepth", 0));
+        assert_eq!(params.lmp_max_depth, 0);
+
+        let mut loaded = SearchParams::default();
+        loaded.load_param_string(&params.to_param_string());
+        assert_eq!(loaded, params);
+    }
+
+    #[test]
+    fn test_own_book_option() {
+        let board = Board::default();
+        let history = vec![board.get_hash()];
+        let mut book = Book::new();
+        // An (unsound) book move the search would never choose
+        let book_move = ChessMove::from_str("g2g4").unwrap();
+        book.entry(board.get_hash())
+            .or_default()
+            .insert(book_move, 1);
+
+        let mv = play_move(
+            &board,
+            &book,
+            &SearchLimits::time(0.2),
+            &history,
+            0,
+            &mut EngineState::new(),
+            &EngineOptions::default(),
+        )
+        .best_move
+        .unwrap();
+        assert_eq!(mv, book_move);
+
+        let options = EngineOptions {
+            own_book: false,
+            ..EngineOptions::default()
+        };
+        let mv = play_move(
+            &board,
+            &book,
+            &SearchLimits::time(0.2),
+            &history,
+            0,
+            &mut EngineState::new(),
+            &options,
+        )
+        .best_move
+        .unwrap();
+        assert_ne!(mv, book_move);
+    }
+
+    #[test]
+    fn test_contempt_scores_draws() {
+        // White mates (Qd8# or Qa7#) but could also stalemate with Qc7
+        let fen = "k7/3Q4/1K6/8/8/8/8/8 w - - 0 1";
+        let board = Board::from_str(fen).unwrap();
+
+        let mv = search_fen(fen, &SearchLimits::time(0.2), &EngineOptions::default())
+            .best_move
+            .unwrap();
+        let after = board.make_move_new(mv);
+        assert_eq!(after.status(), chess::BoardStatus::Checkmate);
+
+        // A (contrived) contempt that values a draw above mate picks a stalemate
+        let options = EngineOptions {
+            contempt: -2 * MATE_EVAL,
+            ..EngineOptions::default()
+        };
+        let mv = search_fen(fen, &SearchLimits::time(0.2), &options)
+            .best_move
+            .unwrap();
+        let after = board.make_move_new(mv);
+        assert_eq!(after.status(), chess::BoardStatus::Stalemate);
+    }
+
+    #[test]
+    fn test_handicap_weakens_engine_side_only() {
+        // White has the better placed pieces; each side's handicap takes
+        // away its own positional terms, so the two don't mirror each other
+        let board =
+            Board::from_str("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3")
+                .unwrap();
+        let accumulator = Accumulator::new(&board);
+        let breakdown = evaluation::eval_breakdown(&board);
+        let eval = breakdown.total();
+        let (white, black) = (
+            breakdown.positional(Color::White),
+            breakdown.positional(Color::Black),
+        );
+        assert_ne!(white, black);
+        assert_eq!(
+            handicapped(&board, &accumulator, eval, (Color::White, 100)),
+            eval - white
+        );
+        assert_eq!(
+            handicapped(&board, &accumulator, eval, (Color::Black, 50)),
+            eval + black / 2
+        );
+        assert_eq!(
+            handicapped(&board, &accumulator, eval, (Color::White, 0)),
+            eval
+        );
+    }
+
+    #[test]
+    fn test_engine_state_clear() {
+        let board = Board::default();
+        let book = Book::new();
+        let history = vec![board.get_hash()];
+        let mut engine_state = EngineState::new();
+        play_move(
+            &board,
+            &book,
+            &SearchLimits::time(0.2),
+            &history,
+            0,
+            &mut engine_state,
+            &EngineOptions::default(),
+        );
+        assert!(engine_state.transposition_table.len() > 0);
+        engine_state.clear();
+        assert_eq!(engine_state.transposition_table.len(), 0);
+    }
+
+    #[test]
+    fn test_node_type_children() {
+        assert_eq!(NodeType::Pv.child(0), NodeType::Pv);
+        assert_eq!(NodeType::Pv.child(1), NodeType::Cut);
+        assert_eq!(NodeType::Cut.child(0), NodeType::All);
+        assert_eq!(NodeType::Cut.child(3), NodeType::Cut);
+        assert_eq!(NodeType::All.child(0), NodeType::Cut);
+    }
+
+    #[test]
+    fn test_search_is_color_symmetric() {
+        // The same position with colors swapped and the board mirrored must
+        // get the mirrored move and the negated score
+        let search =
+            |fen: &str| search_fen(fen, &SearchLimits::depth(4), &EngineOptions::default());
+        let white = search("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
+        let black = search("rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 4 4");
+        let mirror = |mv: ChessMove| {
+            let flip = |sq: Square| ALL_SQUARES[sq.to_index() ^ 56];
+            ChessMove::new(
+                flip(mv.get_source()),
+                flip(mv.get_dest()),
+                mv.get_promotion(),
+            )
+        };
+        assert_eq!(black.best_move, white.best_move.map(mirror));
+        assert_eq!(black.score, -white.score);
+    }
+
+    #[test]
+    fn test_quiescence_searches_evasions() {
+        // Nc7+ forks king and queen. Searched one ply deep, the check is only
+        // resolved in quiescence, which must not stand pat in check.
+        let result = search_fen(
+            "q3k3/8/8/1N6/8/8/7P/6K1 w - - 0 1",
+            &SearchLimits::depth(1),
+            &EngineOptions::default(),
+        );
+        assert_eq!(result.best_move.unwrap().to_string(), "b5c7");
+        assert!(result.score > 300, "score {}", result.score);
+    }
+
+    #[test]
+    fn test_prefers_shortest_mate() {
+        // Qf8 mates at once; many other queen moves mate a move later
+        let result = search_fen(
+            "7k/8/6K1/8/8/8/8/5Q2 w - - 0 1",
+            &SearchLimits::depth(6),
+            &EngineOptions::default(),
+        );
+        assert_eq!(result.best_move.unwrap().to_string(), "f1f8");
+        assert_eq!(result.score, MATE_EVAL - 1);
+        // A mate within the searched depth cannot be bettered by going deeper
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn test_fifty_move_rule() {
+        let search =
+            |fen: &str| search_fen(fen, &SearchLimits::depth(3), &EngineOptions::default());
+        assert!(search("k7/8/8/8/8/8/8/KQ6 w - - 0 80").score > 500);
+        // Every move completes the fifty moves
+        assert_eq!(search("k7/8/8/8/8/8/8/KQ6 w - - 99 80").score, 0);
+        // but a mate still counts
+        assert_eq!(
+            search("k7/8/1K6/8/8/8/7Q/8 w - - 99 80").score,
+            MATE_EVAL - 1
+        );
+    }
+
+    #[test]
+    fn test_insufficient_material_in_search() {
+        let options = EngineOptions {
+            contempt: 30,
+            ..EngineOptions::default()
+        };
+        let search = |fen: &str| search_fen(fen, &SearchLimits::depth(4), &options);
+        // Bishops on the same square color can't mate, and king and two
+        // knights can't force it: both are draws, which contempt scores
+        // below zero for the engine
+        assert_eq!(search("4k3/8/8/8/8/8/8/3BKb2 w - - 0 1").score, -30);
+        assert_eq!(search("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1").score, -30);
+    }
+
+    #[test]
+    fn test_evaluation_fades_with_halfmove_clock() {
+        let search =
+            |fen: &str| search_fen(fen, &SearchLimits::depth(2), &EngineOptions::default()).score;
+        // No pawn move or capture can reset the clock, so a win that is still
+        // far off fades as the fifty-move rule comes closer
+        let fresh = search("8/8/8/3k4/8/8/8/R2NK3 w - - 0 1");
+        let late = search("8/8/8/3k4/8/8/8/R2NK3 w - - 80 1");
+        assert!(fresh > 0 && late > 0);
+        assert!(late < fresh / 2, "{} vs {}", late, fresh);
+        assert_eq!(search("8/8/8/3k4/8/8/8/R2NK3 w - - 10 1"), fresh);
+    }
+
+    #[test]
+    fn test_eval_file_option() {
+        let mut options = EngineOptions {
+            own_book: false,
+            silent: true,
+            ..EngineOptions::default()
+        };
+        let mut engine_state = EngineState::new();
+        set_engine_option(
+            &mut options,
+            &mut engine_state,
+            "EvalFile",
+            "/nonexistent/net.nnue",
+        );
+        assert!(engine_state.network().is_none());
+
+        // A network of zeros scores every position as level
+        let path = std::env::temp_dir().join("xewali_test_zero.nnue");
+        std::fs::write(&path, vec![0u8; nnue::FILE_BYTES]).unwrap();
+        set_engine_option(
+            &mut options,
+            &mut engine_state,
+            "EvalFile",
+            path.to_str().unwrap(),
+        );
+        let _ = std::fs::remove_file(&path);
+        assert!(engine_state.network().is_some());
+        let (board, history, halfmove_clock) = set_position(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            &[],
+        )
+        .unwrap();
+        // The network, not the hand-written terms, evaluates the position
+        assert_eq!(engine_state.static_eval(&board, Variant::Standard), 0);
+        let result = play_move(
+            &board,
+            &Book::new(),
+            &SearchLimits::depth(3),
+            &history,
+            halfmove_clock,
+            &mut engine_state,
+            &options,
+        );
+        assert_eq!(result.score, 0);
+
+        // Scores the network left in the transposition table go with it
+        assert!(engine_state.transposition_table.len() > 0);
+        set_engine_option(&mut options, &mut engine_state, "EvalFile", "<empty>");
+        assert!(engine_state.network().is_none());
+        assert_eq!(engine_state.transposition_table.len(), 0);
+    }
+
+    #[test]
+    fn test_repetition() {
+        let search = |moves: &[&str]| {
+            let moves: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+            let (board, history, halfmove_clock) = set_position(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                &moves,
+            )
+            .unwrap();
+            play_move(
+                &board,
+                &Book::new(),
+                &SearchLimits::depth(4),
+                &history,
+                halfmove_clock,
+                &mut EngineState::new(),
+                &EngineOptions {
+                    own_book: false,
+                    silent: true,
+                    contempt: -300,
+                    ..EngineOptions::default()
+                },
+            )
+        };
+        // Black, who wants a draw, repeats the start position a third time
+        let result = search(&["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"]);
+        assert_eq!(result.best_move, Some(ChessMove::from_str("f6g8").unwrap()));
+        assert_eq!(result.score, -300);
+        // A second occurrence is no draw yet, and White avoids the third
+        let result = search(&["g1f3", "g8f6", "f3g1"]);
+        assert_ne!(result.score, -300);
+    }
+
+    #[test]
+    fn test_eval_cache() {
+        let mut cache = EvalCache::new();
+        let board = Board::default();
+        assert_eq!(cache.get(&board, Variant::Standard), None);
+        cache.insert(&board, 42);
+        assert_eq!(cache.get(&board, Variant::Standard), Some(42));
+        // Evaluations under another variant's rules don't carry over
+        assert_eq!(cache.get(&board, Variant::Antichess), None);
+        cache.insert(&board, 7);
+        assert_eq!(cache.get(&board, Variant::Standard), None);
+    }
+
+    #[test]
+    fn test_upcoming_repetition() {
+        // The position after `moves` from the start, and the hashes of the
+        // positions before it
+        let line = |moves: &[&str]| {
+            let mut board = Board::default();
+            let mut history = Vec::new();
+            for mv in moves {
+                history.push(board.get_hash());
+                board = board.make_move_new(ChessMove::from_str(mv).unwrap());
+            }
+            (board, history)
+        };
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6"];
+        // Ng1 would repeat the position after the third move, but only for
+        // the second time
+        let (board, history) = line(&shuffle);
+        assert!(!upcoming_repetition(&board, &history, history.len()));
+        // Inside the search tree once is enough
+        assert!(upcoming_repetition(&board, &history, 3));
+        // After another round, Ng1 makes it a threefold repetition
+        let (board, history) = line(&[&shuffle[..], &shuffle[2..]].concat());
+        assert!(upcoming_repetition(&board, &history, history.len()));
+    }
+
+    #[test]
+    fn test_easy_move() {
+        // Black's queen just took the knight on f3, and only White's queen
+        // can take back
+        let limits = SearchLimits {
+            flexible_time: true,
+            ..SearchLimits::time(10.0)
+        };
+        let result = search_fen(
+            "rnb1kbnr/pppp1ppp/8/4p3/4P3/5q2/PPPP1P1P/RNBQKB1R w KQkq - 0 3",
+            &limits,
+            &EngineOptions::default(),
+        );
+        assert_eq!(result.best_move, Some(ChessMove::from_str("d1f3").unwrap()));
+        assert!(result.time < Duration::from_secs(5), "{:?}", result.time);
+    }
+
+    #[test]
+    fn test_first_iteration_completes_without_time() {
+        // With no time at all, the free queen is still taken rather than the
+        // first legal move played
+        let limits = SearchLimits {
+            max_time: Some(0.0),
+            ..SearchLimits::time(0.0)
+        };
+        let result = search_fen(
+            "4k3/8/q7/8/8/8/8/R3K3 w - - 0 1",
+            &limits,
+            &EngineOptions::default(),
+        );
+        assert_eq!(result.best_move, Some(ChessMove::from_str("a1a6").unwrap()));
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let limits = SearchLimits {
+            nodes: Some(5000),
+            ..SearchLimits::depth(64)
+        };
+        let result = search_fen(START_POSITION, &limits, &EngineOptions::default());
+        assert_eq!(result.nodes, 5000);
+        assert!(result.depth > 0 && result.depth < 64);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn test_soft_and_hard_time_limits() {
+        let limits = SearchLimits {
+            max_time: Some(30.0),
+            ..SearchLimits::time(0.05)
+        };
+        let result = search_fen(START_POSITION, &limits, &EngineOptions::default());
+        // The iteration running at the soft limit completes, and no new one
+        // is started after it
+        assert!(result.time >= Duration::from_millis(50));
+        assert!(result.time < Duration::from_secs(15), "{:?}", result.time);
+        assert!(result.depth > 0);
+    }
+
+    #[test]
+    fn test_nodes_time() {
+        let options = EngineOptions {
+            nodes_time: 100,
+            ..EngineOptions::default()
+        };
+        let search = || {
+            search_fen(
+                "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+                &SearchLimits::time(0.5),
+                &options,
+            )
+        };
+        // Half a second is 50000 nodes, counted the same on every run
+        let first = search();
+        assert!(first.nodes >= 50_000 && first.nodes < 50_000 + DEFAULT_TIME_CHECK_INTERVAL);
+        let second = search();
+        assert_eq!(first.nodes, second.nodes);
+        assert_eq!(first.best_move, second.best_move);
+    }
+
+    #[test]
+    fn test_promotion_race_extension() {
+        // The knight cannot catch the pawn, which queens two moves on; a
+        // two-ply search only sees the queen with the push to the 7th and the
+        // promotion extended
+        let result = search_fen(
+            "k7/8/5P2/8/8/8/n7/7K w - - 0 1",
+            &SearchLimits::depth(2),
+            &EngineOptions::default(),
+        );
+        assert_eq!(result.best_move, Some(ChessMove::from_str("f6f7").unwrap()));
+        assert!(result.score > 300, "{}", result.score);
+    }
+
+    #[test]
+    fn test_capture_moves_match_filtered_legal_moves() {
+        let board =
+            Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let mut masked: Vec<ChessMove> = capture_moves(&board).collect();
+        let mut filtered: Vec<ChessMove> = MoveGen::new_legal(&board)
+            .filter(|&mv| board.piece_on(mv.get_dest()).is_some())
+            .collect();
+        masked.sort_by_key(|mv| mv.to_string());
+        filtered.sort_by_key(|mv| mv.to_string());
+        assert_eq!(masked, filtered);
+        assert!(!masked.is_empty());
+
+        // En passant is generated too, but not the bishop move to its square
+        let board = Board::from_str("1B2k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let en_passant = ChessMove::from_str("e5d6").unwrap();
+        let captures: Vec<ChessMove> = capture_moves(&board).collect();
+        assert_eq!(captures, vec![en_passant]);
+        assert!(is_capture(&board, en_passant));
+        assert!(!is_capture(&board, ChessMove::from_str("b8d6").unwrap()));
+    }
+
+    #[test]
+    fn test_evasion_ordering() {
+        // The queen on e5 checks down the e-file
+        let board = Board::from_str("4k3/8/8/4q3/3P4/2N5/8/R3K3 w - - 0 1").unwrap();
+        let moves = ordered_moves(&board, &EngineState::new(), Variant::Standard);
+        let sources: Vec<&str> = moves.iter().map(|m| m.2).collect();
+        assert_eq!(moves[0].0.to_string(), "d4e5");
+        assert_eq!(sources[0], "evasion: capture checker");
+        assert_eq!(sources[1], "evasion: block");
+        assert_eq!(moves[1].0.to_string(), "c3e2");
+        assert_eq!(sources.last(), Some(&"evasion: losing block"));
+        assert_eq!(moves.last().unwrap().0.to_string(), "c3e4");
+        assert!(sources[2..sources.len() - 1]
+            .iter()
+            .all(|&s| s == "evasion: king move"));
+    }
+
+    #[test]
+    fn test_hashfull() {
+        assert_eq!(hashfull(0, 1000), 0);
+        assert_eq!(hashfull(250, 1000), 250);
+        assert_eq!(hashfull(5, 10), 500);
+        assert_eq!(hashfull(10, 10), 1000);
+
+        let mut engine_state = EngineState::new();
+        engine_state.set_hash_mb(1);
+        play_move(
+            &Board::default(),
+            &Book::new(),
+            &SearchLimits::depth(5),
+            &[Board::default().get_hash()],
+            0,
+            &mut engine_state,
+            &EngineOptions {
+                own_book: false,
+                silent: true,
+                ..EngineOptions::default()
+            },
+        );
+        assert!(engine_state.hashfull() > 0);
+    }
+
+    #[test]
+    fn test_score_bound_tags() {
+        assert_eq!(ScoreBound::Exact.uci_tag(), "");
+        assert_eq!(ScoreBound::Lower.uci_tag(), " lowerbound");
+        assert_eq!(ScoreBound::Upper.uci_tag(), " upperbound");
+        assert_eq!(ScoreBound::Exact.json_field(), "");
+        assert_eq!(ScoreBound::Lower.json_field(), ",\"bound\":\"lower\"");
+    }
+
+    #[test]
+    fn test_hash_size() {
+        let mut engine_state = EngineState::new();
+        assert_eq!(engine_state.hash_mb(), DEFAULT_HASH_MB);
+        engine_state.set_hash_mb(128);
+        assert_eq!(engine_state.hash_mb(), 128);
+        engine_state.set_hash_mb(0);
+        assert_eq!(engine_state.hash_mb(), 1);
+
+        assert_eq!(auto_hash_mb(2048), 512);
+        assert_eq!(auto_hash_mb(64 * 1024), 1024);
+        assert_eq!(auto_hash_mb(0), 1);
+    }
+
+    #[test]
+    fn test_placeholder_keeps_settings() {
+        let mut engine_state = EngineState::new();
+        engine_state.set_hash_mb(64);
+        engine_state.set_tt_bucket_size(8);
+        engine_state.params.null_move_r = 4;
+        let placeholder = engine_state.placeholder();
+        assert_eq!(placeholder.hash_mb(), 64);
+        assert_eq!(placeholder.tt_bucket_size(), 8);
+        assert_eq!(placeholder.params, engine_state.params);
+        assert_eq!(placeholder.transposition_table.capacity(), 0);
+    }
+
+    #[test]
+    fn test_see() {
+        let see_of = |fen: &str, mv: &str| {
+            let board = Board::from_str(fen).unwrap();
+            see(&board, moves::parse_uci_move(&board, mv).unwrap())
+        };
+        // Undefended pawn
+        assert_eq!(see_of("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5"), 100);
+        // Queen takes a pawn defended by a pawn
+        assert_eq!(see_of("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), -800);
+        // Rooks doubled behind each other: the second rook wins the exchange
+        // against a single defender
+        assert_eq!(see_of("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+        // Taking a defended knight with a bishop is about even; stopping
+        // after the first capture is allowed
+        assert_eq!(see_of("4k3/4p3/3n4/8/1B6/8/8/4K3 w - - 0 1", "b4d6"), -10);
+    }
+
+    #[test]
+    fn test_ordered_moves() {
+        // White can take the queen with the e-pawn
+        let board =
+            Board::from_str("rnb1kbnr/pppp1ppp/8/4p3/3q4/2N1P3/PPPP1PPP/R1BQKBNR w KQkq - 0 1")
+                .unwrap();
+        let engine_state = EngineState::new();
+        let moves = ordered_moves(&board, &engine_state, Variant::Standard);
+        assert_eq!(moves.len(), MoveGen::new_legal(&board).len());
+        assert_eq!(moves[0].0.to_string(), "e3d4");
+        assert_eq!(moves[0].2, "mvv-lva");
+        assert!(moves.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        // A stored best move is tried first
+        let quiet = moves.last().unwrap().0;
+        engine_state.transposition_table.insert(
+            board.get_hash(),
+            TTEntry {
+                depth: 1,
+                eval: 0,
+                flag: TTFlag::Exact,
+                best_move: Some(quiet),
+            },
+        );
+        let moves = ordered_moves(&board, &engine_state, Variant::Standard);
+        assert_eq!(moves[0].0, quiet);
+        assert_eq!(moves[0].2, "tt move");
+    }
+
+    #[test]
+    fn test_history_orders_quiet_moves() {
+        let board =
+            Board::from_str("rnb1kbnr/pppp1ppp/8/4p3/3q4/2N1P3/PPPP1PPP/R1BQKBNR w KQkq - 0 1")
+                .unwrap();
+        let mut engine_state = EngineState::new();
+        let quiet = moves::parse_uci_move(&board, "a2a3").unwrap();
+        engine_state
+            .history
+            .butterfly
+            .reward(Color::White, quiet, 3);
+
+        // The rewarded move leads the quiet moves but stays behind the capture
+        let moves = ordered_moves(&board, &engine_state, Variant::Standard);
+        assert_eq!(moves[0].0.to_string(), "e3d4");
+        let first_quiet = moves.iter().position(|m| m.2 != "mvv-lva").unwrap();
+        assert_eq!(moves[first_quiet].0, quiet);
+        assert_eq!(moves[first_quiet].2, "history");
+
+        // Searches reward the quiet moves that cut off
+        play_move(
+            &Board::default(),
+            &Book::new(),
+            &SearchLimits::depth(4),
+            &[Board::default().get_hash()],
+            0,
+            &mut engine_state,
+            &EngineOptions {
+                own_book: false,
+                silent: true,
+                ..EngineOptions::default()
+            },
+        );
+        let start_moves = ordered_moves(&Board::default(), &engine_state, Variant::Standard);
+        assert!(start_moves.iter().any(|m| m.2 == "history"));
+        engine_state.clear();
+        let moves = ordered_moves(&board, &engine_state, Variant::Standard);
+        assert!(moves.iter().all(|m| m.2 != "history"));
     }
 }
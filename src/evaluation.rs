@@ -2,10 +2,35 @@
 // Rust port: 2024
 // email: himangshu.saikia.iitg@gmail.com
 
-use chess::{BitBoard, Board, BoardStatus, Color, File, Piece, Rank, Square, EMPTY};
+use chess::{BitBoard, Board, BoardStatus, ChessMove, Color, File, Piece, Rank, Square, EMPTY};
 
-/// Mate evaluation score
-pub const MATE_EVAL: f64 = 1e6;
+use crate::kpk;
+use crate::nnue::{self, Network};
+
+/// Mate evaluation score, in centipawns. The search scores a mate `ply`
+/// plies from the root as `MATE_EVAL - ply`, so shorter mates score higher.
+pub const MATE_EVAL: i32 = 1_000_000;
+
+/// Longest mate, in plies, that scores can tell apart from ordinary ones
+pub const MAX_MATE_PLY: i32 = 1000;
+
+/// Scores at least this large (in absolute value) are mates
+pub const MATE_BOUND: i32 = MATE_EVAL - MAX_MATE_PLY;
+
+/// Whether `score` announces a forced mate for either side
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_BOUND
+}
+
+/// Moves to mate for a mate score, as UCI reports it: positive when the side
+/// the score belongs to mates, negative when it gets mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    if !is_mate_score(score) {
+        return None;
+    }
+    let moves = (MATE_EVAL - score.abs() + 1) / 2;
+    Some(if score > 0 { moves } else { -moves })
+}
 
 /// Piece values
 pub const KING_VAL: i32 = 20000;
@@ -15,15 +40,43 @@ pub const BISHOP_VAL: i32 = 330;
 pub const KNIGHT_VAL: i32 = 320;
 pub const PAWN_VAL: i32 = 100;
 
-/// Material threshold for endgame detection
-const ENDGAME_THRESHOLD: i32 = 2000;
+/// Piece values in the endgame, which the ones above blend into as pieces
+/// come off: pawns and rooks gain on an open board, knights lose reach
+pub const QUEEN_VAL_EG: i32 = 950;
+pub const ROOK_VAL_EG: i32 = 540;
+pub const BISHOP_VAL_EG: i32 = 320;
+pub const KNIGHT_VAL_EG: i32 = 290;
+pub const PAWN_VAL_EG: i32 = 120;
+
+/// Score of an endgame known to be won, such as a king and pawn against king
+/// win from the bitbase: more than any material balance, less than any mate
+pub const KNOWN_WIN: i32 = 10_000;
+
+/// Game phase with all pieces on the board, counting 1 for each knight and
+/// bishop, 2 for each rook and 4 for each queen; 0 is a pawn endgame
+pub const MAX_PHASE: i32 = 24;
+
+/// How far from the endgame `board` is, from `MAX_PHASE` down to 0 with only
+/// kings and pawns. Promotions can't push it past `MAX_PHASE`.
+pub fn game_phase(board: &Board) -> i32 {
+    let phase = count_bits(*board.pieces(Piece::Knight) | *board.pieces(Piece::Bishop))
+        + 2 * count_bits(*board.pieces(Piece::Rook))
+        + 4 * count_bits(*board.pieces(Piece::Queen));
+    phase.min(MAX_PHASE)
+}
+
+/// Blend a middlegame value `mg` and an endgame value `eg` by `phase`, so
+/// that terms change smoothly as pieces come off the board
+fn taper(mg: i32, eg: i32, phase: i32) -> i32 {
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
 
 // Piece-square tables (from White's perspective at the bottom, index 0 = A1)
 // The chess crate uses A1=0, H1=7, A8=56, H8=63
 
-/// White Pawn table (A1=0 ... H8=63)
+/// White Pawn Middlegame table (A1=0 ... H8=63)
 #[rustfmt::skip]
-const WHITE_PAWN_TABLE: [i32; 64] = [
+pub(crate) const WHITE_PAWN_MG_TABLE: [i32; 64] = [
     0,  0,  0,  0,  0,  0,  0,  0,
     5, 10, 10,-20,-20, 10, 10,  5,
     5, -5,-10,  0,  0,-10, -5,  5,
@@ -34,9 +87,9 @@ const WHITE_PAWN_TABLE: [i32; 64] = [
     0,  0,  0,  0,  0,  0,  0,  0,
 ];
 
-/// Black Pawn table
+/// Black Pawn Middlegame table
 #[rustfmt::skip]
-const BLACK_PAWN_TABLE: [i32; 64] = [
+pub(crate) const BLACK_PAWN_MG_TABLE: [i32; 64] = [
     0,  0,  0,  0,  0,  0,  0,  0,
    50, 50, 50, 50, 50, 50, 50, 50,
    10, 10, 20, 30, 30, 20, 10, 10,
@@ -47,9 +100,35 @@ const BLACK_PAWN_TABLE: [i32; 64] = [
     0,  0,  0,  0,  0,  0,  0,  0,
 ];
 
-/// White Knight table
+/// White Pawn Endgame table
+#[rustfmt::skip]
+pub(crate) const WHITE_PAWN_EG_TABLE: [i32; 64] = [
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    5,  5,  5,  5,  5,  5,  5,  5,
+   10, 10, 10, 10, 10, 10, 10, 10,
+   20, 20, 20, 20, 20, 20, 20, 20,
+   35, 35, 35, 35, 35, 35, 35, 35,
+   60, 60, 60, 60, 60, 60, 60, 60,
+    0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+/// Black Pawn Endgame table
+#[rustfmt::skip]
+pub(crate) const BLACK_PAWN_EG_TABLE: [i32; 64] = [
+    0,  0,  0,  0,  0,  0,  0,  0,
+   60, 60, 60, 60, 60, 60, 60, 60,
+   35, 35, 35, 35, 35, 35, 35, 35,
+   20, 20, 20, 20, 20, 20, 20, 20,
+   10, 10, 10, 10, 10, 10, 10, 10,
+    5,  5,  5,  5,  5,  5,  5,  5,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+/// White Knight Middlegame table
 #[rustfmt::skip]
-const WHITE_KNIGHT_TABLE: [i32; 64] = [
+pub(crate) const WHITE_KNIGHT_MG_TABLE: [i32; 64] = [
    -50,-40,-30,-30,-30,-30,-40,-50,
    -40,-20,  0,  5,  5,  0,-20,-40,
    -30,  5, 10, 15, 15, 10,  5,-30,
@@ -60,22 +139,48 @@ const WHITE_KNIGHT_TABLE: [i32; 64] = [
    -50,-40,-30,-30,-30,-30,-40,-50,
 ];
 
-/// Black Knight table
+/// Black Knight Middlegame table
 #[rustfmt::skip]
-const BLACK_KNIGHT_TABLE: [i32; 64] = [
+pub(crate) const BLACK_KNIGHT_MG_TABLE: [i32; 64] = [
    -50,-40,-30,-30,-30,-30,-40,-50,
    -40,-20,  0,  0,  0,  0,-20,-40,
    -30,  0, 10, 15, 15, 10,  0,-30,
    -30,  5, 15, 20, 20, 15,  5,-30,
    -30,  0, 15, 20, 20, 15,  0,-30,
-   -30,  0, 10, 15, 15, 10,  0,-30,
+   -30,  5, 10, 15, 15, 10,  5,-30,
    -40,-20,  0,  5,  5,  0,-20,-40,
    -50,-40,-30,-30,-30,-30,-40,-50,
 ];
 
-/// White Bishop table
+/// White Knight Endgame table
+#[rustfmt::skip]
+pub(crate) const WHITE_KNIGHT_EG_TABLE: [i32; 64] = [
+   -40,-30,-20,-20,-20,-20,-30,-40,
+   -30,-10,  0,  0,  0,  0,-10,-30,
+   -20,  0, 10, 10, 10, 10,  0,-20,
+   -20,  0, 10, 15, 15, 10,  0,-20,
+   -20,  0, 10, 15, 15, 10,  0,-20,
+   -20,  0, 10, 10, 10, 10,  0,-20,
+   -30,-10,  0,  0,  0,  0,-10,-30,
+   -40,-30,-20,-20,-20,-20,-30,-40,
+];
+
+/// Black Knight Endgame table
 #[rustfmt::skip]
-const WHITE_BISHOP_TABLE: [i32; 64] = [
+pub(crate) const BLACK_KNIGHT_EG_TABLE: [i32; 64] = [
+   -40,-30,-20,-20,-20,-20,-30,-40,
+   -30,-10,  0,  0,  0,  0,-10,-30,
+   -20,  0, 10, 10, 10, 10,  0,-20,
+   -20,  0, 10, 15, 15, 10,  0,-20,
+   -20,  0, 10, 15, 15, 10,  0,-20,
+   -20,  0, 10, 10, 10, 10,  0,-20,
+   -30,-10,  0,  0,  0,  0,-10,-30,
+   -40,-30,-20,-20,-20,-20,-30,-40,
+];
+
+/// White Bishop Middlegame table
+#[rustfmt::skip]
+pub(crate) const WHITE_BISHOP_MG_TABLE: [i32; 64] = [
    -20,-10,-10,-10,-10,-10,-10,-20,
    -10,  5,  0,  0,  0,  0,  5,-10,
    -10, 10, 10, 10, 10, 10, 10,-10,
@@ -86,9 +191,9 @@ const WHITE_BISHOP_TABLE: [i32; 64] = [
    -20,-10,-10,-10,-10,-10,-10,-20,
 ];
 
-/// Black Bishop table
+/// Black Bishop Middlegame table
 #[rustfmt::skip]
-const BLACK_BISHOP_TABLE: [i32; 64] = [
+pub(crate) const BLACK_BISHOP_MG_TABLE: [i32; 64] = [
    -20,-10,-10,-10,-10,-10,-10,-20,
    -10,  0,  0,  0,  0,  0,  0,-10,
    -10,  0,  5, 10, 10,  5,  0,-10,
@@ -99,9 +204,35 @@ const BLACK_BISHOP_TABLE: [i32; 64] = [
    -20,-10,-10,-10,-10,-10,-10,-20,
 ];
 
-/// White Rook table
+/// White Bishop Endgame table
+#[rustfmt::skip]
+pub(crate) const WHITE_BISHOP_EG_TABLE: [i32; 64] = [
+   -15,-10,-10,-10,-10,-10,-10,-15,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+   -10,  0,  5, 10, 10,  5,  0,-10,
+   -10,  0,  5, 10, 10,  5,  0,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -15,-10,-10,-10,-10,-10,-10,-15,
+];
+
+/// Black Bishop Endgame table
+#[rustfmt::skip]
+pub(crate) const BLACK_BISHOP_EG_TABLE: [i32; 64] = [
+   -15,-10,-10,-10,-10,-10,-10,-15,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+   -10,  0,  5, 10, 10,  5,  0,-10,
+   -10,  0,  5, 10, 10,  5,  0,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -15,-10,-10,-10,-10,-10,-10,-15,
+];
+
+/// White Rook Middlegame table
 #[rustfmt::skip]
-const WHITE_ROOK_TABLE: [i32; 64] = [
+pub(crate) const WHITE_ROOK_MG_TABLE: [i32; 64] = [
     0,  0,  0,  5,  5,  0,  0,  0,
    -5,  0,  0,  0,  0,  0,  0, -5,
    -5,  0,  0,  0,  0,  0,  0, -5,
@@ -112,9 +243,9 @@ const WHITE_ROOK_TABLE: [i32; 64] = [
     0,  0,  0,  0,  0,  0,  0,  0,
 ];
 
-/// Black Rook table
+/// Black Rook Middlegame table
 #[rustfmt::skip]
-const BLACK_ROOK_TABLE: [i32; 64] = [
+pub(crate) const BLACK_ROOK_MG_TABLE: [i32; 64] = [
     0,  0,  0,  0,  0,  0,  0,  0,
     5, 10, 10, 10, 10, 10, 10,  5,
    -5,  0,  0,  0,  0,  0,  0, -5,
@@ -125,9 +256,35 @@ const BLACK_ROOK_TABLE: [i32; 64] = [
     0,  0,  0,  5,  5,  0,  0,  0,
 ];
 
-/// White Queen table
+/// White Rook Endgame table
+#[rustfmt::skip]
+pub(crate) const WHITE_ROOK_EG_TABLE: [i32; 64] = [
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+   10, 10, 10, 10, 10, 10, 10, 10,
+    0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+/// Black Rook Endgame table
 #[rustfmt::skip]
-const WHITE_QUEEN_TABLE: [i32; 64] = [
+pub(crate) const BLACK_ROOK_EG_TABLE: [i32; 64] = [
+    0,  0,  0,  0,  0,  0,  0,  0,
+   10, 10, 10, 10, 10, 10, 10, 10,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+/// White Queen Middlegame table
+#[rustfmt::skip]
+pub(crate) const WHITE_QUEEN_MG_TABLE: [i32; 64] = [
    -20,-10,-10, -5, -5,-10,-10,-20,
    -10,  0,  5,  0,  0,  0,  0,-10,
    -10,  5,  5,  5,  5,  5,  0,-10,
@@ -138,9 +295,9 @@ const WHITE_QUEEN_TABLE: [i32; 64] = [
    -20,-10,-10, -5, -5,-10,-10,-20,
 ];
 
-/// Black Queen table
+/// Black Queen Middlegame table
 #[rustfmt::skip]
-const BLACK_QUEEN_TABLE: [i32; 64] = [
+pub(crate) const BLACK_QUEEN_MG_TABLE: [i32; 64] = [
    -20,-10,-10, -5, -5,-10,-10,-20,
    -10,  0,  0,  0,  0,  0,  0,-10,
    -10,  0,  5,  5,  5,  5,  0,-10,
@@ -151,9 +308,35 @@ const BLACK_QUEEN_TABLE: [i32; 64] = [
    -20,-10,-10, -5, -5,-10,-10,-20,
 ];
 
+/// White Queen Endgame table
+#[rustfmt::skip]
+pub(crate) const WHITE_QUEEN_EG_TABLE: [i32; 64] = [
+   -20,-10,-10, -5, -5,-10,-10,-20,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+   -10,  5, 10, 10, 10, 10,  5,-10,
+    -5,  5, 10, 15, 15, 10,  5, -5,
+    -5,  5, 10, 15, 15, 10,  5, -5,
+   -10,  5, 10, 10, 10, 10,  5,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+   -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+/// Black Queen Endgame table
+#[rustfmt::skip]
+pub(crate) const BLACK_QUEEN_EG_TABLE: [i32; 64] = [
+   -20,-10,-10, -5, -5,-10,-10,-20,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+   -10,  5, 10, 10, 10, 10,  5,-10,
+    -5,  5, 10, 15, 15, 10,  5, -5,
+    -5,  5, 10, 15, 15, 10,  5, -5,
+   -10,  5, 10, 10, 10, 10,  5,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+   -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
 /// White King Middlegame table
 #[rustfmt::skip]
-const WHITE_KING_MG_TABLE: [i32; 64] = [
+pub(crate) const WHITE_KING_MG_TABLE: [i32; 64] = [
     20, 30, 10,  0,  0, 10, 30, 20,
     20, 20,  0,  0,  0,  0, 20, 20,
    -10,-20,-20,-20,-20,-20,-20,-10,
@@ -166,7 +349,7 @@ const WHITE_KING_MG_TABLE: [i32; 64] = [
 
 /// Black King Middlegame table
 #[rustfmt::skip]
-const BLACK_KING_MG_TABLE: [i32; 64] = [
+pub(crate) const BLACK_KING_MG_TABLE: [i32; 64] = [
    -30,-40,-40,-50,-50,-40,-40,-30,
    -30,-40,-40,-50,-50,-40,-40,-30,
    -30,-40,-40,-50,-50,-40,-40,-30,
@@ -179,7 +362,7 @@ const BLACK_KING_MG_TABLE: [i32; 64] = [
 
 /// White King Endgame table
 #[rustfmt::skip]
-const WHITE_KING_EG_TABLE: [i32; 64] = [
+pub(crate) const WHITE_KING_EG_TABLE: [i32; 64] = [
    -50,-30,-30,-30,-30,-30,-30,-50,
    -30,-30,  0,  0,  0,  0,-30,-30,
    -30,-10, 20, 30, 30, 20,-10,-30,
@@ -192,7 +375,7 @@ const WHITE_KING_EG_TABLE: [i32; 64] = [
 
 /// Black King Endgame table
 #[rustfmt::skip]
-const BLACK_KING_EG_TABLE: [i32; 64] = [
+pub(crate) const BLACK_KING_EG_TABLE: [i32; 64] = [
    -50,-40,-30,-20,-20,-30,-40,-50,
    -30,-20,-10,  0,  0,-10,-20,-30,
    -30,-10, 20, 30, 30, 20,-10,-30,
@@ -203,47 +386,35 @@ const BLACK_KING_EG_TABLE: [i32; 64] = [
    -50,-30,-30,-30,-30,-30,-30,-50,
 ];
 
-/// Get piece-square table value for a piece at a square
-fn piece_square_value(piece: Piece, color: Color, square: Square, is_endgame: bool) -> i32 {
+/// Middlegame and endgame piece-square table values for a piece at a square
+fn piece_square_values(piece: Piece, color: Color, square: Square) -> (i32, i32) {
     let sq_idx = square.to_index();
-
-    match (piece, color) {
-        (Piece::Pawn, Color::White) => WHITE_PAWN_TABLE[sq_idx],
-        (Piece::Pawn, Color::Black) => BLACK_PAWN_TABLE[sq_idx],
-        (Piece::Knight, Color::White) => WHITE_KNIGHT_TABLE[sq_idx],
-        (Piece::Knight, Color::Black) => BLACK_KNIGHT_TABLE[sq_idx],
-        (Piece::Bishop, Color::White) => WHITE_BISHOP_TABLE[sq_idx],
-        (Piece::Bishop, Color::Black) => BLACK_BISHOP_TABLE[sq_idx],
-        (Piece::Rook, Color::White) => WHITE_ROOK_TABLE[sq_idx],
-        (Piece::Rook, Color::Black) => BLACK_ROOK_TABLE[sq_idx],
-        (Piece::Queen, Color::White) => WHITE_QUEEN_TABLE[sq_idx],
-        (Piece::Queen, Color::Black) => BLACK_QUEEN_TABLE[sq_idx],
-        (Piece::King, Color::White) => {
-            if is_endgame {
-                WHITE_KING_EG_TABLE[sq_idx]
-            } else {
-                WHITE_KING_MG_TABLE[sq_idx]
-            }
-        }
-        (Piece::King, Color::Black) => {
-            if is_endgame {
-                BLACK_KING_EG_TABLE[sq_idx]
-            } else {
-                BLACK_KING_MG_TABLE[sq_idx]
-            }
-        }
-    }
+    let (mg, eg) = match (piece, color) {
+        (Piece::Pawn, Color::White) => (&WHITE_PAWN_MG_TABLE, &WHITE_PAWN_EG_TABLE),
+        (Piece::Pawn, Color::Black) => (&BLACK_PAWN_MG_TABLE, &BLACK_PAWN_EG_TABLE),
+        (Piece::Knight, Color::White) => (&WHITE_KNIGHT_MG_TABLE, &WHITE_KNIGHT_EG_TABLE),
+        (Piece::Knight, Color::Black) => (&BLACK_KNIGHT_MG_TABLE, &BLACK_KNIGHT_EG_TABLE),
+        (Piece::Bishop, Color::White) => (&WHITE_BISHOP_MG_TABLE, &WHITE_BISHOP_EG_TABLE),
+        (Piece::Bishop, Color::Black) => (&BLACK_BISHOP_MG_TABLE, &BLACK_BISHOP_EG_TABLE),
+        (Piece::Rook, Color::White) => (&WHITE_ROOK_MG_TABLE, &WHITE_ROOK_EG_TABLE),
+        (Piece::Rook, Color::Black) => (&BLACK_ROOK_MG_TABLE, &BLACK_ROOK_EG_TABLE),
+        (Piece::Queen, Color::White) => (&WHITE_QUEEN_MG_TABLE, &WHITE_QUEEN_EG_TABLE),
+        (Piece::Queen, Color::Black) => (&BLACK_QUEEN_MG_TABLE, &BLACK_QUEEN_EG_TABLE),
+        (Piece::King, Color::White) => (&WHITE_KING_MG_TABLE, &WHITE_KING_EG_TABLE),
+        (Piece::King, Color::Black) => (&BLACK_KING_MG_TABLE, &BLACK_KING_EG_TABLE),
+    };
+    (mg[sq_idx], eg[sq_idx])
 }
 
-/// Get the base material value for a piece type
-fn piece_value(piece: Piece) -> i32 {
+/// Get the middlegame and endgame material values for a piece type
+fn piece_value(piece: Piece) -> (i32, i32) {
     match piece {
-        Piece::Pawn => PAWN_VAL,
-        Piece::Knight => KNIGHT_VAL,
-        Piece::Bishop => BISHOP_VAL,
-        Piece::Rook => ROOK_VAL,
-        Piece::Queen => QUEEN_VAL,
-        Piece::King => KING_VAL,
+        Piece::Pawn => (PAWN_VAL, PAWN_VAL_EG),
+        Piece::Knight => (KNIGHT_VAL, KNIGHT_VAL_EG),
+        Piece::Bishop => (BISHOP_VAL, BISHOP_VAL_EG),
+        Piece::Rook => (ROOK_VAL, ROOK_VAL_EG),
+        Piece::Queen => (QUEEN_VAL, QUEEN_VAL_EG),
+        Piece::King => (KING_VAL, KING_VAL),
     }
 }
 
@@ -261,6 +432,18 @@ pub enum GameResult {
     Draw,
 }
 
+impl std::fmt::Display for GameResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            GameResult::Ongoing => "game ongoing",
+            GameResult::WhiteWins => "White wins",
+            GameResult::BlackWins => "Black wins",
+            GameResult::Draw => "draw",
+        };
+        write!(f, "{}", text)
+    }
+}
+
 /// Check if the game has ended and return the result
 pub fn has_game_ended(board: &Board) -> GameResult {
     match board.status() {
@@ -287,7 +470,7 @@ pub fn has_game_ended(board: &Board) -> GameResult {
 }
 
 /// Check for insufficient material to mate
-fn is_insufficient_material(board: &Board) -> bool {
+pub(crate) fn is_insufficient_material(board: &Board) -> bool {
     let all_pieces = *board.combined();
     let piece_count = all_pieces.popcnt();
 
@@ -297,106 +480,488 @@ fn is_insufficient_material(board: &Board) -> bool {
     }
 
     // King + minor piece vs King
-    if piece_count == 3 {
-        let knights = *board.pieces(Piece::Knight);
-        let bishops = *board.pieces(Piece::Bishop);
-        if knights.popcnt() == 1 || bishops.popcnt() == 1 {
-            return true;
-        }
+    let knights = *board.pieces(Piece::Knight);
+    let bishops = *board.pieces(Piece::Bishop);
+    if piece_count == 3 && (knights.popcnt() == 1 || bishops.popcnt() == 1) {
+        return true;
     }
 
-    false
+    // Kings and bishops, all on the same square color (KB vs KB included)
+    let light = BitBoard::new(LIGHT_SQUARES);
+    let kings = *board.pieces(Piece::King);
+    all_pieces == kings | bishops && (bishops & light == EMPTY || bishops & !light == EMPTY)
+}
+
+/// Whether the position is king and two knights against a bare king, where
+/// mate is possible but can't be forced, so it is scored as a draw
+fn is_drawish_material(board: &Board) -> bool {
+    let knights = *board.pieces(Piece::Knight);
+    board.combined().popcnt() == 4
+        && knights.popcnt() == 2
+        && [Color::White, Color::Black]
+            .iter()
+            .any(|&color| knights & *board.color_combined(color) == knights)
+}
+
+/// Middlegame and endgame material and piece-square sums of each side,
+/// indexed by `Color::to_index`, kept up to date move by move during the
+/// search instead of being recounted at every evaluation, and blended by the
+/// game phase when evaluating. Kings carry no material.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Accumulator {
+    pub material: [(i32, i32); 2],
+    pub pst: [(i32, i32); 2],
 }
 
-/// Calculate material for one side (without piece-square tables)
-fn calculate_material(board: &Board, color: Color) -> i32 {
-    let mut material = 0;
+impl Accumulator {
+    /// Sums for `board`, counted from scratch
+    pub fn new(board: &Board) -> Self {
+        let mut accumulator = Accumulator::default();
+        for sq in *board.combined() {
+            if let (Some(piece), Some(color)) = (board.piece_on(sq), board.color_on(sq)) {
+                accumulator.add(piece, color, sq);
+            }
+        }
+        accumulator
+    }
+
+    /// Material of each side at `phase`
+    pub fn material(&self, phase: i32) -> [i32; 2] {
+        self.material.map(|(mg, eg)| taper(mg, eg, phase))
+    }
+
+    /// Piece-square sums of each side at `phase`
+    pub fn pst(&self, phase: i32) -> [i32; 2] {
+        self.pst.map(|(mg, eg)| taper(mg, eg, phase))
+    }
+
+    fn add(&mut self, piece: Piece, color: Color, sq: Square) {
+        let i = color.to_index();
+        if piece != Piece::King {
+            let (mg, eg) = piece_value(piece);
+            self.material[i].0 += mg;
+            self.material[i].1 += eg;
+        }
+        let (mg, eg) = piece_square_values(piece, color, sq);
+        self.pst[i].0 += mg;
+        self.pst[i].1 += eg;
+    }
 
-    for piece in [
-        Piece::Pawn,
-        Piece::Knight,
-        Piece::Bishop,
-        Piece::Rook,
-        Piece::Queen,
-    ] {
-        let piece_bb = *board.pieces(piece) & *board.color_combined(color);
-        material += piece_bb.popcnt() as i32 * piece_value(piece);
+    fn remove(&mut self, piece: Piece, color: Color, sq: Square) {
+        let i = color.to_index();
+        if piece != Piece::King {
+            let (mg, eg) = piece_value(piece);
+            self.material[i].0 -= mg;
+            self.material[i].1 -= eg;
+        }
+        let (mg, eg) = piece_square_values(piece, color, sq);
+        self.pst[i].0 -= mg;
+        self.pst[i].1 -= eg;
     }
 
-    material
+    /// Sums after `mv` is made on `board`, which they are the sums of
+    pub fn after_move(&self, board: &Board, mv: ChessMove) -> Self {
+        let mut next = *self;
+        let (source, dest) = (mv.get_source(), mv.get_dest());
+        let us = board.side_to_move();
+        let Some(piece) = board.piece_on(source) else {
+            return next;
+        };
+
+        next.remove(piece, us, source);
+        if let Some(captured) = board.piece_on(dest) {
+            next.remove(captured, !us, dest);
+        } else if piece == Piece::Pawn && source.get_file() != dest.get_file() {
+            // En passant: the captured pawn stands beside the source square
+            let captured = Square::make_square(source.get_rank(), dest.get_file());
+            next.remove(Piece::Pawn, !us, captured);
+        }
+        next.add(mv.get_promotion().unwrap_or(piece), us, dest);
+
+        // Castling is a two-square king move, which brings the rook along
+        let king_files = dest.get_file().to_index() as i32 - source.get_file().to_index() as i32;
+        if piece == Piece::King && king_files.abs() == 2 {
+            let (rook_from, rook_to) = if king_files > 0 {
+                (File::H, File::F)
+            } else {
+                (File::A, File::D)
+            };
+            let rank = source.get_rank();
+            next.remove(Piece::Rook, us, Square::make_square(rank, rook_from));
+            next.add(Piece::Rook, us, Square::make_square(rank, rook_to));
+        }
+        next
+    }
 }
 
 /// Evaluate the position
 /// Returns positive values for White advantage, negative for Black advantage
-pub fn eval(board: &Board) -> f64 {
+pub fn eval(board: &Board) -> i32 {
+    eval_with_draw_score(board, 0)
+}
+
+/// Evaluate the position, scoring drawn positions (stalemate, insufficient
+/// material) as `draw_score` instead of 0 so the search can apply contempt
+pub fn eval_with_draw_score(board: &Board, draw_score: i32) -> i32 {
+    eval_accumulated(board, &Accumulator::new(board), draw_score)
+}
+
+/// Evaluate the position as `eval_with_draw_score` does, taking material and
+/// piece-square sums from `accumulator` rather than counting them
+pub fn eval_accumulated(board: &Board, accumulator: &Accumulator, draw_score: i32) -> i32 {
+    eval_with(board, draw_score, || {
+        accumulated_breakdown(board, accumulator).total()
+    })
+}
+
+/// Evaluate the position as `eval_accumulated` does, with `network` in
+/// place of the hand-written terms. Ended games, drawn material and king
+/// and pawn against king are still scored exactly.
+pub fn eval_network(
+    board: &Board,
+    network: &Network,
+    accumulator: &nnue::Accumulator,
+    draw_score: i32,
+) -> i32 {
+    eval_with(board, draw_score, || network.evaluate(board, accumulator))
+}
+
+/// Score of the position, exact if the game has ended or the material
+/// decides it, and otherwise `static_eval()`
+fn eval_with(board: &Board, draw_score: i32, static_eval: impl FnOnce() -> i32) -> i32 {
     // Check for game end
     match has_game_ended(board) {
         GameResult::WhiteWins => return MATE_EVAL,
         GameResult::BlackWins => return -MATE_EVAL,
-        GameResult::Draw => return 0.0,
+        GameResult::Draw => return draw_score,
         GameResult::Ongoing => {}
     }
+    if is_drawish_material(board) {
+        return draw_score;
+    }
 
-    let mut white_material: i32 = 0;
-    let mut black_material: i32 = 0;
-
-    // Calculate raw material (without king) for endgame detection
-    let white_raw_material = calculate_material(board, Color::White);
-    let black_raw_material = calculate_material(board, Color::Black);
-    let is_endgame =
-        white_raw_material < ENDGAME_THRESHOLD && black_raw_material < ENDGAME_THRESHOLD;
-
-    // Calculate material with piece-square tables
-    for color in [Color::White, Color::Black] {
-        for piece in [
-            Piece::Pawn,
-            Piece::Knight,
-            Piece::Bishop,
-            Piece::Rook,
-            Piece::Queen,
-            Piece::King,
-        ] {
-            let piece_bb = *board.pieces(piece) & *board.color_combined(color);
+    // King and pawn against king is scored exactly from the bitbase, a win
+    // the more the further the pawn has advanced
+    if let Some(result) = kpk::probe(board) {
+        let rank = board.pieces(Piece::Pawn).to_square().get_rank().to_index() as i32;
+        return match result {
+            GameResult::WhiteWins => KNOWN_WIN + PAWN_VAL + rank,
+            GameResult::BlackWins => -(KNOWN_WIN + PAWN_VAL + 7 - rank),
+            _ => draw_score,
+        };
+    }
 
-            for sq in piece_bb {
-                let base_value = if piece == Piece::King {
-                    0
-                } else {
-                    piece_value(piece)
-                };
-                let psq_value = piece_square_value(piece, color, sq, is_endgame);
+    // A bare king against mating material is lost; scoring it a known win
+    // keeps it above the king and pawn wins that promote into it
+    let total = static_eval();
+    if can_mop_up(board, Color::White) {
+        total + KNOWN_WIN
+    } else if can_mop_up(board, Color::Black) {
+        total - KNOWN_WIN
+    } else {
+        total
+    }
+}
 
-                if color == Color::White {
-                    white_material += base_value + psq_value;
-                } else {
-                    black_material += base_value + psq_value;
-                }
-            }
-        }
+/// The terms of the static evaluation for each side, indexed by
+/// `Color::to_index` (White first). All values are in centipawns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalBreakdown {
+    /// Game phase, from `MAX_PHASE` with all pieces on the board down to 0
+    pub phase: i32,
+    pub material: [i32; 2],
+    pub pst: [i32; 2],
+    pub mobility: [i32; 2],
+    /// Fades out towards the endgame, where king safety matters less
+    pub king_safety: [i32; 2],
+    /// Having castled or being able to, or a king stuck in the centre; fades
+    /// out towards the endgame too
+    pub castling: [i32; 2],
+    /// Knights and bishops still at home, in the opening only
+    pub development: [i32; 2],
+    /// Attacks on the centre and the extended centre; fades out towards the
+    /// endgame
+    pub center: [i32; 2],
+    /// Holes in the pawn structure near the centre and the king
+    pub weak_squares: [i32; 2],
+    pub passed_pawns: [i32; 2],
+    /// Pawn majorities, and minority attacks against them
+    pub pawn_majorities: [i32; 2],
+    pub bishop_pair: [i32; 2],
+    /// Rooks behind passed pawns, and rooks defending each other
+    pub rooks: [i32; 2],
+    /// Enemy pieces attacked by cheaper pieces
+    pub threats: [i32; 2],
+    /// Penalty for undefended pieces, more for those under attack
+    pub hanging: [i32; 2],
+    /// Driving a bare enemy king to the edge, with our king close by
+    pub mop_up: [i32; 2],
+    /// What the total is scaled by, out of `SCALE_NORMAL`, shrinking it in
+    /// drawish endgames
+    pub scale: i32,
+}
+
+impl EvalBreakdown {
+    /// Final evaluation in centipawns: material difference + mobility
+    /// + king safety + castling + development + centre control + weak squares
+    /// + passed pawns + pawn majorities + bishop pair + rooks + threats + hanging pieces
+    /// + mop-up, scaled by `scale`
+    pub fn total(&self) -> i32 {
+        self.unscaled_total() * self.scale / SCALE_NORMAL
+    }
+
+    /// Everything but material of the side `color`, scaled like the total:
+    /// what the evaluation makes of where its pieces and pawns stand
+    pub fn positional(&self, color: Color) -> i32 {
+        let i = color.to_index();
+        (self.side(i) - self.material[i]) * self.scale / SCALE_NORMAL
     }
 
-    // Calculate mobility (influence)
-    let white_influence = calculate_mobility(board, Color::White);
-    let black_influence = calculate_mobility(board, Color::Black);
+    fn unscaled_total(&self) -> i32 {
+        self.side(0) - self.side(1)
+    }
+
+    /// Sum of every term of the side at `i`, before scaling
+    fn side(&self, i: usize) -> i32 {
+        self.material[i]
+            + self.pst[i]
+            + self.mobility[i]
+            + self.king_safety[i]
+            + self.castling[i]
+            + self.development[i]
+            + self.center[i]
+            + self.weak_squares[i]
+            + self.passed_pawns[i]
+            + self.pawn_majorities[i]
+            + self.bishop_pair[i]
+            + self.rooks[i]
+            + self.threats[i]
+            + self.hanging[i]
+            + self.mop_up[i]
+    }
+}
+
+/// Compute every term of the static evaluation separately. Unlike `eval`,
+/// this doesn't check whether the game has ended or look the position up in
+/// the king and pawn against king bitbase.
+pub fn eval_breakdown(board: &Board) -> EvalBreakdown {
+    accumulated_breakdown(board, &Accumulator::new(board))
+}
+
+/// Compute every term of the static evaluation as `eval_breakdown` does,
+/// but blended at `phase` instead of the board's own game phase. At
+/// `MAX_PHASE` and 0 this gives the middlegame and the endgame value of each
+/// term that blends the two, which the tuner scales separately.
+pub fn eval_breakdown_at(board: &Board, phase: i32) -> EvalBreakdown {
+    phased_breakdown(board, &Accumulator::new(board), phase)
+}
 
-    // Avoid division by zero
-    let influence_ratio = if black_influence > 0 {
-        white_influence as f64 / black_influence as f64
-    } else if white_influence > 0 {
-        10.0 // White has all the influence
+/// Compute every term of the static evaluation as `eval_breakdown` does,
+/// taking material and piece-square sums from `accumulator`
+pub fn accumulated_breakdown(board: &Board, accumulator: &Accumulator) -> EvalBreakdown {
+    phased_breakdown(board, accumulator, game_phase(board))
+}
+
+/// Every term of the static evaluation at `phase`, with material and
+/// piece-square sums from `accumulator`
+fn phased_breakdown(board: &Board, accumulator: &Accumulator, phase: i32) -> EvalBreakdown {
+    let material = accumulator.material(phase);
+    let pst = accumulator.pst(phase);
+
+    let mobility = [
+        calculate_mobility(board, Color::White, phase),
+        calculate_mobility(board, Color::Black, phase),
+    ];
+
+    // King safety, fading out towards the endgame
+    let king_safety = [
+        taper(king_safety(board, Color::White), 0, phase),
+        taper(king_safety(board, Color::Black), 0, phase),
+    ];
+
+    let mut breakdown = EvalBreakdown {
+        phase,
+        material,
+        pst,
+        mobility,
+        king_safety,
+        castling: [
+            taper(castling(board, Color::White), 0, phase),
+            taper(castling(board, Color::Black), 0, phase),
+        ],
+        development: [
+            development(board, Color::White, phase),
+            development(board, Color::Black, phase),
+        ],
+        center: [
+            taper(center_control(board, Color::White), 0, phase),
+            taper(center_control(board, Color::Black), 0, phase),
+        ],
+        weak_squares: [
+            weak_squares(board, Color::White, phase),
+            weak_squares(board, Color::Black, phase),
+        ],
+        passed_pawns: [
+            passed_pawns(board, Color::White, phase),
+            passed_pawns(board, Color::Black, phase),
+        ],
+        pawn_majorities: [
+            pawn_majorities(board, Color::White, phase),
+            pawn_majorities(board, Color::Black, phase),
+        ],
+        bishop_pair: [
+            bishop_pair(board, Color::White, phase),
+            bishop_pair(board, Color::Black, phase),
+        ],
+        rooks: [
+            rooks(board, Color::White, phase),
+            rooks(board, Color::Black, phase),
+        ],
+        threats: [
+            threats(board, Color::White, phase),
+            threats(board, Color::Black, phase),
+        ],
+        hanging: [
+            hanging_pieces(board, Color::White, phase),
+            hanging_pieces(board, Color::Black, phase),
+        ],
+        mop_up: [mop_up(board, Color::White), mop_up(board, Color::Black)],
+        scale: SCALE_NORMAL,
+    };
+    let strong = if breakdown.unscaled_total() >= 0 {
+        Color::White
     } else {
-        1.0 // No influence from either side
+        Color::Black
     };
+    breakdown.scale = scale_factor(board, strong, &material, phase);
+    breakdown
+}
 
-    // King safety (skipped in endgame)
-    let king_safety_score = if !is_endgame {
-        king_safety(board, Color::White, is_endgame) - king_safety(board, Color::Black, is_endgame)
-    } else {
-        0
+/// `board` with the colors swapped and the ranks flipped, so each side
+/// stands where the other did, castling rights and en passant included
+pub fn mirror(board: &Board) -> Board {
+    let fen = board.to_string();
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let swap_case = |text: &str| -> String {
+        text.chars()
+            .map(|c| {
+                if c.is_ascii_uppercase() {
+                    c.to_ascii_lowercase()
+                } else {
+                    c.to_ascii_uppercase()
+                }
+            })
+            .collect()
+    };
+
+    let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+    let side = if fields[1] == "w" { "b" } else { "w" };
+    let castling: String = "KQkq"
+        .chars()
+        .filter(|&c| swap_case(fields[2]).contains(c))
+        .collect();
+    let en_passant = match fields[3].as_bytes() {
+        [file, b'3'] => format!("{}6", *file as char),
+        [file, b'6'] => format!("{}3", *file as char),
+        _ => "-".to_string(),
     };
+    let mirrored = format!(
+        "{} {} {} {} {}",
+        placement.join("/"),
+        side,
+        if castling.is_empty() { "-" } else { &castling },
+        en_passant,
+        fields[4..].join(" ")
+    );
+    mirrored
+        .parse()
+        .expect("a mirrored legal position is legal")
+}
+
+/// Check that the evaluation of `board` is the exact negative of its
+/// mirror's, term by term, naming the first term that isn't. The scale
+/// factor is only checked through the total, as a level position picks
+/// White as the stronger side either way.
+pub fn check_symmetry(board: &Board) -> Result<(), String> {
+    let mirrored = mirror(board);
+    let ours = eval_breakdown(board);
+    let theirs = eval_breakdown(&mirrored);
+    let terms: [(&str, fn(&EvalBreakdown) -> [i32; 2]); 15] = [
+        ("material", |b| b.material),
+        ("pst", |b| b.pst),
+        ("mobility", |b| b.mobility),
+        ("king safety", |b| b.king_safety),
+        ("castling", |b| b.castling),
+        ("development", |b| b.development),
+        ("centre control", |b| b.center),
+        ("weak squares", |b| b.weak_squares),
+        ("passed pawns", |b| b.passed_pawns),
+        ("pawn majorities", |b| b.pawn_majorities),
+        ("bishop pair", |b| b.bishop_pair),
+        ("rooks", |b| b.rooks),
+        ("threats", |b| b.threats),
+        ("hanging", |b| b.hanging),
+        ("mop-up", |b| b.mop_up),
+    ];
+    for (name, term) in terms {
+        let ([white, black], [mirrored_white, mirrored_black]) = (term(&ours), term(&theirs));
+        if (white, black) != (mirrored_black, mirrored_white) {
+            return Err(format!(
+                "{} is {} for White and {} for Black, but {} and {} mirrored",
+                name, white, black, mirrored_white, mirrored_black
+            ));
+        }
+    }
 
-    // Final evaluation: material difference + mobility bonus + king safety
-    (white_material - black_material + king_safety_score) as f64 + 10.0 * influence_ratio.ln()
+    let (score, mirrored_score) = (eval(board), eval(&mirrored));
+    if score != -mirrored_score {
+        return Err(format!(
+            "evaluation is {}, but {} mirrored",
+            score, mirrored_score
+        ));
+    }
+    Ok(())
+}
+
+/// Scale factor of an ordinary position
+pub const SCALE_NORMAL: i32 = 64;
+
+/// Scale factor of an endgame with only opposite-colored bishops and pawns,
+/// and of one where opposite-colored bishops are among other pieces
+const SCALE_OCB: i32 = 24;
+const SCALE_OCB_PIECES: i32 = 48;
+
+/// Scale factor when the stronger side has no pawns and is at most a minor
+/// piece ahead, like rook against bishop
+const SCALE_NO_PAWNS: i32 = 12;
+
+/// How much of the evaluation the side `strong`, ahead by it, can expect to
+/// convert, out of `SCALE_NORMAL`. Opposite-colored bishops draw a pawn or
+/// two up, and without pawns an edge of a minor piece rarely wins.
+fn scale_factor(board: &Board, strong: Color, material: &[i32; 2], phase: i32) -> i32 {
+    let pawns =
+        |color: Color| count_bits(*board.pieces(Piece::Pawn) & *board.color_combined(color));
+    let pawn = taper(PAWN_VAL, PAWN_VAL_EG, phase);
+    let pieces = |color: Color| material[color.to_index()] - pawn * pawns(color);
+    if pawns(strong) == 0
+        && pieces(strong) - pieces(!strong) <= taper(BISHOP_VAL, BISHOP_VAL_EG, phase)
+    {
+        return SCALE_NO_PAWNS;
+    }
+
+    let bishops = *board.pieces(Piece::Bishop);
+    let light = BitBoard::new(LIGHT_SQUARES);
+    let one_bishop = |color: Color| (bishops & *board.color_combined(color)).popcnt() == 1;
+    if one_bishop(Color::White) && one_bishop(Color::Black) && count_bits(bishops & light) == 1 {
+        let others =
+            *board.pieces(Piece::Knight) | *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+        return if others == EMPTY {
+            SCALE_OCB
+        } else {
+            SCALE_OCB_PIECES
+        };
+    }
+    SCALE_NORMAL
 }
 
 /// Build a bitboard mask for all squares on a given file.
@@ -408,32 +973,20 @@ fn file_mask(file: File) -> BitBoard {
     bb
 }
 
-/// Evaluate king safety for one side. Returns a score in centipawns (positive = safer).
-/// In the endgame this returns 0, since king centralization matters more than shelter.
-///
-/// Components:
-///   - Pawn shield: bonus for friendly pawns on the 2nd/3rd rank near the king
-///   - Open files: penalty for missing pawns on files near the king
-///   - Enemy attacks: penalty for enemy pieces attacking squares around the king
-fn king_safety(board: &Board, color: Color, is_endgame: bool) -> i32 {
-    if is_endgame {
-        return 0;
-    }
+/// Middlegame penalty per file at or next to the king without a pawn of
+/// ours, and extra when it has no enemy pawn either
+pub(crate) const SHELTER_MISSING_PAWN: i32 = 15;
+pub(crate) const SHELTER_OPEN_FILE: i32 = 10;
 
-    let king_sq = board.king_square(color);
-    let king_file = king_sq.get_file().to_index() as i32;
-    let enemy = if color == Color::White {
-        Color::Black
-    } else {
-        Color::White
-    };
-
-    let our_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(color);
-    let their_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(enemy);
+/// Middlegame bonus per shield pawn on its home rank, and per shield pawn
+/// one rank further up
+pub(crate) const SHIELD_PAWN: i32 = 10;
+pub(crate) const SHIELD_PAWN_ADVANCED: i32 = 5;
 
-    let mut score: i32 = 0;
+/// Pawn shield and open file score for a king on `king_file`
+fn pawn_shelter(our_pawns: BitBoard, their_pawns: BitBoard, king_file: i32, color: Color) -> i32 {
+    let mut score = 0;
 
-    // --- Pawn shield & open file penalties ---
     // Examine the king file and its neighbors (up to 3 files)
     let file_start = (king_file - 1).max(0);
     let file_end = (king_file + 1).min(7);
@@ -445,10 +998,10 @@ fn king_safety(board: &Board, color: Color, is_endgame: bool) -> i32 {
 
         if friendly_on_file == EMPTY {
             // No friendly pawn on this file — king is exposed
-            score -= 15;
+            score -= SHELTER_MISSING_PAWN;
             if enemy_on_file == EMPTY {
                 // Fully open file next to king
-                score -= 10;
+                score -= SHELTER_OPEN_FILE;
             }
         } else {
             // Bonus for pawn shield proximity to king
@@ -462,111 +1015,841 @@ fn king_safety(board: &Board, color: Color, is_endgame: bool) -> i32 {
             let r2_mask = BitBoard::set(shield_rank_2, File::from_index(f as usize));
 
             if friendly_on_file & r1_mask != EMPTY {
-                score += 10; // pawn on home rank shielding king
+                score += SHIELD_PAWN; // pawn on home rank shielding king
             } else if friendly_on_file & r2_mask != EMPTY {
-                score += 5; // pawn advanced one rank, still decent cover
+                score += SHIELD_PAWN_ADVANCED; // pawn advanced one rank, still decent cover
+            }
+        }
+    }
+
+    score
+}
+
+/// Pawn shelter of the king on `king_sq`, forecast: the best of the shield
+/// as it stands and with any one of its pawns, on the king's file or next to
+/// it, a step further up or back onto an empty square. A pawn lifted for a
+/// move, or about to be, then doesn't count fully against the king.
+fn shelter_forecast(
+    board: &Board,
+    our_pawns: BitBoard,
+    their_pawns: BitBoard,
+    king_sq: Square,
+    color: Color,
+) -> i32 {
+    let king_file = king_sq.get_file().to_index() as i32;
+    let shield = our_pawns & BitBoard::new(adjacent_files(king_sq) | FILE_A << king_file);
+    let mut best = pawn_shelter(our_pawns, their_pawns, king_file, color);
+    for pawn in shield {
+        for step in [pawn.up(), pawn.down()].into_iter().flatten() {
+            if *board.combined() & BitBoard::from_square(step) != EMPTY {
+                continue;
             }
+            let moved = our_pawns ^ BitBoard::from_square(pawn) ^ BitBoard::from_square(step);
+            best = best.max(pawn_shelter(moved, their_pawns, king_file, color));
         }
     }
+    best
+}
+
+/// Evaluate king safety for one side. Returns a middlegame score in centipawns
+/// (positive = safer), which the caller fades out towards the endgame, since
+/// king centralization matters more than shelter there.
+///
+/// Components:
+///   - Pawn shield: bonus for friendly pawns on the 2nd/3rd rank near the king
+///   - Open files: penalty for missing pawns on files near the king
+///   - Shelter forecast: the shield and open file terms take the best
+///     shelter with any one shield pawn a step further up or back
+///   - Enemy attacks: attack units for each king zone square enemy pieces
+///     attack, weighted by piece type and mapped through a danger table
+///   - Pinned defenders: penalty for our pieces next to the king that are
+///     pinned to it, and so can't guard the squares they seem to
+fn king_safety(board: &Board, color: Color) -> i32 {
+    let king_sq = board.king_square(color);
+    let enemy = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+
+    let our_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(color);
+    let their_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(enemy);
+
+    // --- Pawn shield & open file penalties ---
+    let mut score = shelter_forecast(board, our_pawns, their_pawns, king_sq, color);
 
     // --- Enemy piece attacks into king zone ---
     let king_zone = chess::get_king_moves(king_sq) | BitBoard::from_square(king_sq);
     let occupied = *board.combined();
 
-    // Knights
-    let enemy_knights = *board.pieces(Piece::Knight) & *board.color_combined(enemy);
-    for sq in enemy_knights {
-        let attacks = chess::get_knight_moves(sq) & king_zone;
-        if attacks != EMPTY {
-            score -= 10;
+    // --- Pinned defenders ---
+    score -= PINNED_DEFENDER * count_bits(pinned_pieces(board, color) & king_zone);
+
+    // Attack units: each enemy piece adds its weight for every king zone
+    // square it attacks, and the danger table turns the sum into a penalty
+    // that grows faster than the attack does. A lone attacker counts half.
+    let mut units = 0;
+    let mut attackers = 0;
+    for (piece, weight) in ATTACK_UNITS {
+        for sq in *board.pieces(piece) & *board.color_combined(enemy) {
+            let attacks = match piece {
+                Piece::Knight => chess::get_knight_moves(sq),
+                Piece::Bishop => chess::get_bishop_moves(sq, occupied),
+                Piece::Rook => chess::get_rook_moves(sq, occupied),
+                _ => chess::get_bishop_moves(sq, occupied) | chess::get_rook_moves(sq, occupied),
+            };
+            let zone_attacks = count_bits(attacks & king_zone);
+            if zone_attacks > 0 {
+                attackers += 1;
+                units += weight * zone_attacks;
+            }
         }
     }
+    if attackers == 1 {
+        units /= 2;
+    }
+    score -= king_danger(units);
 
-    // Bishops
-    let enemy_bishops = *board.pieces(Piece::Bishop) & *board.color_combined(enemy);
-    for sq in enemy_bishops {
-        let attacks = chess::get_bishop_moves(sq, occupied) & king_zone;
-        if attacks != EMPTY {
-            score -= 10;
-        }
+    score
+}
+
+/// Middlegame bonus for a castled king, and for still having the right to
+/// castle
+pub(crate) const CASTLED: i32 = 25;
+pub(crate) const CASTLING_RIGHTS: i32 = 10;
+
+/// Middlegame penalty for a king in the centre that can no longer castle
+/// while a rook is still in its corner
+pub(crate) const STUCK_KING: i32 = 30;
+
+/// Castling of one side, as a middlegame score the caller fades out towards
+/// the endgame. A king on its back rank on the b-c or g-h files, with no
+/// rook of ours trapped beside it towards the corner, has castled (or got
+/// there some other way, which is as good). A king on the d-f files of its
+/// first two ranks that has lost its castling rights while a rook still
+/// stands in its corner has walked into the centre with its rooks
+/// undeveloped; one further up is left to the other king safety terms.
+fn castling(board: &Board, color: Color) -> i32 {
+    let king = board.king_square(color);
+    let back_rank = if color == Color::White {
+        Rank::First
+    } else {
+        Rank::Eighth
+    };
+    if board.castle_rights(color) != chess::CastleRights::NoRights {
+        return CASTLING_RIGHTS;
+    }
+
+    let rooks = *board.pieces(Piece::Rook) & *board.color_combined(color);
+    let file = king.get_file().to_index();
+    let back_rank_rook = |f: usize| rooks & BitBoard::set(back_rank, File::from_index(f)) != EMPTY;
+    let on_back_rank = king.get_rank() == back_rank;
+    let near_home = (king.get_rank().to_index() as i32 - back_rank.to_index() as i32).abs() <= 1;
+    match file {
+        1 | 2 if on_back_rank && !(0..file).any(back_rank_rook) => CASTLED,
+        6 | 7 if on_back_rank && !(file + 1..8).any(back_rank_rook) => CASTLED,
+        3..=5 if near_home && (back_rank_rook(0) || back_rank_rook(7)) => -STUCK_KING,
+        _ => 0,
     }
+}
+
+/// Penalty per knight or bishop on its starting square
+pub(crate) const UNDEVELOPED_MINOR: i32 = 15;
 
-    // Rooks
-    let enemy_rooks = *board.pieces(Piece::Rook) & *board.color_combined(enemy);
-    for sq in enemy_rooks {
-        let attacks = chess::get_rook_moves(sq, occupied) & king_zone;
-        if attacks != EMPTY {
-            score -= 15;
+/// Game phase below which development no longer counts
+const DEVELOPMENT_PHASE: i32 = 16;
+
+/// Starting squares of White's knights and bishops, mirrored for Black's
+const KNIGHT_HOMES: [Square; 2] = [Square::B1, Square::G1];
+const BISHOP_HOMES: [Square; 2] = [Square::C1, Square::F1];
+
+/// Development of one side: a penalty for each knight and bishop that hasn't
+/// left its starting square, so the first move of each minor piece is worth
+/// making and any further one earns nothing more. It counts in full with all
+/// pieces on the board and fades out by `DEVELOPMENT_PHASE`, past the
+/// opening.
+fn development(board: &Board, color: Color, phase: i32) -> i32 {
+    if phase <= DEVELOPMENT_PHASE {
+        return 0;
+    }
+    let home = |sq: Square| {
+        if color == Color::White {
+            sq
+        } else {
+            Square::make_square(Rank::Eighth, sq.get_file())
         }
+    };
+    let at_home = |homes: [Square; 2], piece: Piece| {
+        homes
+            .into_iter()
+            .map(home)
+            .filter(|&sq| board.piece_on(sq) == Some(piece) && board.color_on(sq) == Some(color))
+            .count() as i32
+    };
+    let undeveloped = at_home(KNIGHT_HOMES, Piece::Knight) + at_home(BISHOP_HOMES, Piece::Bishop);
+    -undeveloped * UNDEVELOPED_MINOR * (phase - DEVELOPMENT_PHASE) / (MAX_PHASE - DEVELOPMENT_PHASE)
+}
+
+/// The four centre squares (d4, e4, d5, e5), and the ring around them that
+/// makes up the rest of the extended centre (c3 to f6)
+const CENTER: u64 = 0x0000_0018_1800_0000;
+const EXTENDED_CENTER: u64 = 0x0000_3c24_243c_0000;
+
+/// Middlegame bonus per attack on a centre square by a pawn and by a piece,
+/// and per attack on the rest of the extended centre
+pub(crate) const CENTER_PAWN: i32 = 6;
+pub(crate) const CENTER_PIECE: i32 = 3;
+pub(crate) const EXTENDED_CENTER_PAWN: i32 = 2;
+pub(crate) const EXTENDED_CENTER_PIECE: i32 = 1;
+
+/// Centre control of one side, as a middlegame score the caller fades out
+/// towards the endgame: every attack by a pawn, knight, bishop, rook or
+/// queen on a centre square, and less for the rest of the extended centre,
+/// pawns counting most since no piece can chase them away. Unlike the
+/// piece-square tables, which reward standing in the centre, this rewards
+/// covering it from wherever the attacker stands.
+fn center_control(board: &Board, color: Color) -> i32 {
+    let ours = *board.color_combined(color);
+    let occupied = *board.combined();
+    let (center, extended) = (BitBoard::new(CENTER), BitBoard::new(EXTENDED_CENTER));
+    let mut score = 0;
+    for sq in ours & !*board.pieces(Piece::King) {
+        let (attacks, on_center, on_extended) = match board.piece_on(sq) {
+            Some(Piece::Pawn) => (
+                chess::get_pawn_attacks(sq, color, !EMPTY),
+                CENTER_PAWN,
+                EXTENDED_CENTER_PAWN,
+            ),
+            Some(Piece::Knight) => (
+                chess::get_knight_moves(sq),
+                CENTER_PIECE,
+                EXTENDED_CENTER_PIECE,
+            ),
+            Some(Piece::Bishop) => (
+                chess::get_bishop_moves(sq, occupied),
+                CENTER_PIECE,
+                EXTENDED_CENTER_PIECE,
+            ),
+            Some(Piece::Rook) => (
+                chess::get_rook_moves(sq, occupied),
+                CENTER_PIECE,
+                EXTENDED_CENTER_PIECE,
+            ),
+            _ => (
+                chess::get_bishop_moves(sq, occupied) | chess::get_rook_moves(sq, occupied),
+                CENTER_PIECE,
+                EXTENDED_CENTER_PIECE,
+            ),
+        };
+        score +=
+            on_center * count_bits(attacks & center) + on_extended * count_bits(attacks & extended);
+    }
+    score
+}
+
+/// The squares of each side's half of the extended centre where a hole lets
+/// an enemy piece settle in front of our pawns: c3 to f4 for White, c5 to f6
+/// for Black
+const HOLE_ZONE: [u64; 2] = [0x0000_0000_3c3c_0000, 0x0000_3c3c_0000_0000];
+
+/// Penalty per hole in the centre and per hole in front of the king in the
+/// middlegame and in the endgame. Both count double on the color of a bishop
+/// the opponent still has.
+pub(crate) const HOLE: (i32, i32) = (6, 2);
+pub(crate) const KING_HOLE: (i32, i32) = (10, 0);
+
+/// The squares pawns of `color` attack or may attack once they advance: those
+/// ahead of each pawn on the files next to it
+fn pawn_attack_span(pawns: BitBoard, color: Color) -> BitBoard {
+    let mut span = 0;
+    for sq in pawns {
+        span |= adjacent_files(sq) & ranks_ahead(sq, color);
     }
+    BitBoard::new(span)
+}
+
+/// Holes of one side: squares of its half of the centre, and of the third
+/// rank in front of a king still on its first two ranks, that none of its
+/// pawns can ever attack, returned as the centre's and the king's
+fn holes(board: &Board, color: Color) -> (BitBoard, BitBoard) {
+    let our_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(color);
+    let guarded = pawn_attack_span(our_pawns, color) | our_pawns;
+
+    let king = board.king_square(color);
+    let (home, third) = match color {
+        Color::White => (king.get_rank().to_index() <= 1, Rank::Third),
+        Color::Black => (king.get_rank().to_index() >= 6, Rank::Sixth),
+    };
+    let shelter = if home {
+        let files = adjacent_files(king) | FILE_A << king.get_file().to_index();
+        BitBoard::new(files) & chess::get_rank(third)
+    } else {
+        EMPTY
+    };
+    (
+        BitBoard::new(HOLE_ZONE[color.to_index()]) & !guarded,
+        shelter & !guarded,
+    )
+}
 
-    // Queens
-    let enemy_queens = *board.pieces(Piece::Queen) & *board.color_combined(enemy);
-    for sq in enemy_queens {
-        let attacks = (chess::get_bishop_moves(sq, occupied) | chess::get_rook_moves(sq, occupied))
-            & king_zone;
-        if attacks != EMPTY {
-            score -= 25;
+/// Penalty for the holes of one side, blended by `phase`. An enemy bishop
+/// on a hole's color can use it and the squares around it that no pawn
+/// will ever cover, so such holes count double.
+fn weak_squares(board: &Board, color: Color, phase: i32) -> i32 {
+    let their_bishops = *board.pieces(Piece::Bishop) & *board.color_combined(!color);
+    let light = BitBoard::new(LIGHT_SQUARES);
+    let weight = |holes: BitBoard| {
+        [light, !light]
+            .into_iter()
+            .map(|squares| {
+                let bishops = i32::from(their_bishops & squares != EMPTY);
+                (1 + bishops) * count_bits(holes & squares)
+            })
+            .sum::<i32>()
+    };
+    let (center, king) = holes(board, color);
+    let (center, king) = (weight(center), weight(king));
+    -taper(
+        center * HOLE.0 + king * KING_HOLE.0,
+        center * HOLE.1 + king * KING_HOLE.1,
+        phase,
+    )
+}
+
+/// Attack units per king zone square attacked, by piece type
+const ATTACK_UNITS: [(Piece, i32); 4] = [
+    (Piece::Knight, 2),
+    (Piece::Bishop, 2),
+    (Piece::Rook, 3),
+    (Piece::Queen, 5),
+];
+
+/// Middlegame penalty for the attack units against a king, rising slowly for
+/// the first few and steeply once several pieces join in, up to a cap
+#[rustfmt::skip]
+pub(crate) const KING_DANGER: [i32; 64] = [
+      0,   0,   1,   2,   3,   5,   7,   9,  12,  15,
+     18,  22,  26,  30,  35,  39,  44,  50,  56,  62,
+     68,  75,  82,  85,  89,  97, 105, 113, 122, 131,
+    140, 150, 169, 180, 191, 202, 213, 225, 237, 248,
+    260, 272, 283, 295, 307, 319, 330, 342, 354, 366,
+    377, 389, 401, 412, 424, 436, 448, 459, 471, 483,
+    494, 500, 500, 500,
+];
+
+/// King danger penalty for `units` attack units
+fn king_danger(units: i32) -> i32 {
+    KING_DANGER[(units.max(0) as usize).min(KING_DANGER.len() - 1)]
+}
+
+/// Middlegame penalty per piece in the king zone pinned to the king
+pub(crate) const PINNED_DEFENDER: i32 = 12;
+
+/// Passed pawn bonus by rank, counted from the pawn's own side
+pub(crate) const PASSED_PAWN_BONUS: [i32; 8] = [0, 5, 10, 20, 35, 60, 100, 0];
+
+/// Endgame bonus per square the enemy king is away from a passed pawn's stop
+/// square, and penalty per square our own king is, both per rank the pawn
+/// has advanced past its third
+pub(crate) const PASSED_PAWN_THEIR_KING: i32 = 5;
+pub(crate) const PASSED_PAWN_OUR_KING: i32 = 2;
+
+/// King moves it takes to get from `a` to `b` on an empty board
+pub(crate) fn king_distance(a: Square, b: Square) -> i32 {
+    let files = (a.get_file().to_index() as i32 - b.get_file().to_index() as i32).abs();
+    let ranks = (a.get_rank().to_index() as i32 - b.get_rank().to_index() as i32).abs();
+    files.max(ranks)
+}
+
+/// Whether any piece of `color` attacks `sq`
+pub(crate) fn attacks_square(board: &Board, sq: Square, color: Color) -> bool {
+    let ours = *board.color_combined(color);
+    let occupied = *board.combined();
+    let bishops_queens = (*board.pieces(Piece::Bishop) | *board.pieces(Piece::Queen)) & ours;
+    let rooks_queens = (*board.pieces(Piece::Rook) | *board.pieces(Piece::Queen)) & ours;
+
+    // A pawn of `color` attacks `sq` if a pawn of the other color on `sq`
+    // would attack it back
+    chess::get_pawn_attacks(sq, !color, *board.pieces(Piece::Pawn) & ours) != EMPTY
+        || chess::get_knight_moves(sq) & *board.pieces(Piece::Knight) & ours != EMPTY
+        || chess::get_bishop_moves(sq, occupied) & bishops_queens != EMPTY
+        || chess::get_rook_moves(sq, occupied) & rooks_queens != EMPTY
+        || chess::get_king_moves(sq) & *board.pieces(Piece::King) & ours != EMPTY
+}
+
+/// Whether the pawn of `color` on `sq` is passed: no enemy pawn stands ahead
+/// of it on its own or an adjacent file
+fn is_passed(board: &Board, sq: Square, color: Color) -> bool {
+    let their_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(!color);
+    let files = adjacent_files(sq) | FILE_A << sq.get_file().to_index();
+    their_pawns & BitBoard::new(files & ranks_ahead(sq, color)) == EMPTY
+}
+
+/// The a-file
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+
+/// The files on either side of `sq`'s
+fn adjacent_files(sq: Square) -> u64 {
+    let file = sq.get_file().to_index();
+    let mut files = 0;
+    if file > 0 {
+        files |= FILE_A << (file - 1);
+    }
+    if file < 7 {
+        files |= FILE_A << (file + 1);
+    }
+    files
+}
+
+/// The ranks in front of `sq` as seen by `color`
+fn ranks_ahead(sq: Square, color: Color) -> u64 {
+    let rank = sq.get_rank().to_index() as u32;
+    if color == Color::White {
+        u64::MAX.checked_shl(8 * (rank + 1)).unwrap_or(0)
+    } else {
+        u64::MAX.checked_shr(8 * (8 - rank)).unwrap_or(0)
+    }
+}
+
+/// Score the passed pawns of one side. Each gets a bonus growing with its
+/// rank, which shrinks when an enemy piece blockades its stop square (most
+/// of all for a knight, which blockades without losing activity) and grows
+/// when friendly pieces escort it by covering the stop square. Towards the
+/// endgame (by `phase`), an advanced passer is worth more the closer our
+/// king is to its stop square and the further the enemy king is.
+fn passed_pawns(board: &Board, color: Color, phase: i32) -> i32 {
+    let our_king = board.king_square(color);
+    let their_king = board.king_square(!color);
+    let our_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(color);
+    let mut score = 0;
+
+    for sq in our_pawns {
+        if !is_passed(board, sq, color) {
+            continue;
         }
+        let rank = sq.get_rank().to_index();
+        let relative_rank = if color == Color::White {
+            rank
+        } else {
+            7 - rank
+        };
+
+        let bonus = PASSED_PAWN_BONUS[relative_rank];
+        let stop = if color == Color::White {
+            sq.up()
+        } else {
+            sq.down()
+        };
+        let Some(stop) = stop else {
+            continue;
+        };
+
+        let mut value = bonus;
+        match board.piece_on(stop) {
+            Some(blocker) if board.color_on(stop) == Some(!color) => {
+                value -= if blocker == Piece::Knight {
+                    bonus * 3 / 4
+                } else {
+                    bonus / 2
+                };
+            }
+            _ => {
+                if attacks_square(board, stop, color) {
+                    value += bonus / 4;
+                }
+                if attacks_square(board, stop, !color) {
+                    value -= bonus / 4;
+                }
+            }
+        }
+        let weight = relative_rank.saturating_sub(2) as i32;
+        let king_proximity = weight
+            * (PASSED_PAWN_THEIR_KING * king_distance(their_king, stop)
+                - PASSED_PAWN_OUR_KING * king_distance(our_king, stop));
+        score += value + taper(0, king_proximity, phase);
     }
 
     score
 }
 
-/// Calculate mobility (number of attacked squares) for a color
-fn calculate_mobility(board: &Board, color: Color) -> i32 {
-    // For mobility, we count the number of squares attacked by each piece
-    // We use a temporary board with the given color to move to generate attacks
+/// Bonus per pawn of a wing majority, counting only pawns on distinct files
+/// since doubled pawns cannot make a passer
+pub(crate) const MAJORITY_BONUS: i32 = 8;
 
-    let mut influence = 0;
+/// Extra bonus for a majority on the wing away from the enemy king, which
+/// makes an outside passed pawn the king has to run after
+pub(crate) const OUTSIDE_MAJORITY_BONUS: i32 = 12;
 
-    // Pawn attacks
-    let pawns = *board.pieces(Piece::Pawn) & *board.color_combined(color);
-    for sq in pawns {
-        let attacks = chess::get_pawn_attacks(sq, color, *board.combined());
-        influence += count_bits(attacks);
-    }
+/// Bonus for a rook on a half-open file of a wing where its pawns are
+/// outnumbered, ready to lead a minority attack against the majority
+pub(crate) const MINORITY_ATTACK_BONUS: i32 = 10;
+
+/// The queenside (files a-d) and the kingside (files e-h)
+const WINGS: [u64; 2] = [0x0f0f_0f0f_0f0f_0f0f, 0xf0f0_f0f0_f0f0_f0f0];
 
-    // Knight attacks
-    let knights = *board.pieces(Piece::Knight) & *board.color_combined(color);
-    for sq in knights {
-        let attacks = chess::get_knight_moves(sq);
-        influence += count_bits(attacks);
+/// Score the pawn majorities of one side. More pawns than the opponent on a
+/// wing, counted by file, will make a passed pawn in the endgame, all the
+/// more so on the wing away from the enemy king. These terms count in full
+/// in the endgame and half in the middlegame, where the side with the
+/// minority also gets a bonus for a rook on a half-open file of that wing.
+/// The middlegame and endgame scores are blended by `phase`.
+fn pawn_majorities(board: &Board, color: Color, phase: i32) -> i32 {
+    let our_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(color);
+    let their_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(!color);
+    let our_rooks = *board.pieces(Piece::Rook) & *board.color_combined(color);
+    let their_king = BitBoard::from_square(board.king_square(!color));
+    let mut majorities = 0;
+    let mut minority_attack = 0;
+
+    for wing in WINGS.map(BitBoard::new) {
+        let files = (0..8)
+            .map(|f| file_mask(File::from_index(f)))
+            .filter(|&file| file & wing != EMPTY);
+        let mut our_files = 0;
+        let mut half_open_rook = false;
+        for file in files {
+            if our_pawns & file != EMPTY {
+                our_files += 1;
+            } else if their_pawns & file != EMPTY && our_rooks & file != EMPTY {
+                half_open_rook = true;
+            }
+        }
+        let their_count = count_bits(their_pawns & wing);
+        let our_count = count_bits(our_pawns & wing);
+
+        let extra = our_files - their_count;
+        if extra > 0 {
+            majorities += MAJORITY_BONUS * extra;
+            if their_king & wing == EMPTY {
+                majorities += OUTSIDE_MAJORITY_BONUS;
+            }
+        } else if our_count > 0 && our_count < their_count && half_open_rook {
+            minority_attack += MINORITY_ATTACK_BONUS;
+        }
     }
 
-    // Bishop attacks
+    taper(majorities / 2 + minority_attack, majorities, phase)
+}
+
+/// Bonus for keeping bishops of both square colors in the middlegame and in
+/// the endgame, where open lines make the pair stronger still
+pub(crate) const BISHOP_PAIR_MG: i32 = 30;
+pub(crate) const BISHOP_PAIR_EG: i32 = 50;
+
+/// The light squares (b1, a2, ...)
+const LIGHT_SQUARES: u64 = 0x55aa_55aa_55aa_55aa;
+
+/// Bishop pair bonus of one side, blended by `phase`. Two bishops on the
+/// same square color, after an underpromotion, don't count as a pair.
+fn bishop_pair(board: &Board, color: Color, phase: i32) -> i32 {
     let bishops = *board.pieces(Piece::Bishop) & *board.color_combined(color);
-    for sq in bishops {
-        let attacks = chess::get_bishop_moves(sq, *board.combined());
-        influence += count_bits(attacks);
+    let light = BitBoard::new(LIGHT_SQUARES);
+    if bishops & light != EMPTY && bishops & !light != EMPTY {
+        taper(BISHOP_PAIR_MG, BISHOP_PAIR_EG, phase)
+    } else {
+        0
     }
+}
 
-    // Rook attacks
-    let rooks = *board.pieces(Piece::Rook) & *board.color_combined(color);
-    for sq in rooks {
-        let attacks = chess::get_rook_moves(sq, *board.combined());
-        influence += count_bits(attacks);
+/// Bonus per rook behind a passed pawn of either side, on its file with
+/// nothing in between, in the middlegame and the endgame
+pub(crate) const ROOK_BEHIND_PASSER: (i32, i32) = (10, 30);
+
+/// Bonus per pair of rooks defending each other along a rank or file, and
+/// extra when they are doubled on a file without pawns
+pub(crate) const CONNECTED_ROOKS: (i32, i32) = (15, 5);
+pub(crate) const DOUBLED_ROOKS_OPEN_FILE: (i32, i32) = (20, 10);
+
+/// Rook placement of one side, blended by `phase`. A rook behind a passed
+/// pawn supports its own pawn's advance, or holds back the enemy's, from the
+/// file it runs on, and more of the pawn's path there is to cover the
+/// further it goes. Rooks that see each other, with the pieces between them
+/// developed, work together, doubled on an open file most of all.
+fn rooks(board: &Board, color: Color, phase: i32) -> i32 {
+    let our_rooks = *board.pieces(Piece::Rook) & *board.color_combined(color);
+    let occupied = *board.combined();
+    let pawns = *board.pieces(Piece::Pawn);
+    let mut behind = 0;
+    let mut connected = 0;
+    let mut doubled = 0;
+
+    for rook in our_rooks {
+        // Each pair once, from its lower rook
+        let defended = chess::get_rook_moves(rook, occupied) & our_rooks;
+        for other in defended.filter(|other| other.to_index() > rook.to_index()) {
+            connected += 1;
+            let file = file_mask(rook.get_file());
+            if other.get_file() == rook.get_file() && pawns & file == EMPTY {
+                doubled += 1;
+            }
+        }
+    }
+
+    for pawn in *board.pieces(Piece::Pawn) {
+        let Some(owner) = board.color_on(pawn) else {
+            continue;
+        };
+        if !is_passed(board, pawn, owner) {
+            continue;
+        }
+        let file = file_mask(pawn.get_file());
+        for rook in our_rooks & file & chess::get_rook_moves(pawn, occupied) {
+            let rook_rank = rook.get_rank().to_index();
+            let pawn_rank = pawn.get_rank().to_index();
+            if (owner == Color::White) == (rook_rank < pawn_rank) {
+                behind += 1;
+            }
+        }
     }
 
-    // Queen attacks
-    let queens = *board.pieces(Piece::Queen) & *board.color_combined(color);
-    for sq in queens {
-        let attacks = chess::get_bishop_moves(sq, *board.combined())
-            | chess::get_rook_moves(sq, *board.combined());
-        influence += count_bits(attacks);
+    taper(
+        behind * ROOK_BEHIND_PASSER.0
+            + connected * CONNECTED_ROOKS.0
+            + doubled * DOUBLED_ROOKS_OPEN_FILE.0,
+        behind * ROOK_BEHIND_PASSER.1
+            + connected * CONNECTED_ROOKS.1
+            + doubled * DOUBLED_ROOKS_OPEN_FILE.1,
+        phase,
+    )
+}
+
+/// Bonus per enemy knight, bishop, rook or queen attacked by one of our
+/// pawns, in the middlegame and the endgame
+pub(crate) const THREAT_BY_PAWN: (i32, i32) = (50, 40);
+
+/// Bonus per enemy rook or queen attacked by one of our knights or bishops
+pub(crate) const THREAT_BY_MINOR: (i32, i32) = (35, 30);
+
+/// Threats of one side, blended by `phase`: enemy pieces attacked by a less
+/// valuable piece of ours, which can't simply be defended and have to move.
+/// Each attacked piece counts, so a fork of two scores twice.
+fn threats(board: &Board, color: Color, phase: i32) -> i32 {
+    let ours = *board.color_combined(color);
+    let theirs = *board.color_combined(!color);
+    let occupied = *board.combined();
+    let minors = *board.pieces(Piece::Knight) | *board.pieces(Piece::Bishop);
+    let majors = *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+
+    let pawn_attacks = (*board.pieces(Piece::Pawn) & ours).fold(EMPTY, |attacks, sq| {
+        attacks | chess::get_pawn_attacks(sq, color, !EMPTY)
+    });
+    let minor_attacks = (minors & ours).fold(EMPTY, |attacks, sq| {
+        attacks
+            | if board.piece_on(sq) == Some(Piece::Knight) {
+                chess::get_knight_moves(sq)
+            } else {
+                chess::get_bishop_moves(sq, occupied)
+            }
+    });
+
+    let by_pawn = count_bits(pawn_attacks & theirs & (minors | majors));
+    let by_minor = count_bits(minor_attacks & theirs & majors);
+    taper(
+        by_pawn * THREAT_BY_PAWN.0 + by_minor * THREAT_BY_MINOR.0,
+        by_pawn * THREAT_BY_PAWN.1 + by_minor * THREAT_BY_MINOR.1,
+        phase,
+    )
+}
+
+/// Penalty per knight, bishop, rook or queen that no friendly piece defends
+/// and an enemy piece attacks, in the middlegame and the endgame
+pub(crate) const HANGING_PIECE: (i32, i32) = (40, 30);
+
+/// Penalty per knight, bishop, rook or queen that no friendly piece defends
+pub(crate) const UNDEFENDED_PIECE: (i32, i32) = (8, 5);
+
+/// Squares any piece of `color` attacks
+fn attacked_squares(board: &Board, color: Color) -> BitBoard {
+    let ours = *board.color_combined(color);
+    let occupied = *board.combined();
+    let mut attacks = chess::get_king_moves(board.king_square(color));
+    for sq in ours & *board.pieces(Piece::Pawn) {
+        attacks |= chess::get_pawn_attacks(sq, color, !EMPTY);
+    }
+    for sq in ours & *board.pieces(Piece::Knight) {
+        attacks |= chess::get_knight_moves(sq);
+    }
+    for sq in ours & (*board.pieces(Piece::Bishop) | *board.pieces(Piece::Queen)) {
+        attacks |= chess::get_bishop_moves(sq, occupied);
     }
+    for sq in ours & (*board.pieces(Piece::Rook) | *board.pieces(Piece::Queen)) {
+        attacks |= chess::get_rook_moves(sq, occupied);
+    }
+    attacks
+}
 
-    // King attacks
-    let king_sq = board.king_square(color);
-    let king_attacks = chess::get_king_moves(king_sq);
-    influence += count_bits(king_attacks);
+/// Penalty (a negative score) for the pieces of one side that no friendly
+/// piece defends, blended by `phase`: small for any such piece, which a
+/// single attack can win, and large when the opponent already attacks it
+fn hanging_pieces(board: &Board, color: Color, phase: i32) -> i32 {
+    let pieces =
+        *board.color_combined(color) & !*board.pieces(Piece::Pawn) & !*board.pieces(Piece::King);
+    let undefended = pieces & !attacked_squares(board, color);
+    let hanging = count_bits(undefended & attacked_squares(board, !color));
+    let loose = count_bits(undefended) - hanging;
+    -taper(
+        hanging * HANGING_PIECE.0 + loose * UNDEFENDED_PIECE.0,
+        hanging * HANGING_PIECE.1 + loose * UNDEFENDED_PIECE.1,
+        phase,
+    )
+}
+
+/// Mop-up bonus per square of Manhattan distance between the bare king and
+/// the centre, and per square the kings are closer than opposite corners
+const MOP_UP_EDGE: i32 = 10;
+const MOP_UP_KINGS: i32 = 4;
+
+/// Bonus per square of Manhattan distance the bare king is closer to a
+/// corner of the bishop's color than the far side of the board, when mating
+/// with bishop and knight
+const KBN_CORNER: i32 = 6;
+
+/// The corners the light and the dark squared bishop mate in
+const LIGHT_CORNERS: [Square; 2] = [Square::A8, Square::H1];
+const DARK_CORNERS: [Square; 2] = [Square::A1, Square::H8];
+
+/// Whether `color` has enough material to mate without pawns: a queen, a
+/// rook, a bishop and a knight, or bishops on both square colors
+fn has_mating_material(board: &Board, color: Color) -> bool {
+    let ours = *board.color_combined(color);
+    let bishops = *board.pieces(Piece::Bishop) & ours;
+    let light = BitBoard::new(LIGHT_SQUARES);
+    (*board.pieces(Piece::Queen) | *board.pieces(Piece::Rook)) & ours != EMPTY
+        || (bishops != EMPTY && *board.pieces(Piece::Knight) & ours != EMPTY)
+        || (bishops & light != EMPTY && bishops & !light != EMPTY)
+}
+
+/// Whether `color` faces a bare king with enough material to mate it
+fn can_mop_up(board: &Board, color: Color) -> bool {
+    board.color_combined(!color).popcnt() == 1 && has_mating_material(board, color)
+}
 
-    influence
+/// Squares, by Manhattan distance, from `sq` to the nearest of the four
+/// centre squares
+fn center_distance(sq: Square) -> i32 {
+    let from_center = |index: usize| {
+        let index = index as i32;
+        (3 - index).max(index - 4)
+    };
+    from_center(sq.get_file().to_index()) + from_center(sq.get_rank().to_index())
+}
+
+/// Squares, by Manhattan distance, from `a` to `b`
+fn manhattan_distance(a: Square, b: Square) -> i32 {
+    let files = (a.get_file().to_index() as i32 - b.get_file().to_index() as i32).abs();
+    let ranks = (a.get_rank().to_index() as i32 - b.get_rank().to_index() as i32).abs();
+    files + ranks
+}
+
+/// Mop-up bonus of one side facing a bare king with mating material, so that
+/// the search makes progress towards the mate: the closer the enemy king is
+/// to the edge and the closer our king is to it, the better. With only a
+/// bishop and a knight, which mate in a corner of the bishop's color alone,
+/// the enemy king is driven towards such a corner instead.
+fn mop_up(board: &Board, color: Color) -> i32 {
+    if !can_mop_up(board, color) {
+        return 0;
+    }
+    let our_king = board.king_square(color);
+    let their_king = board.king_square(!color);
+    let ours = *board.color_combined(color);
+    let bishops = *board.pieces(Piece::Bishop) & ours;
+    let knights = *board.pieces(Piece::Knight) & ours;
+
+    let kbn = bishops.popcnt() == 1 && knights.popcnt() == 1 && ours.popcnt() == 3;
+    let edge = if kbn {
+        let corners = if bishops & BitBoard::new(LIGHT_SQUARES) != EMPTY {
+            LIGHT_CORNERS
+        } else {
+            DARK_CORNERS
+        };
+        let corner_distance = corners
+            .iter()
+            .map(|&corner| manhattan_distance(their_king, corner))
+            .min()
+            .unwrap_or(14);
+        KBN_CORNER * (14 - corner_distance)
+    } else {
+        MOP_UP_EDGE * center_distance(their_king)
+    };
+    edge + MOP_UP_KINGS * (14 - manhattan_distance(our_king, their_king))
+}
+
+/// Mobility weights per piece type, in centipawns per square of the mobility
+/// area attacked in the middlegame and in the endgame, and the number of
+/// squares that scores zero
+pub(crate) const MOBILITY: [(Piece, i32, i32, i32); 4] = [
+    (Piece::Knight, 4, 4, 4),
+    (Piece::Bishop, 4, 5, 6),
+    (Piece::Rook, 2, 3, 7),
+    (Piece::Queen, 1, 2, 13),
+];
+
+/// Penalty per knight, bishop, rook or queen pinned to its king, in the
+/// middlegame and the endgame
+pub(crate) const PINNED_PIECE: (i32, i32) = (10, 15);
+
+/// Pieces of `color` pinned to their king by an enemy bishop, rook or queen:
+/// the only piece between the king and the slider on its line
+pub(crate) fn pinned_pieces(board: &Board, color: Color) -> BitBoard {
+    let king = board.king_square(color);
+    let theirs = *board.color_combined(!color);
+    let occupied = *board.combined();
+    let queens = *board.pieces(Piece::Queen);
+    let diagonal = (*board.pieces(Piece::Bishop) | queens) & chess::get_bishop_rays(king);
+    let straight = (*board.pieces(Piece::Rook) | queens) & chess::get_rook_rays(king);
+
+    let mut pinned = EMPTY;
+    for pinner in (diagonal | straight) & theirs {
+        let between = chess::between(king, pinner) & occupied;
+        if between.popcnt() == 1 {
+            pinned |= between & *board.color_combined(color);
+        }
+    }
+    pinned
+}
+
+/// Mobility of one side's pieces, blended by `phase`. Each knight, bishop,
+/// rook and queen scores the squares it attacks in its mobility area, those
+/// neither occupied by a friendly piece nor attacked by an enemy pawn, with
+/// a weight per piece type. A piece pinned to its king scores no mobility,
+/// and a penalty instead.
+fn calculate_mobility(board: &Board, color: Color, phase: i32) -> i32 {
+    let ours = *board.color_combined(color);
+    let occupied = *board.combined();
+    let enemy_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(!color);
+    let pawn_attacks = enemy_pawns.fold(EMPTY, |attacks, sq| {
+        attacks | chess::get_pawn_attacks(sq, !color, !EMPTY)
+    });
+    let area = !ours & !pawn_attacks;
+    let pinned = pinned_pieces(board, color);
+
+    let (mut mg, mut eg) = (0, 0);
+    for (piece, mg_weight, eg_weight, baseline) in MOBILITY {
+        for sq in *board.pieces(piece) & ours {
+            if pinned & BitBoard::from_square(sq) != EMPTY {
+                mg -= PINNED_PIECE.0;
+                eg -= PINNED_PIECE.1;
+                continue;
+            }
+            let attacks = match piece {
+                Piece::Knight => chess::get_knight_moves(sq),
+                Piece::Bishop => chess::get_bishop_moves(sq, occupied),
+                Piece::Rook => chess::get_rook_moves(sq, occupied),
+                _ => chess::get_bishop_moves(sq, occupied) | chess::get_rook_moves(sq, occupied),
+            };
+            let count = count_bits(attacks & area) - baseline;
+            mg += mg_weight * count;
+            eg += eg_weight * count;
+        }
+    }
+    taper(mg, eg, phase)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chess::MoveGen;
     use std::str::FromStr;
 
     #[test]
@@ -575,7 +1858,7 @@ mod tests {
         let score = eval(&board);
         // Starting position should be roughly equal
         assert!(
-            score.abs() < 50.0,
+            score.abs() < 50,
             "Starting position eval {} should be near 0",
             score
         );
@@ -590,4 +1873,581 @@ mod tests {
         let score = eval(&board);
         assert_eq!(score, MATE_EVAL);
     }
+
+    #[test]
+    fn test_mate_in() {
+        assert_eq!(mate_in(MATE_EVAL - 1), Some(1));
+        assert_eq!(mate_in(MATE_EVAL - 5), Some(3));
+        assert_eq!(mate_in(-(MATE_EVAL - 2)), Some(-1));
+        assert_eq!(mate_in(-(MATE_EVAL - 6)), Some(-3));
+        assert_eq!(mate_in(-MATE_EVAL), Some(0));
+        assert_eq!(mate_in(900), None);
+        assert!(!is_mate_score(MATE_BOUND - 1));
+    }
+
+    #[test]
+    fn test_eval_breakdown_adds_up() {
+        let board =
+            Board::from_str("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        let breakdown = eval_breakdown(&board);
+        assert_eq!(breakdown.phase, MAX_PHASE);
+        assert_eq!(breakdown.material, [4000, 4000]);
+        assert_eq!(breakdown.total(), eval(&board));
+    }
+
+    #[test]
+    fn test_accumulator_follows_moves() {
+        // Quiet moves, captures, en passant, castling on both wings and
+        // underpromotions
+        let games: [(&str, &[&str]); 3] = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                &[
+                    "e2e4", "d7d5", "e4d5", "g8f6", "g1f3", "f6d5", "f1c4", "e7e6", "e1g1",
+                ],
+            ),
+            (
+                "r3k2r/pPp5/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1",
+                &["e5d6", "e8g8", "b7a8n", "c7d6", "a1a7"],
+            ),
+            (
+                "r3k3/8/8/8/8/8/6p1/4K2R b Kq - 0 1",
+                &["e8c8", "h1h8", "g2g1r"],
+            ),
+        ];
+        for (fen, moves) in games {
+            let mut board = Board::from_str(fen).unwrap();
+            let mut accumulator = Accumulator::new(&board);
+            for mv in moves {
+                let mv = ChessMove::from_str(mv).unwrap();
+                accumulator = accumulator.after_move(&board, mv);
+                board = board.make_move_new(mv);
+                assert_eq!(accumulator, Accumulator::new(&board), "after {}", mv);
+            }
+        }
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let dead = |fen: &str| is_insufficient_material(&Board::from_str(fen).unwrap());
+        assert!(dead("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(dead("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"));
+        assert!(dead("4kb2/8/8/8/8/8/8/4K3 w - - 0 1"));
+        // Bishops on the same square color, one each or several
+        assert!(dead("4k3/8/8/8/8/8/8/3BKb2 w - - 0 1"));
+        assert!(dead("4k3/8/8/8/3B4/8/8/4KB2 w - - 0 1"));
+        // Bishops on different square colors can mate
+        assert!(!dead("4k3/8/8/8/8/8/8/3BK1b1 w - - 0 1"));
+        // as can two knights, though they can't force it
+        assert!(!dead("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1"));
+        assert!(!dead("4k3/8/8/8/8/8/8/4KNn1 w - - 0 1"));
+        assert!(!dead("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+
+        let knights = Board::from_str("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1").unwrap();
+        assert!(is_drawish_material(&knights));
+        assert_eq!(eval_with_draw_score(&knights, -25), -25);
+        let same_color = Board::from_str("4k3/8/8/8/8/8/8/3BKb2 w - - 0 1").unwrap();
+        assert_eq!(has_game_ended(&same_color), GameResult::Draw);
+        assert_eq!(eval(&same_color), 0);
+    }
+
+    #[test]
+    fn test_scale_factor() {
+        // A pawn up with only opposite-colored bishops left
+        let ocb = Board::from_str("4k3/5p2/8/2b5/8/8/4PP2/3BK3 w - - 0 1").unwrap();
+        let breakdown = eval_breakdown(&ocb);
+        assert_eq!(breakdown.scale, SCALE_OCB);
+        assert_eq!(
+            breakdown.total(),
+            breakdown.unscaled_total() * SCALE_OCB / SCALE_NORMAL
+        );
+        // With rooks as well
+        let rooks = Board::from_str("r3k3/5p2/8/2b5/8/8/4PP2/3BK2R w - - 0 1").unwrap();
+        assert_eq!(eval_breakdown(&rooks).scale, SCALE_OCB_PIECES);
+        // Bishops on the same square color
+        let same = Board::from_str("4k3/5p2/8/8/2b5/8/4PP2/3BK3 w - - 0 1").unwrap();
+        assert_eq!(eval_breakdown(&same).scale, SCALE_NORMAL);
+        // Rook against bishop, and rook against rook
+        let exchange = Board::from_str("4k3/8/8/2b5/8/8/8/4K2R w - - 0 1").unwrap();
+        assert_eq!(eval_breakdown(&exchange).scale, SCALE_NO_PAWNS);
+        assert_eq!(eval_breakdown(&Board::default()).scale, SCALE_NORMAL);
+    }
+
+    #[test]
+    fn test_rook_behind_passed_pawn() {
+        // White's rook is behind its a-pawn, Black's in front of the e-pawn;
+        // a rook behind an enemy passer counts as well
+        let board = Board::from_str("4r1k1/8/8/4P3/P7/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(rooks(&board, Color::White, 0), ROOK_BEHIND_PASSER.1);
+        let board = Board::from_str("6k1/8/8/P7/8/8/4K3/r7 w - - 0 1").unwrap();
+        assert_eq!(rooks(&board, Color::Black, MAX_PHASE), ROOK_BEHIND_PASSER.0);
+        // A rook in front of the pawn, or blocked off from it, isn't behind
+        let board = Board::from_str("r5k1/8/8/P7/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(rooks(&board, Color::Black, 0), 0);
+        let board = Board::from_str("6k1/8/8/P7/8/N7/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(rooks(&board, Color::White, 0), 0);
+        // Nor behind a pawn that isn't passed
+        let board = Board::from_str("6k1/1p6/8/P7/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(rooks(&board, Color::White, 0), 0);
+    }
+
+    #[test]
+    fn test_connected_rooks() {
+        // Castled, with the back rank cleared, and not yet
+        let connected = Board::from_str("6k1/5ppp/8/8/8/8/5PPP/R4RK1 w - - 0 1").unwrap();
+        let blocked = Board::from_str("6k1/5ppp/8/8/8/8/5PPP/R1B2RK1 w - - 0 1").unwrap();
+        assert_eq!(
+            rooks(&connected, Color::White, MAX_PHASE),
+            CONNECTED_ROOKS.0
+        );
+        assert_eq!(rooks(&blocked, Color::White, MAX_PHASE), 0);
+        // Doubled on the open d-file, and on the half-open e-file
+        let open = Board::from_str("6k1/5ppp/8/8/8/3R4/5PPP/3R2K1 w - - 0 1").unwrap();
+        assert_eq!(
+            rooks(&open, Color::White, 0),
+            CONNECTED_ROOKS.1 + DOUBLED_ROOKS_OPEN_FILE.1
+        );
+        let half_open = Board::from_str("6k1/4pppp/8/8/8/4R3/5PPP/4R1K1 w - - 0 1").unwrap();
+        assert_eq!(rooks(&half_open, Color::White, 0), CONNECTED_ROOKS.1);
+    }
+
+    #[test]
+    fn test_kpk_eval() {
+        let won = Board::from_str("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(eval(&won), KNOWN_WIN + PAWN_VAL + 4);
+        let drawn = Board::from_str("k7/8/8/8/8/8/P7/7K w - - 0 1").unwrap();
+        assert_eq!(eval(&drawn), 0);
+        assert_eq!(eval_with_draw_score(&drawn, -25), -25);
+    }
+
+    #[test]
+    fn test_mop_up() {
+        // The bare king on the edge, with White's king close, scores higher
+        // than in the centre
+        let edge = Board::from_str("3k4/8/3K4/8/8/8/8/7Q w - - 0 1").unwrap();
+        let center = Board::from_str("8/8/8/3k4/8/8/3K4/7Q w - - 0 1").unwrap();
+        assert_eq!(
+            mop_up(&edge, Color::White),
+            MOP_UP_EDGE * 3 + MOP_UP_KINGS * 12
+        );
+        assert_eq!(mop_up(&center, Color::White), MOP_UP_KINGS * 11);
+        assert_eq!(mop_up(&edge, Color::Black), 0);
+        assert!(eval(&edge) > KNOWN_WIN);
+        // A lone knight can't mate, and the bare king side has no mop-up
+        let knight = Board::from_str("3k4/8/3K4/8/8/8/8/7N w - - 0 1").unwrap();
+        assert_eq!(mop_up(&knight, Color::White), 0);
+        // With bishop and knight, the king belongs in the bishop's corner
+        let right = Board::from_str("k7/2K5/8/8/8/8/8/3BN3 w - - 0 1").unwrap();
+        let wrong = Board::from_str("7k/5K2/8/8/8/8/8/3BN3 w - - 0 1").unwrap();
+        assert_eq!(
+            mop_up(&right, Color::White) - mop_up(&wrong, Color::White),
+            KBN_CORNER * 7
+        );
+        // Promoting in a won king and pawn endgame is still progress
+        let queened = Board::from_str("4Q3/8/8/8/1k6/8/8/4K3 b - - 0 1").unwrap();
+        assert!(eval(&queened) > KNOWN_WIN + PAWN_VAL + 7);
+    }
+
+    #[test]
+    fn test_tapered_eval() {
+        assert_eq!(game_phase(&Board::default()), MAX_PHASE);
+        let pawn_endgame = Board::from_str("4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        assert_eq!(game_phase(&pawn_endgame), 0);
+        assert_eq!(taper(100, 20, MAX_PHASE), 100);
+        assert_eq!(taper(100, 20, MAX_PHASE / 2), 60);
+        assert_eq!(taper(100, 20, 0), 20);
+
+        // With only queens left the king tables are mostly the endgame's, and
+        // king safety counts for a third
+        let queens = Board::from_str("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let breakdown = eval_breakdown(&queens);
+        assert_eq!(breakdown.phase, 8);
+        assert_eq!(
+            breakdown.king_safety[0],
+            king_safety(&queens, Color::White) / 3
+        );
+        // Every piece-square table and piece value is blended the same way
+        let queen = piece_square_values(Piece::Queen, Color::White, Square::D1);
+        let king = piece_square_values(Piece::King, Color::White, Square::E1);
+        assert_eq!(king, (WHITE_KING_MG_TABLE[4], WHITE_KING_EG_TABLE[4]));
+        assert_eq!(
+            breakdown.pst[0],
+            taper(queen.0 + king.0, queen.1 + king.1, 8)
+        );
+        assert_eq!(breakdown.material[0], taper(QUEEN_VAL, QUEEN_VAL_EG, 8));
+    }
+
+    #[test]
+    fn test_mobility_area() {
+        // The knight on b1 reaches a3 and c3, unless a pawn guards them, and
+        // never d2, where its own pawn stands
+        let free = Board::from_str("4k3/8/8/8/8/8/3P4/1N2K3 w - - 0 1").unwrap();
+        let guarded = Board::from_str("4k3/8/8/8/1p6/8/3P4/1N2K3 w - - 0 1").unwrap();
+        assert_eq!(
+            calculate_mobility(&free, Color::White, MAX_PHASE),
+            4 * (2 - 4)
+        );
+        assert_eq!(
+            calculate_mobility(&guarded, Color::White, MAX_PHASE),
+            4 * (0 - 4)
+        );
+        // Rooks and queens count for more in the endgame
+        let rook = Board::from_str("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(
+            calculate_mobility(&rook, Color::White, MAX_PHASE),
+            2 * (10 - 7)
+        );
+        assert_eq!(calculate_mobility(&rook, Color::White, 0), 3 * (10 - 7));
+    }
+
+    #[test]
+    fn test_pins() {
+        // The knight on e2 is pinned by the rook on e7, unless the bishop on
+        // e3 stands between them too
+        let pinned = Board::from_str("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        let shielded = Board::from_str("4k3/4r3/8/8/8/4B3/4N3/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            pinned_pieces(&pinned, Color::White),
+            BitBoard::from_square(Square::E2)
+        );
+        assert_eq!(pinned_pieces(&pinned, Color::Black), EMPTY);
+        assert_eq!(pinned_pieces(&shielded, Color::White), EMPTY);
+        // A pinned piece scores no mobility, and a penalty
+        assert_eq!(
+            calculate_mobility(&pinned, Color::White, MAX_PHASE),
+            -PINNED_PIECE.0
+        );
+        // and weakens the king it stands next to; the rook attacks the king
+        // zone the same either way
+        let unpinned = Board::from_str("4k3/8/4r3/8/8/8/4N3/5K2 w - - 0 1").unwrap();
+        let pinned = Board::from_str("4k3/8/4r3/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            king_safety(&unpinned, Color::White) - king_safety(&pinned, Color::White),
+            PINNED_DEFENDER
+        );
+    }
+
+    #[test]
+    fn test_threats() {
+        // The knight on c7 forks the rooks on a8 and e8
+        let fork = Board::from_str("r3r1k1/2N5/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(
+            threats(&fork, Color::White, MAX_PHASE),
+            2 * THREAT_BY_MINOR.0
+        );
+        assert_eq!(threats(&fork, Color::Black, MAX_PHASE), 0);
+        // The pawn on d5 attacks the knight on c6 and the bishop on e6
+        let pawn = Board::from_str("4k3/8/2n1b3/3P4/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(threats(&pawn, Color::White, 0), 2 * THREAT_BY_PAWN.1);
+        // A pawn attacked by a pawn is no threat
+        let pawns = Board::from_str("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(threats(&pawns, Color::White, 0), 0);
+    }
+
+    #[test]
+    fn test_hanging_pieces() {
+        // White's knight on f4 is defended by the e3 pawn; the bishop on b5
+        // is undefended and attacked by the a6 pawn, the rook on h1 is
+        // undefended but not attacked
+        let board = Board::from_str("6k1/8/p7/1B6/5N2/4P3/8/4K2R w - - 0 1").unwrap();
+        assert_eq!(
+            hanging_pieces(&board, Color::White, MAX_PHASE),
+            -(HANGING_PIECE.0 + UNDEFENDED_PIECE.0)
+        );
+        // The king defends
+        let board = Board::from_str("4k3/8/8/8/8/8/8/4KR2 w - - 0 1").unwrap();
+        assert_eq!(hanging_pieces(&board, Color::White, 0), 0);
+    }
+
+    #[test]
+    fn test_bishop_pair() {
+        let board = Board::default();
+        assert_eq!(bishop_pair(&board, Color::White, MAX_PHASE), BISHOP_PAIR_MG);
+        assert_eq!(bishop_pair(&board, Color::Black, 0), BISHOP_PAIR_EG);
+        // Black has traded a bishop for a knight
+        let board =
+            Board::from_str("rn1qkbnr/ppp1pppp/8/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R w KQkq - 0 3")
+                .unwrap();
+        assert_eq!(bishop_pair(&board, Color::Black, MAX_PHASE), 0);
+        // Two light-squared bishops are no pair
+        let board = Board::from_str("4k3/8/8/8/8/8/8/3BKB2 w - - 0 1").unwrap();
+        assert_eq!(bishop_pair(&board, Color::White, 0), 0);
+    }
+
+    #[test]
+    fn test_king_attack_units() {
+        // A lone queen attacking two squares next to the castled king, and
+        // the same queen backed by a knight
+        let queen = Board::from_str("6k1/8/8/8/8/7q/5PPP/6K1 w - - 0 1").unwrap();
+        let both = Board::from_str("6k1/8/8/8/7n/7q/5PPP/6K1 w - - 0 1").unwrap();
+        let shelter = pawn_shelter(
+            *queen.pieces(Piece::Pawn) & *queen.color_combined(Color::White),
+            EMPTY,
+            File::G.to_index() as i32,
+            Color::White,
+        );
+        let queen_units = 5 * count_bits(
+            (chess::get_bishop_moves(Square::H3, *queen.combined())
+                | chess::get_rook_moves(Square::H3, *queen.combined()))
+                & (chess::get_king_moves(Square::G1) | BitBoard::from_square(Square::G1)),
+        );
+        assert_eq!(
+            king_safety(&queen, Color::White),
+            shelter - king_danger(queen_units / 2)
+        );
+        // The knight on h4 joins the attack on g2
+        assert_eq!(
+            king_safety(&both, Color::White),
+            shelter - king_danger(queen_units + 2)
+        );
+        assert!(king_danger(queen_units + 2) > 2 * king_danger(queen_units / 2));
+    }
+
+    #[test]
+    fn test_mirror() {
+        let board =
+            Board::from_str("r3k2r/pp3ppp/2n5/3pP3/8/8/PPP2PPP/R3K1R1 w Qkq d6 0 12").unwrap();
+        let mirrored = mirror(&board);
+        assert_eq!(
+            mirrored,
+            Board::from_str("r3k1r1/ppp2ppp/8/8/3Pp3/2N5/PP3PPP/R3K2R b KQq d3 0 12").unwrap()
+        );
+        assert_eq!(mirror(&mirrored), board);
+    }
+
+    /// Every position up to two plies from a varied set, openings with
+    /// castling rights and en passant, middlegames and endgames alike, must
+    /// score the exact negative of its mirror
+    #[test]
+    fn test_eval_symmetry() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+            "3b4/5kp1/1p1p1p1p/pP1PpP1P/P1P1P3/3KN3/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3BN3/8/4K3 w - - 0 1",
+            "8/8/8/4k3/8/8/2P5/4K3 b - - 0 1",
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            for first in MoveGen::new_legal(&board) {
+                let next = board.make_move_new(first);
+                for second in MoveGen::new_legal(&next) {
+                    let position = next.make_move_new(second);
+                    if let Err(e) = check_symmetry(&position) {
+                        panic!("{} after {} {} from {}", e, first, second, fen);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_development() {
+        let board = Board::default();
+        assert_eq!(
+            development(&board, Color::White, MAX_PHASE),
+            -4 * UNDEVELOPED_MINOR
+        );
+        // After 1. e4 e5 2. Nf3 Nc6 3. Bc4, White has two pieces left and Black three
+        let board =
+            Board::from_str("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3")
+                .unwrap();
+        assert_eq!(
+            development(&board, Color::White, MAX_PHASE),
+            -2 * UNDEVELOPED_MINOR
+        );
+        assert_eq!(
+            development(&board, Color::Black, MAX_PHASE),
+            -3 * UNDEVELOPED_MINOR
+        );
+        // Halfway to the threshold it counts half, and past it not at all
+        assert_eq!(
+            development(&board, Color::White, (MAX_PHASE + DEVELOPMENT_PHASE) / 2),
+            -UNDEVELOPED_MINOR
+        );
+        assert_eq!(development(&board, Color::White, DEVELOPMENT_PHASE), 0);
+    }
+
+    #[test]
+    fn test_center_control() {
+        // The starting position: the pawns on b2-g2 make eight attacks on the
+        // ring's third rank squares, and the knights cover c3 and f3
+        let board = Board::default();
+        let start = 8 * EXTENDED_CENTER_PAWN + 2 * EXTENDED_CENTER_PIECE;
+        assert_eq!(center_control(&board, Color::White), start);
+        assert_eq!(center_control(&board, Color::Black), start);
+
+        // 1. e4 d5: the pawns now attack d5 and e4 from the centre, and the
+        // queen and the bishops see through the opened lines
+        let board = Board::from_str("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2")
+            .unwrap();
+        assert!(center_control(&board, Color::White) > start);
+        assert!(center_control(&board, Color::Black) > start);
+        let breakdown = eval_breakdown(&board);
+        assert_eq!(
+            breakdown.center[0],
+            taper(center_control(&board, Color::White), 0, breakdown.phase)
+        );
+
+        // Nothing is left of it without pieces
+        let board = Board::from_str("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(eval_breakdown(&board).center, [0, 0]);
+    }
+
+    #[test]
+    fn test_weak_squares() {
+        // Every square of the starting position can still be covered by a pawn
+        let board = Board::default();
+        assert_eq!(holes(&board, Color::White), (EMPTY, EMPTY));
+        assert_eq!(eval_breakdown(&board).weak_squares, [0, 0]);
+
+        // 1. c4 c5 2. e4 e5: no pawn is left to cover d3 and d4, and d3 is
+        // in front of the king as well. The same holds for Black on d6 and
+        // d5.
+        let board =
+            Board::from_str("rnbqkbnr/pp1p1ppp/8/2p1p3/2P1P3/8/PP1P1PPP/RNBQKBNR w KQkq - 0 3")
+                .unwrap();
+        let squares = |squares: &[Square]| {
+            squares
+                .iter()
+                .fold(EMPTY, |bb, &sq| bb | BitBoard::from_square(sq))
+        };
+        assert_eq!(
+            holes(&board, Color::White),
+            (squares(&[Square::D3, Square::D4]), squares(&[Square::D3]))
+        );
+        assert_eq!(
+            holes(&board, Color::Black),
+            (squares(&[Square::D5, Square::D6]), squares(&[Square::D6]))
+        );
+        let phase = game_phase(&board);
+        let both_bishops = weak_squares(&board, Color::White, phase);
+        assert_eq!(
+            both_bishops,
+            -taper(
+                4 * HOLE.0 + 2 * KING_HOLE.0,
+                4 * HOLE.1 + 2 * KING_HOLE.1,
+                phase
+            )
+        );
+        assert_eq!(weak_squares(&board, Color::Black, phase), both_bishops);
+
+        // Without Black's light-squared bishop, the light d3 counts once
+        let board =
+            Board::from_str("rn1qkbnr/pp1p1ppp/8/2p1p3/2P1P3/8/PP1P1PPP/RNBQKBNR w KQkq - 0 3")
+                .unwrap();
+        let phase = game_phase(&board);
+        assert_eq!(
+            weak_squares(&board, Color::White, phase),
+            -taper(3 * HOLE.0 + KING_HOLE.0, 3 * HOLE.1 + KING_HOLE.1, phase)
+        );
+    }
+
+    #[test]
+    fn test_castling() {
+        assert_eq!(castling(&Board::default(), Color::White), CASTLING_RIGHTS);
+        // Castled short, and long
+        let castled = Board::from_str("2kr3r/pppq1ppp/8/8/8/8/PPPQ1PPP/R4RK1 w - - 0 1").unwrap();
+        assert_eq!(castling(&castled, Color::White), CASTLED);
+        assert_eq!(castling(&castled, Color::Black), CASTLED);
+        // A king on g1 with the rook still beside it on h1 hasn't castled
+        let trapped = Board::from_str("4k3/8/8/8/8/8/5PPP/R5KR w - - 0 1").unwrap();
+        assert_eq!(castling(&trapped, Color::White), 0);
+        // Stuck in the centre: Ke2 with the rooks at home
+        let stuck = Board::from_str("r3k2r/pppq1ppp/8/8/8/8/PPPQKPPP/R6R w kq - 0 1").unwrap();
+        assert_eq!(castling(&stuck, Color::White), -STUCK_KING);
+        let walked = Board::from_str("r3k2r/pppq1ppp/8/8/8/8/PPPQ1PPP/R3K2R w kq - 0 1").unwrap();
+        assert_eq!(castling(&walked, Color::White), -STUCK_KING);
+        // Further up the board the king is no longer stuck at home
+        let marched = Board::from_str("r3k2r/pppq1ppp/8/8/4K3/8/PPPQ1PPP/R6R w kq - 0 1").unwrap();
+        assert_eq!(castling(&marched, Color::White), 0);
+    }
+
+    #[test]
+    fn test_shelter_forecast() {
+        // An uncastled king whose f-pawn was lifted two squares: with the
+        // pawn a step back on f3 its shield is at its best, and that counts
+        let board = Board::from_str("4k3/8/8/8/5P2/8/3PP3/4K3 w - - 0 1").unwrap();
+        let pawns = *board.pieces(Piece::Pawn) & *board.color_combined(Color::White);
+        let standing = pawn_shelter(pawns, EMPTY, File::E.to_index() as i32, Color::White);
+        assert_eq!(standing, 2 * SHIELD_PAWN);
+        assert_eq!(
+            king_safety(&board, Color::White),
+            standing + SHIELD_PAWN_ADVANCED
+        );
+        // Blocked on f4, the pawn has no step back to take
+        let blocked = Board::from_str("4k3/8/8/8/5P2/5b2/3PP3/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            shelter_forecast(&blocked, pawns, EMPTY, Square::E1, Color::White),
+            standing
+        );
+    }
+
+    #[test]
+    fn test_passed_pawn_blockade_and_escort() {
+        // White passer on d6 with the stop square free, covered by the king
+        let free = Board::from_str("4k3/8/3P4/4K3/8/8/8/8 w - - 0 1").unwrap();
+        // Same passer blockaded by a knight, and by a bishop
+        let knight = Board::from_str("4k3/3n4/3P4/4K3/8/8/8/8 w - - 0 1").unwrap();
+        let bishop = Board::from_str("4k3/3b4/3P4/4K3/8/8/8/8 w - - 0 1").unwrap();
+        // Not passed: an enemy pawn on an adjacent file ahead
+        let stopped = Board::from_str("4k3/2p5/3P4/4K3/8/8/8/8 w - - 0 1").unwrap();
+
+        let free_score = passed_pawns(&free, Color::White, MAX_PHASE);
+        assert!(free_score > 0);
+        assert!(passed_pawns(&bishop, Color::White, MAX_PHASE) < free_score);
+        assert!(
+            passed_pawns(&knight, Color::White, MAX_PHASE)
+                < passed_pawns(&bishop, Color::White, MAX_PHASE)
+        );
+        assert_eq!(passed_pawns(&stopped, Color::White, MAX_PHASE), 0);
+    }
+
+    #[test]
+    fn test_passed_pawn_king_proximity() {
+        // The same d5 passer with White's king escorting it, and left behind
+        let escorted = Board::from_str("7k/8/8/3P4/2K5/8/8/8 w - - 0 1").unwrap();
+        let behind = Board::from_str("7k/8/8/3P4/8/8/8/K7 w - - 0 1").unwrap();
+        let score = |board: &Board, phase: i32| passed_pawns(board, Color::White, phase);
+        // d6 is 2 squares from the escort and 5 from the king left behind,
+        // and the pawn is 2 ranks past its third
+        assert_eq!(
+            score(&escorted, 0) - score(&behind, 0),
+            2 * PASSED_PAWN_OUR_KING * 3
+        );
+        assert!(score(&escorted, 0) > score(&escorted, MAX_PHASE));
+        // In the middlegame the kings don't matter
+        assert_eq!(score(&escorted, MAX_PHASE), score(&behind, MAX_PHASE));
+    }
+
+    #[test]
+    fn test_pawn_majorities() {
+        // Six pawns each, with a majority of one on opposite wings, but only
+        // White's, on the queenside, is far from the enemy king
+        let board = Board::from_str("6k1/pp2pppp/8/8/8/8/PPP2PPP/6K1 w - - 0 1").unwrap();
+        let white = pawn_majorities(&board, Color::White, 0);
+        let black = pawn_majorities(&board, Color::Black, 0);
+        assert_eq!(white, MAJORITY_BONUS + OUTSIDE_MAJORITY_BONUS);
+        assert_eq!(black, MAJORITY_BONUS);
+        assert!(eval(&board) > 0);
+        // Doubled pawns make no majority
+        let doubled = Board::from_str("6k1/pp3ppp/8/8/8/P7/PP3PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(pawn_majorities(&doubled, Color::White, 0), 0);
+    }
+
+    #[test]
+    fn test_minority_attack() {
+        // White's b-rook backs a lone a-pawn against three on the queenside
+        let board = Board::from_str("r5k1/pp3ppp/2p5/8/8/8/P4PPP/1R4K1 w - - 0 1").unwrap();
+        assert_eq!(
+            pawn_majorities(&board, Color::White, MAX_PHASE),
+            MINORITY_ATTACK_BONUS
+        );
+        // Only in the middlegame
+        assert_eq!(pawn_majorities(&board, Color::White, 0), 0);
+    }
 }
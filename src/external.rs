@@ -0,0 +1,206 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use chess::{Board, ChessMove, Color};
+
+use crate::engine::SearchLimits;
+use crate::evaluation::MATE_EVAL;
+use crate::game::GamePlayer;
+use crate::moves;
+
+/// Another UCI engine, run as a child process, that can take part in games
+pub struct ExternalEngine {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl ExternalEngine {
+    /// Start the engine binary at `path` and complete the UCI handshake
+    pub fn start(path: &str) -> io::Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut engine = ExternalEngine {
+            name: path.to_string(),
+            child,
+            stdin,
+            stdout,
+        };
+
+        engine.send("uci")?;
+        loop {
+            let line = engine.read_line()?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+        engine.sync()?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+
+    /// Next line of output; an error once the engine has exited
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{} exited", self.name),
+            ));
+        }
+        Ok(line.trim_end().to_string())
+    }
+
+    /// Wait until the engine has processed everything sent so far
+    fn sync(&mut self) -> io::Result<()> {
+        self.send("isready")?;
+        while self.read_line()?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    /// Search the position given by a UCI `position` command. Returns the
+    /// `bestmove` text and the last reported score from White's point of view.
+    fn search(
+        &mut self,
+        position: &str,
+        limits: &SearchLimits,
+        side: Color,
+    ) -> io::Result<(String, Option<i32>)> {
+        self.send(position)?;
+        self.send(&go_command(limits))?;
+
+        let mut eval = None;
+        loop {
+            let line = self.read_line()?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens.first() {
+                Some(&"info") => eval = parse_score(&tokens, side).or(eval),
+                Some(&"bestmove") => {
+                    return Ok((tokens.get(1).unwrap_or(&"").to_string(), eval));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+impl GamePlayer for ExternalEngine {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self) {
+        // A failure here shows up as a missing move on the next search
+        let _ = self.send("ucinewgame").and_then(|_| self.sync());
+    }
+
+    fn choose_move(
+        &mut self,
+        start: &Board,
+        moves: &[ChessMove],
+        board: &Board,
+        _history: &[u64],
+        limits: &SearchLimits,
+    ) -> Option<(ChessMove, Option<i32>)> {
+        let position = position_command(start, moves);
+        let (move_str, eval) = self.search(&position, limits, board.side_to_move()).ok()?;
+        let mv = moves::parse_uci_move(board, &move_str).ok()?;
+        Some((mv, eval))
+    }
+}
+
+/// UCI `position` command for the game that started at `start`
+fn position_command(start: &Board, moves: &[ChessMove]) -> String {
+    let mut command = format!("position fen {}", start);
+    if !moves.is_empty() {
+        command += " moves";
+        for mv in moves {
+            command += &format!(" {}", mv);
+        }
+    }
+    command
+}
+
+/// UCI `go` command searching within `limits`
+fn go_command(limits: &SearchLimits) -> String {
+    let mut command = "go".to_string();
+    if let Some(seconds) = limits.time {
+        command += &format!(" movetime {}", (seconds * 1000.0).round() as u64);
+    }
+    if let Some(depth) = limits.depth {
+        command += &format!(" depth {}", depth);
+    }
+    command
+}
+
+/// Score of an `info` line, converted from the point of view of `side` (the
+/// side to move) to White's
+fn parse_score(tokens: &[&str], side: Color) -> Option<i32> {
+    let at = tokens.iter().position(|&t| t == "score")?;
+    let value: i32 = tokens.get(at + 2)?.parse().ok()?;
+    let score = match *tokens.get(at + 1)? {
+        "cp" => value,
+        // Mate in N moves is 2N - 1 plies away, getting mated in N is 2N
+        "mate" if value > 0 => MATE_EVAL - (2 * value - 1),
+        "mate" => -(MATE_EVAL + 2 * value),
+        _ => return None,
+    };
+    Some(if side == Color::White { score } else { -score })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_score() {
+        let tokens = |line: &'static str| line.split_whitespace().collect::<Vec<_>>();
+        let line = tokens("info depth 5 score cp 34 nodes 1000 pv e2e4");
+        assert_eq!(parse_score(&line, Color::White), Some(34));
+        assert_eq!(parse_score(&line, Color::Black), Some(-34));
+        let mate = tokens("info depth 9 score mate -3 pv h7h8");
+        assert_eq!(parse_score(&mate, Color::White), Some(-(MATE_EVAL - 6)));
+        let mate = tokens("info depth 9 score mate 2 pv h7h8");
+        assert_eq!(parse_score(&mate, Color::Black), Some(-(MATE_EVAL - 3)));
+        assert_eq!(
+            parse_score(&tokens("info string hello"), Color::White),
+            None
+        );
+    }
+
+    #[test]
+    fn test_position_and_go_commands() {
+        let board = Board::default();
+        assert_eq!(
+            position_command(&board, &[]),
+            "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        let e4 = moves::parse_uci_move(&board, "e2e4").unwrap();
+        assert!(position_command(&board, &[e4]).ends_with(" moves e2e4"));
+        assert_eq!(go_command(&SearchLimits::depth(4)), "go depth 4");
+        assert_eq!(go_command(&SearchLimits::time(0.25)), "go movetime 250");
+    }
+}
@@ -0,0 +1,296 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{Board, ChessMove, Color};
+
+use crate::adjudication::{self, MercyRule};
+use crate::book::Book;
+use crate::engine::{self, EngineOptions, EngineState, SearchLimits};
+use crate::evaluation::GameResult;
+use crate::moves;
+use crate::variant::Variant;
+
+/// Games still running after this many plies are drawn
+pub const MAX_GAME_PLIES: usize = 400;
+
+/// Time per move when neither a depth nor a movetime is given, in milliseconds
+pub const DEFAULT_GAME_MOVETIME_MS: u64 = 100;
+
+/// Centipawn advantage, held for `MERCY_PLIES` plies, that ends a game
+const MERCY_THRESHOLD: i32 = 1000;
+const MERCY_PLIES: u32 = 8;
+
+/// An engine configuration taking part in internal games
+pub struct Player {
+    pub name: String,
+    pub options: EngineOptions,
+    pub engine_state: EngineState,
+}
+
+impl Player {
+    /// A player with default settings, no book and no search output
+    pub fn new(name: &str) -> Self {
+        Player {
+            name: name.to_string(),
+            options: EngineOptions {
+                own_book: false,
+                silent: true,
+                ..EngineOptions::default()
+            },
+            engine_state: EngineState::new(),
+        }
+    }
+
+    /// A player sharing the settings of an engine session, but with its own
+    /// transposition table and without book or search output
+    pub fn with_settings(name: &str, options: &EngineOptions, engine_state: &EngineState) -> Self {
+        let mut state = EngineState::new();
+        state.set_tt_bucket_size(engine_state.tt_bucket_size());
+        state.set_hash_mb(engine_state.hash_mb());
+        state.params = engine_state.params;
+        state.set_time_check_interval(engine_state.time_check_interval());
+        state.set_network(engine_state.network().cloned());
+        Player {
+            name: name.to_string(),
+            options: EngineOptions {
+                own_book: false,
+                silent: true,
+                ..options.clone()
+            },
+            engine_state: state,
+        }
+    }
+
+    /// Set a UCI option or search parameter. Returns false for unknown names.
+    pub fn set_option(&mut self, name: &str, value: &str) -> bool {
+        engine::set_engine_option(&mut self.options, &mut self.engine_state, name, value)
+    }
+}
+
+/// One side of an internal game: an engine configuration or an external
+/// engine
+pub trait GamePlayer {
+    fn name(&self) -> &str;
+
+    /// Rules the player plays by, which decide when the game is over
+    fn variant(&self) -> Variant {
+        Variant::Standard
+    }
+
+    /// Called before the first move of every game
+    fn new_game(&mut self);
+
+    /// Pick a move in `board`, reached from `start` by `moves`. `history`
+    /// holds the hash of every position so far. Returns the move and, if
+    /// known, the evaluation from White's point of view, or None if no
+    /// legal move came back.
+    fn choose_move(
+        &mut self,
+        start: &Board,
+        moves: &[ChessMove],
+        board: &Board,
+        history: &[u64],
+        limits: &SearchLimits,
+    ) -> Option<(ChessMove, Option<i32>)>;
+}
+
+impl GamePlayer for Player {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn variant(&self) -> Variant {
+        self.options.variant
+    }
+
+    fn new_game(&mut self) {
+        self.engine_state.clear();
+    }
+
+    fn choose_move(
+        &mut self,
+        start: &Board,
+        moves: &[ChessMove],
+        board: &Board,
+        history: &[u64],
+        limits: &SearchLimits,
+    ) -> Option<(ChessMove, Option<i32>)> {
+        let book = Book::new();
+        // Games start with a fresh halfmove clock, as `play_game` counts it
+        let boards: Vec<Board> = std::iter::once(*start)
+            .chain(moves.iter().scan(*start, |board, &mv| {
+                *board = board.make_move_new(mv);
+                Some(*board)
+            }))
+            .collect();
+        let result = engine::play_move(
+            board,
+            &book,
+            limits,
+            history,
+            adjudication::halfmove_clock(0, &boards),
+            &mut self.engine_state,
+            &self.options,
+        );
+        Some((result.best_move?, Some(result.score)))
+    }
+}
+
+/// Outcome of one internal game
+pub struct GameRecord {
+    pub moves: Vec<ChessMove>,
+    pub result: GameResult,
+    /// Why the game ended, e.g. "checkmate" or "threefold repetition"
+    pub reason: &'static str,
+}
+
+/// Points White scored in a finished game
+pub fn white_points(result: GameResult) -> f64 {
+    match result {
+        GameResult::WhiteWins => 1.0,
+        GameResult::BlackWins => 0.0,
+        GameResult::Draw | GameResult::Ongoing => 0.5,
+    }
+}
+
+/// Result in PGN notation
+pub fn result_text(result: GameResult) -> &'static str {
+    match result {
+        GameResult::WhiteWins => "1-0",
+        GameResult::BlackWins => "0-1",
+        GameResult::Draw => "1/2-1/2",
+        GameResult::Ongoing => "*",
+    }
+}
+
+/// Search limits per move of an internal game. A depth alone searches to
+/// that depth; otherwise each move gets `movetime_ms` (by default
+/// `DEFAULT_GAME_MOVETIME_MS`), also capped at the depth if one is given.
+pub fn game_limits(depth: Option<i32>, movetime_ms: Option<u64>) -> SearchLimits {
+    match (depth, movetime_ms) {
+        (Some(depth), None) => SearchLimits::depth(depth),
+        (depth, movetime) => SearchLimits {
+            time: Some(movetime.unwrap_or(DEFAULT_GAME_MOVETIME_MS) as f64 / 1000.0),
+            depth,
+            flexible_time: false,
+            max_time: None,
+            nodes: None,
+        },
+    }
+}
+
+/// Board after playing `moves` (in UCI notation) from the start position
+pub fn opening_board(moves: &str) -> Result<Board, String> {
+    let mut board = Board::default();
+    for move_str in moves.split_whitespace() {
+        let mv = moves::parse_uci_move(&board, move_str).map_err(|e| e.to_string())?;
+        board = board.make_move_new(mv);
+    }
+    Ok(board)
+}
+
+/// Play one game from `start` between `white` and `black`, adjudicating
+/// repetitions, the fifty-move rule, hopeless positions and overlong games.
+/// A player that fails to return a legal move loses.
+pub fn play_game<'a>(
+    start: &Board,
+    white: &mut (dyn GamePlayer + 'a),
+    black: &mut (dyn GamePlayer + 'a),
+    limits: &SearchLimits,
+) -> GameRecord {
+    let mut board = *start;
+    let mut history = vec![board.get_hash()];
+    let mut moves = Vec::new();
+    let mut halfmove_clock = 0;
+    let mut mercy_rule = MercyRule::new(MERCY_THRESHOLD, MERCY_PLIES);
+    white.new_game();
+    black.new_game();
+
+    let finish = |moves, result, reason| GameRecord {
+        moves,
+        result,
+        reason,
+    };
+
+    loop {
+        let player = if board.side_to_move() == Color::White {
+            &mut *white
+        } else {
+            &mut *black
+        };
+        if let Some((result, reason)) =
+            adjudication::rules_result(&board, &history, halfmove_clock, player.variant())
+        {
+            return finish(moves, result, reason);
+        }
+        if moves.len() >= MAX_GAME_PLIES {
+            return finish(moves, GameResult::Draw, "move limit");
+        }
+
+        let legal = player.variant().legal_moves(&board);
+        let choice = player
+            .choose_move(start, &moves, &board, &history, limits)
+            .filter(|(mv, _)| legal.contains(mv));
+        let Some((mv, eval)) = choice else {
+            let result = if board.side_to_move() == Color::White {
+                GameResult::BlackWins
+            } else {
+                GameResult::WhiteWins
+            };
+            return finish(moves, result, "no legal move");
+        };
+
+        let next = board.make_move_new(mv);
+        halfmove_clock = if adjudication::resets_halfmove_clock(&board, &next) {
+            0
+        } else {
+            halfmove_clock + 1
+        };
+        board = next;
+        history.push(board.get_hash());
+        moves.push(mv);
+
+        if let Some(result) = eval.and_then(|eval| mercy_rule.update(eval)) {
+            return finish(moves, result, "mercy rule");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_player_options() {
+        let mut player = Player::new("test");
+        assert!(player.set_option("Contempt", "20"));
+        assert!(player.set_option("NullMoveR", "3"));
+        assert!(!player.set_option("NoSuchOption", "1"));
+        assert_eq!(player.options.contempt, 20);
+        assert_eq!(player.engine_state.params.null_move_r, 3);
+    }
+
+    #[test]
+    fn test_opening_board() {
+        let board = opening_board("e2e4 e7e5").unwrap();
+        let expected =
+            Board::from_str("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2")
+                .unwrap();
+        assert_eq!(board, expected);
+        assert!(opening_board("e2e5").is_err());
+    }
+
+    #[test]
+    fn test_play_game_finds_mate() {
+        // White mates at once with Qd7-b7
+        let start = Board::from_str("k7/3Q4/1K6/8/8/8/8/8 w - - 0 1").unwrap();
+        let mut white = Player::new("white");
+        let mut black = Player::new("black");
+        let record = play_game(&start, &mut white, &mut black, &SearchLimits::depth(2));
+        assert_eq!(record.result, GameResult::WhiteWins);
+        assert_eq!(record.reason, "checkmate");
+        assert_eq!(record.moves.len(), 1);
+    }
+}
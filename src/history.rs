@@ -0,0 +1,238 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{Board, ChessMove, Color, Piece, Square};
+
+/// Largest score a history entry reaches before the whole table is aged
+pub const HISTORY_MAX: i32 = 8192;
+
+/// Piece that moved and the square it went to, as seen by continuation
+/// history; None for a null move
+pub type PriorMove = Option<(Piece, Square)>;
+
+/// Add a cutoff bonus to one entry of a table, ageing the whole table once
+/// the entry outgrows `HISTORY_MAX`
+fn bump(scores: &mut [i32], index: usize, depth: i32) {
+    scores[index] += depth * depth;
+    if scores[index] > HISTORY_MAX {
+        age(scores);
+    }
+}
+
+fn age(scores: &mut [i32]) {
+    for score in scores {
+        *score /= 2;
+    }
+}
+
+/// Butterfly history: how often a quiet move, by from and to square and side,
+/// caused a beta cutoff. Deeper cutoffs count for more.
+pub struct HistoryTable {
+    scores: Vec<i32>,
+}
+
+impl Default for HistoryTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistoryTable {
+    pub fn new() -> Self {
+        HistoryTable {
+            scores: vec![0; 2 * 64 * 64],
+        }
+    }
+
+    fn index(color: Color, mv: ChessMove) -> usize {
+        (color.to_index() * 64 + mv.get_source().to_index()) * 64 + mv.get_dest().to_index()
+    }
+
+    /// Score of `mv` played by `color`, between 0 and `HISTORY_MAX`
+    pub fn get(&self, color: Color, mv: ChessMove) -> i32 {
+        self.scores[Self::index(color, mv)]
+    }
+
+    /// Credit `mv` with a beta cutoff `depth` plies from the horizon
+    pub fn reward(&mut self, color: Color, mv: ChessMove, depth: i32) {
+        bump(&mut self.scores, Self::index(color, mv), depth);
+    }
+
+    /// Halve every score, so that what was learned in earlier searches
+    /// gradually gives way to the current position
+    pub fn age(&mut self) {
+        age(&mut self.scores);
+    }
+
+    pub fn clear(&mut self) {
+        self.scores.fill(0);
+    }
+}
+
+/// Continuation history: how often a quiet move, by side, piece and
+/// destination, caused a beta cutoff right after a given earlier move
+pub struct ContinuationHistory {
+    scores: Vec<i32>,
+}
+
+impl Default for ContinuationHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContinuationHistory {
+    pub fn new() -> Self {
+        ContinuationHistory {
+            scores: vec![0; 2 * 6 * 64 * 6 * 64],
+        }
+    }
+
+    fn index(color: Color, prior: (Piece, Square), piece: Piece, to: Square) -> usize {
+        let (prior_piece, prior_to) = prior;
+        (((color.to_index() * 6 + prior_piece.to_index()) * 64 + prior_to.to_index()) * 6
+            + piece.to_index())
+            * 64
+            + to.to_index()
+    }
+
+    /// Score of `piece` going to `to`, played by `color` after `prior`
+    pub fn get(&self, color: Color, prior: (Piece, Square), piece: Piece, to: Square) -> i32 {
+        self.scores[Self::index(color, prior, piece, to)]
+    }
+
+    pub fn reward(
+        &mut self,
+        color: Color,
+        prior: (Piece, Square),
+        piece: Piece,
+        to: Square,
+        depth: i32,
+    ) {
+        bump(
+            &mut self.scores,
+            Self::index(color, prior, piece, to),
+            depth,
+        );
+    }
+
+    pub fn age(&mut self) {
+        age(&mut self.scores);
+    }
+
+    pub fn clear(&mut self) {
+        self.scores.fill(0);
+    }
+}
+
+/// All quiet move histories: the butterfly table, and continuation tables
+/// keyed by the move one ply back (the opponent's reply) and two plies back
+/// (our own previous move)
+#[derive(Default)]
+pub struct MoveHistory {
+    pub butterfly: HistoryTable,
+    pub continuation: [ContinuationHistory; 2],
+}
+
+impl MoveHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Combined history of the quiet move `mv` on `board`, with `priors` the
+    /// moves that led to it (most recent last). Between 0 and `HISTORY_MAX`.
+    pub fn quiet_score(&self, board: &Board, mv: ChessMove, priors: &[PriorMove]) -> i32 {
+        let color = board.side_to_move();
+        let piece = board.piece_on(mv.get_source()).unwrap_or(Piece::Pawn);
+        let mut total = self.butterfly.get(color, mv);
+        for (table, prior) in self.continuation.iter().zip(priors.iter().rev()) {
+            if let Some(prior) = prior {
+                total += table.get(color, *prior, piece, mv.get_dest());
+            }
+        }
+        total / 3
+    }
+
+    /// Credit the quiet move `mv` on `board` with a beta cutoff
+    pub fn reward(&mut self, board: &Board, mv: ChessMove, priors: &[PriorMove], depth: i32) {
+        let color = board.side_to_move();
+        let piece = board.piece_on(mv.get_source()).unwrap_or(Piece::Pawn);
+        self.butterfly.reward(color, mv, depth);
+        for (table, prior) in self.continuation.iter_mut().zip(priors.iter().rev()) {
+            if let Some(prior) = prior {
+                table.reward(color, *prior, piece, mv.get_dest(), depth);
+            }
+        }
+    }
+
+    pub fn age(&mut self) {
+        self.butterfly.age();
+        self.continuation
+            .iter_mut()
+            .for_each(ContinuationHistory::age);
+    }
+
+    pub fn clear(&mut self) {
+        self.butterfly.clear();
+        self.continuation
+            .iter_mut()
+            .for_each(ContinuationHistory::clear);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reward_and_age() {
+        let mut history = HistoryTable::new();
+        let mv = ChessMove::new(Square::G1, Square::F3, None);
+        history.reward(Color::White, mv, 4);
+        history.reward(Color::White, mv, 2);
+        assert_eq!(history.get(Color::White, mv), 20);
+        assert_eq!(history.get(Color::Black, mv), 0);
+
+        history.age();
+        assert_eq!(history.get(Color::White, mv), 10);
+
+        // Overflowing an entry ages the whole table
+        for _ in 0..1000 {
+            history.reward(Color::White, mv, 10);
+        }
+        assert!(history.get(Color::White, mv) <= HISTORY_MAX);
+
+        history.clear();
+        assert_eq!(history.get(Color::White, mv), 0);
+    }
+
+    #[test]
+    fn test_continuation_history() {
+        let board = Board::default();
+        let mut history = MoveHistory::new();
+        let nf3 = ChessMove::new(Square::G1, Square::F3, None);
+        let after_e5 = [
+            Some((Piece::Pawn, Square::E4)),
+            Some((Piece::Pawn, Square::E5)),
+        ];
+        let after_d5 = [
+            Some((Piece::Pawn, Square::E4)),
+            Some((Piece::Pawn, Square::D5)),
+        ];
+
+        history.reward(&board, nf3, &after_e5, 6);
+        let both = history.quiet_score(&board, nf3, &after_e5);
+        // Only the butterfly and two-ply entries apply after a different reply
+        let other_reply = history.quiet_score(&board, nf3, &after_d5);
+        assert_eq!(both, 36);
+        assert_eq!(other_reply, 24);
+        assert_eq!(history.quiet_score(&board, nf3, &[]), 12);
+        assert_eq!(history.quiet_score(&board, nf3, &[None, None]), 12);
+
+        history.age();
+        assert_eq!(history.quiet_score(&board, nf3, &after_e5), 18);
+        history.clear();
+        assert_eq!(history.quiet_score(&board, nf3, &after_e5), 0);
+    }
+}
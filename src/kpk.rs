@@ -0,0 +1,305 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use std::sync::OnceLock;
+
+use chess::{BitBoard, Board, Color, Piece, Square, ALL_SQUARES, EMPTY};
+
+use crate::evaluation::{king_distance, GameResult};
+
+/// Positions in the bitbase: side to move, both kings, and the pawn on one of
+/// the files a-d and ranks 2-7
+const SIZE: usize = 2 * 24 * 64 * 64;
+
+/// Outcomes while the bitbase is built, as bits so that the outcomes of a
+/// position's successors can be or-ed together. Impossible positions add
+/// nothing.
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+/// A king and pawn against king position, seen with the pawn White's (the
+/// strong side) and on files a-d
+#[derive(Clone, Copy)]
+struct Position {
+    white_to_move: bool,
+    strong_king: Square,
+    weak_king: Square,
+    pawn: Square,
+}
+
+impl Position {
+    fn index(self) -> usize {
+        let (file, rank) = (
+            self.pawn.get_file().to_index(),
+            self.pawn.get_rank().to_index(),
+        );
+        self.strong_king.to_index()
+            | self.weak_king.to_index() << 6
+            | usize::from(self.white_to_move) << 12
+            | file << 13
+            | (6 - rank) << 15
+    }
+
+    fn from_index(index: usize) -> Self {
+        let file = (index >> 13) & 3;
+        let rank = 6 - (index >> 15);
+        Position {
+            white_to_move: (index >> 12) & 1 == 1,
+            strong_king: ALL_SQUARES[index & 63],
+            weak_king: ALL_SQUARES[(index >> 6) & 63],
+            pawn: ALL_SQUARES[rank * 8 + file],
+        }
+    }
+
+    /// Outcome decided by the position alone, before looking at any move:
+    /// impossible setups, a pawn that promotes safely, stalemate and a pawn
+    /// the weak king can take
+    fn initial(self) -> u8 {
+        let Position {
+            white_to_move,
+            strong_king,
+            weak_king,
+            pawn,
+        } = self;
+        let strong_attacks = chess::get_king_moves(strong_king);
+        let pawn_attacks = chess::get_pawn_attacks(pawn, Color::White, !EMPTY);
+
+        if king_distance(strong_king, weak_king) <= 1
+            || strong_king == pawn
+            || weak_king == pawn
+            || (white_to_move && pawn_attacks & BitBoard::from_square(weak_king) != EMPTY)
+        {
+            return INVALID;
+        }
+
+        if white_to_move {
+            if pawn.get_rank().to_index() == 6 {
+                let promotion = ALL_SQUARES[pawn.to_index() + 8];
+                if strong_king != promotion
+                    && weak_king != promotion
+                    && (king_distance(weak_king, promotion) > 1
+                        || king_distance(strong_king, promotion) == 1)
+                {
+                    return WIN;
+                }
+            }
+            return UNKNOWN;
+        }
+
+        let weak_moves = chess::get_king_moves(weak_king);
+        if weak_moves & !(strong_attacks | pawn_attacks) == EMPTY {
+            return DRAW;
+        }
+        let pawn_bb = BitBoard::from_square(pawn);
+        if weak_moves & pawn_bb & !strong_attacks != EMPTY {
+            return DRAW;
+        }
+        UNKNOWN
+    }
+
+    /// Outcome from the outcomes of the positions one move on, still
+    /// `UNKNOWN` while any of them that could decide it is
+    fn classify(self, db: &[u8]) -> u8 {
+        let mut outcomes = INVALID;
+        if self.white_to_move {
+            for sq in chess::get_king_moves(self.strong_king) {
+                outcomes |= db[Position {
+                    white_to_move: false,
+                    strong_king: sq,
+                    ..self
+                }
+                .index()];
+            }
+            let rank = self.pawn.get_rank().to_index();
+            if rank < 6 {
+                let push = ALL_SQUARES[self.pawn.to_index() + 8];
+                outcomes |= db[Position {
+                    white_to_move: false,
+                    pawn: push,
+                    ..self
+                }
+                .index()];
+                if rank == 1 && push != self.strong_king && push != self.weak_king {
+                    outcomes |= db[Position {
+                        white_to_move: false,
+                        pawn: ALL_SQUARES[push.to_index() + 8],
+                        ..self
+                    }
+                    .index()];
+                }
+            }
+            if outcomes & WIN != 0 {
+                WIN
+            } else if outcomes & UNKNOWN != 0 {
+                UNKNOWN
+            } else {
+                DRAW
+            }
+        } else {
+            for sq in chess::get_king_moves(self.weak_king) {
+                outcomes |= db[Position {
+                    white_to_move: true,
+                    weak_king: sq,
+                    ..self
+                }
+                .index()];
+            }
+            if outcomes & DRAW != 0 {
+                DRAW
+            } else if outcomes & UNKNOWN != 0 {
+                UNKNOWN
+            } else {
+                WIN
+            }
+        }
+    }
+}
+
+/// Whether the strong side wins, one bit per position, found by retrograde
+/// analysis: positions are classified from their successors until no more
+/// can be, and those left unknown are draws
+struct Bitbase {
+    wins: Vec<u64>,
+}
+
+impl Bitbase {
+    fn build() -> Self {
+        let mut db: Vec<u8> = (0..SIZE)
+            .map(|index| Position::from_index(index).initial())
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 0..SIZE {
+                if db[index] == UNKNOWN {
+                    let outcome = Position::from_index(index).classify(&db);
+                    if outcome != UNKNOWN {
+                        db[index] = outcome;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        let mut wins = vec![0; SIZE / 64];
+        for (index, &outcome) in db.iter().enumerate() {
+            if outcome == WIN {
+                wins[index / 64] |= 1 << (index % 64);
+            }
+        }
+        Bitbase { wins }
+    }
+
+    fn is_win(&self, position: Position) -> bool {
+        let index = position.index();
+        self.wins[index / 64] & (1 << (index % 64)) != 0
+    }
+}
+
+fn bitbase() -> &'static Bitbase {
+    static BITBASE: OnceLock<Bitbase> = OnceLock::new();
+    BITBASE.get_or_init(Bitbase::build)
+}
+
+/// Build the bitbase now if it isn't yet, so that the first search to reach
+/// a king and pawn ending doesn't spend its time on it
+pub fn init() {
+    bitbase();
+}
+
+/// The result with best play of a king and pawn against king position, or
+/// `None` if `board` has any other material
+pub(crate) fn probe(board: &Board) -> Option<GameResult> {
+    let pawns = *board.pieces(Piece::Pawn);
+    if board.combined().popcnt() != 3 || pawns.popcnt() != 1 {
+        return None;
+    }
+    let pawn = pawns.to_square();
+    let strong = board.color_on(pawn)?;
+
+    // Flip the board so the pawn is White's, and mirror it onto files a-d
+    let mirror = pawn.get_file().to_index() >= 4;
+    let normalize = |sq: Square| {
+        let mut index = sq.to_index();
+        if strong == Color::Black {
+            index ^= 56;
+        }
+        if mirror {
+            index ^= 7;
+        }
+        ALL_SQUARES[index]
+    };
+    let position = Position {
+        white_to_move: board.side_to_move() == strong,
+        strong_king: normalize(board.king_square(strong)),
+        weak_king: normalize(board.king_square(!strong)),
+        pawn: normalize(pawn),
+    };
+
+    Some(if !bitbase().is_win(position) {
+        GameResult::Draw
+    } else if strong == Color::White {
+        GameResult::WhiteWins
+    } else {
+        GameResult::BlackWins
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn result(fen: &str) -> Option<GameResult> {
+        probe(&Board::from_str(fen).unwrap())
+    }
+
+    #[test]
+    fn test_index_roundtrip() {
+        for index in [0, 4096, SIZE / 2 + 777, SIZE - 1] {
+            assert_eq!(Position::from_index(index).index(), index);
+        }
+    }
+
+    #[test]
+    fn test_kpk_results() {
+        // The king on the sixth rank in front of its pawn wins, whoever moves
+        assert_eq!(
+            result("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"),
+            Some(GameResult::WhiteWins)
+        );
+        assert_eq!(
+            result("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+            Some(GameResult::WhiteWins)
+        );
+        // The same for Black
+        assert_eq!(
+            result("8/8/8/8/3p4/3k4/8/3K4 b - - 0 1"),
+            Some(GameResult::BlackWins)
+        );
+        // Stalemate
+        assert_eq!(
+            result("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"),
+            Some(GameResult::Draw)
+        );
+        // A rook pawn against a king in its corner
+        assert_eq!(
+            result("k7/8/8/8/8/8/P7/7K w - - 0 1"),
+            Some(GameResult::Draw)
+        );
+        assert_eq!(
+            result("7k/8/8/8/8/8/7P/K7 w - - 0 1"),
+            Some(GameResult::Draw)
+        );
+        // The pawn runs away from a king outside its square
+        assert_eq!(
+            result("7k/8/8/8/P7/8/8/K7 w - - 0 1"),
+            Some(GameResult::WhiteWins)
+        );
+        // Not king and pawn against king
+        assert_eq!(result("4k3/8/4K3/4P3/8/8/8/7R w - - 0 1"), None);
+    }
+}
@@ -0,0 +1,25 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+pub mod adjudication;
+pub mod bench;
+pub mod book;
+pub mod cuckoo;
+pub mod debug_log;
+pub mod engine;
+pub mod evaluation;
+pub mod external;
+pub mod game;
+pub mod history;
+pub mod kpk;
+pub mod movepick;
+pub mod moves;
+pub mod nnue;
+pub mod perft;
+pub mod timeman;
+pub mod tournament;
+pub mod tt;
+pub mod tune;
+pub mod uci;
+pub mod variant;
@@ -0,0 +1,427 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{between, BitBoard, Board, ChessMove, Color, MoveGen, Piece, Rank, EMPTY};
+
+use crate::engine::{
+    captured_piece, en_passant_target, is_capture, loses_exchange, piece_order_value,
+    score_evasion, score_move,
+};
+use crate::history::{MoveHistory, PriorMove, HISTORY_MAX};
+use crate::variant::Variant;
+
+/// Ordering score of the transposition table move
+const TT_MOVE_SCORE: i32 = 100_000;
+
+/// Ordering score of a killer move, between captures and quiet moves
+const KILLER_SCORE: i32 = 0;
+
+/// Added to the history score of a quiet move that moves the piece a
+/// threat attacks out of the way, or blocks the threat's path
+const THREAT_EVASION_SCORE: i32 = HISTORY_MAX;
+
+/// Added to the MVV-LVA score of a capture that loses material, so it sorts
+/// below every quiet move
+const LOSING_CAPTURE_SCORE: i32 = -100_000;
+
+/// Where the moves handed out by a `MovePicker` come from, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stage {
+    TtMove,
+    /// Generating and scoring the captures, which hands out no move
+    GenerateCaptures,
+    /// Captures and promotions that do not lose material
+    GoodCaptures,
+    /// Quiet moves that caused a beta cutoff at the same ply elsewhere
+    Killers,
+    /// Generating and scoring the quiet moves, which hands out no move
+    GenerateQuiets,
+    /// Quiet moves by their history
+    Quiets,
+    LosingCaptures,
+    /// Every legal move scored up front: check evasions, and variants whose
+    /// rules restrict the legal moves
+    AllMoves,
+    Done,
+}
+
+/// Hands out the legal moves of a position in search order, one stage at a
+/// time: the TT move, captures that do not lose material, killers, quiet
+/// moves by history and finally losing captures. Each stage is generated and
+/// scored only once the previous one runs out, so a node that cuts off early
+/// never pays for ordering its quiet moves.
+pub(crate) struct MovePicker {
+    stage: Stage,
+    /// Stage of the move handed out last
+    picked_from: Stage,
+    tt_move: Option<ChessMove>,
+    killers: [Option<ChessMove>; 2],
+    /// Opponent capture that refuted passing here, if any
+    threat: Option<ChessMove>,
+    movegen: MoveGen,
+    /// Moves of the current stage with their ordering scores
+    moves: Vec<(ChessMove, i32)>,
+    losing_captures: Vec<(ChessMove, i32)>,
+    /// Moves taken out of the generator early, while looking for the TT move
+    /// or a killer, to be handed out in their own stage
+    set_aside: Vec<ChessMove>,
+}
+
+impl MovePicker {
+    pub fn new(
+        board: &Board,
+        variant: Variant,
+        tt_move: Option<ChessMove>,
+        killers: [Option<ChessMove>; 2],
+        threat: Option<ChessMove>,
+        history: &MoveHistory,
+        priors: &[PriorMove],
+    ) -> Self {
+        let mut picker = MovePicker {
+            stage: Stage::TtMove,
+            picked_from: Stage::TtMove,
+            tt_move,
+            killers,
+            threat,
+            movegen: MoveGen::new_legal(board),
+            moves: Vec::new(),
+            losing_captures: Vec::new(),
+            set_aside: Vec::new(),
+        };
+        let in_check = *board.checkers() != EMPTY;
+        if in_check || variant != Variant::Standard {
+            picker.stage = Stage::AllMoves;
+            let moves = match variant {
+                Variant::Standard => (&mut picker.movegen).collect(),
+                _ => variant.legal_moves(board),
+            };
+            picker.moves = moves
+                .into_iter()
+                .map(|mv| {
+                    let score = if in_check {
+                        score_evasion(board, mv, tt_move)
+                    } else {
+                        score_move(board, mv, tt_move, history, priors)
+                    };
+                    (mv, score)
+                })
+                .collect();
+        }
+        picker
+    }
+
+    /// Stage the move handed out last came from
+    pub fn last_stage(&self) -> Stage {
+        self.picked_from
+    }
+
+    /// Take `mv` out of the moves still to be handed out, reporting whether
+    /// it was there, i.e. legal and not handed out yet. The generator can't
+    /// check a single move, so every move landing on the same square is taken
+    /// out of it and set aside.
+    fn take_if_legal(&mut self, mv: ChessMove) -> bool {
+        if let Some(index) = self.set_aside.iter().position(|&m| m == mv) {
+            self.set_aside.swap_remove(index);
+            return true;
+        }
+        self.movegen
+            .set_iterator_mask(BitBoard::from_square(mv.get_dest()));
+        self.set_aside.extend(&mut self.movegen);
+        match self.set_aside.iter().position(|&m| m == mv) {
+            Some(index) => {
+                self.set_aside.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Next move to search with its ordering score, None once all are out
+    pub fn next(
+        &mut self,
+        board: &Board,
+        history: &MoveHistory,
+        priors: &[PriorMove],
+    ) -> Option<(ChessMove, i32)> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if let Some(mv) = self.tt_move {
+                        if self.take_if_legal(mv) {
+                            self.picked_from = Stage::TtMove;
+                            return Some((mv, TT_MOVE_SCORE));
+                        }
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    self.stage = Stage::GoodCaptures;
+                    self.generate_captures(board);
+                }
+                Stage::GoodCaptures => {
+                    if let Some(picked) = pick_best(&mut self.moves, board.side_to_move()) {
+                        self.picked_from = Stage::GoodCaptures;
+                        return Some(picked);
+                    }
+                    self.stage = Stage::Killers;
+                }
+                Stage::Killers => {
+                    let Some(index) = self.killers.iter().position(Option::is_some) else {
+                        self.stage = Stage::GenerateQuiets;
+                        continue;
+                    };
+                    let mv = self.killers[index].take().expect("killer is set");
+                    // Captures were handed out already, and a killer that
+                    // is not legal here is not in the generator
+                    let quiet = !is_capture(board, mv) && mv.get_promotion().is_none();
+                    if quiet && self.take_if_legal(mv) {
+                        self.picked_from = Stage::Killers;
+                        return Some((mv, KILLER_SCORE));
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    self.stage = Stage::Quiets;
+                    self.generate_quiets(board, history, priors);
+                }
+                Stage::Quiets => {
+                    if let Some(picked) = pick_best(&mut self.moves, board.side_to_move()) {
+                        self.picked_from = Stage::Quiets;
+                        return Some(picked);
+                    }
+                    self.stage = Stage::LosingCaptures;
+                }
+                Stage::LosingCaptures => {
+                    if let Some(picked) = pick_best(&mut self.losing_captures, board.side_to_move())
+                    {
+                        self.picked_from = Stage::LosingCaptures;
+                        return Some(picked);
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::AllMoves => {
+                    let picked = pick_best(&mut self.moves, board.side_to_move());
+                    if picked.is_none() {
+                        self.stage = Stage::Done;
+                    }
+                    self.picked_from = Stage::AllMoves;
+                    return picked;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /// Captures and promotions, split into those that hold their material and
+    /// those that lose it. Other moves to the last rank or the en passant
+    /// square stay set aside.
+    fn generate_captures(&mut self, board: &Board) {
+        let last_rank = match board.side_to_move() {
+            Color::White => Rank::Eighth,
+            Color::Black => Rank::First,
+        };
+        let mut mask = *board.color_combined(!board.side_to_move()) | chess::get_rank(last_rank);
+        if let Some(sq) = en_passant_target(board) {
+            mask |= BitBoard::from_square(sq);
+        }
+        self.movegen.set_iterator_mask(mask);
+        let moves: Vec<ChessMove> = self.set_aside.drain(..).chain(&mut self.movegen).collect();
+        for mv in moves {
+            if is_capture(board, mv) {
+                let score = mvv_lva(board, mv);
+                if loses_exchange(board, mv) {
+                    self.losing_captures
+                        .push((mv, score + LOSING_CAPTURE_SCORE));
+                } else {
+                    self.moves.push((mv, score));
+                }
+            } else if mv.get_promotion().is_some() {
+                self.moves.push((mv, mvv_lva(board, mv)));
+            } else {
+                self.set_aside.push(mv);
+            }
+        }
+    }
+
+    /// The remaining moves, all quiet, scored by their history. Moves that
+    /// meet the threat come before the rest.
+    fn generate_quiets(&mut self, board: &Board, history: &MoveHistory, priors: &[PriorMove]) {
+        self.movegen.set_iterator_mask(!EMPTY);
+        let threat = self.threat;
+        self.moves = self
+            .set_aside
+            .drain(..)
+            .chain(&mut self.movegen)
+            .map(|mv| {
+                let mut score = history.quiet_score(board, mv, priors) - HISTORY_MAX;
+                if threat.is_some_and(|threat| meets_threat(mv, threat)) {
+                    score += THREAT_EVASION_SCORE;
+                }
+                (mv, score)
+            })
+            .collect();
+    }
+}
+
+/// Whether `mv` moves the piece `threat` attacks out of the way, or puts a
+/// piece between the attacker and its target
+fn meets_threat(mv: ChessMove, threat: ChessMove) -> bool {
+    mv.get_source() == threat.get_dest()
+        || between(threat.get_source(), threat.get_dest()) & BitBoard::from_square(mv.get_dest())
+            != EMPTY
+}
+
+/// Ordering score of a capture or promotion: the most valuable victim first,
+/// taken by the least valuable attacker. The king counts as the cheapest
+/// attacker, since a legal king capture cannot be answered.
+fn mvv_lva(board: &Board, mv: ChessMove) -> i32 {
+    let victim = captured_piece(board, mv).map_or(0, piece_order_value);
+    let attacker = match board.piece_on(mv.get_source()) {
+        Some(Piece::King) | None => 0,
+        Some(piece) => piece_order_value(piece),
+    };
+    let promotion = mv
+        .get_promotion()
+        .map_or(0, |piece| 9000 + piece_order_value(piece));
+    victim * 10 - attacker + promotion
+}
+
+/// Remove and return the highest scoring move of `color`. Equal scores are
+/// broken by the squares as seen from `color`'s side, not by the order the
+/// moves were generated in, so that mirrored positions are searched alike.
+fn pick_best(moves: &mut Vec<(ChessMove, i32)>, color: Color) -> Option<(ChessMove, i32)> {
+    let flip = if color == Color::White { 0 } else { 56 };
+    let tiebreak = |mv: ChessMove| {
+        std::cmp::Reverse((
+            mv.get_source().to_index() ^ flip,
+            mv.get_dest().to_index() ^ flip,
+            mv.get_promotion().map(|piece| piece.to_index()),
+        ))
+    };
+    let best = moves
+        .iter()
+        .enumerate()
+        .max_by_key(|&(_, &(mv, score))| (score, tiebreak(mv)))?
+        .0;
+    Some(moves.swap_remove(best))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::parse_uci_move;
+    use std::str::FromStr;
+
+    fn all_moves(
+        board: &Board,
+        tt_move: Option<ChessMove>,
+        killers: [Option<ChessMove>; 2],
+    ) -> Vec<(ChessMove, Stage)> {
+        let history = MoveHistory::new();
+        let mut picker = MovePicker::new(
+            board,
+            Variant::Standard,
+            tt_move,
+            killers,
+            None,
+            &history,
+            &[],
+        );
+        let mut moves = Vec::new();
+        while let Some((mv, _)) = picker.next(board, &history, &[]) {
+            moves.push((mv, picker.last_stage()));
+        }
+        moves
+    }
+
+    #[test]
+    fn test_stages_cover_every_legal_move_once() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            let mut legal: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+            let tt_move = legal.last().copied();
+            // A legal quiet killer, and one that is not legal here
+            let killers = [
+                legal.iter().copied().find(|&mv| !is_capture(&board, mv)),
+                Some(ChessMove::new(chess::Square::A1, chess::Square::H8, None)),
+            ];
+
+            let picked = all_moves(&board, tt_move, killers);
+            assert_eq!(picked[0], (tt_move.unwrap(), Stage::TtMove), "{}", fen);
+            let mut picked: Vec<ChessMove> = picked.into_iter().map(|(mv, _)| mv).collect();
+            picked.sort_by_key(|mv| mv.to_string());
+            legal.sort_by_key(|mv| mv.to_string());
+            assert_eq!(picked, legal, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_stage_order() {
+        // exd5 trades pawns, Qxd5 drops the queen to the e6 pawn, and Nb5
+        // is a killer
+        let board =
+            Board::from_str("rnbqkb1r/pppp1ppp/4pn2/3p4/4P3/2NQ1N2/PPPP1PPP/R1B1KB1R w KQkq - 0 1")
+                .unwrap();
+        let killer = parse_uci_move(&board, "c3b5").unwrap();
+        let picked = all_moves(&board, None, [Some(killer), None]);
+        let stages: Vec<Stage> = picked.iter().map(|&(_, stage)| stage).collect();
+
+        // Stages come out in order
+        let mut sorted = stages.clone();
+        sorted.sort_by_key(|&stage| stage as u8);
+        assert_eq!(stages, sorted);
+
+        let stage_of = |uci: &str| {
+            let mv = parse_uci_move(&board, uci).unwrap();
+            picked.iter().find(|&&(m, _)| m == mv).unwrap().1
+        };
+        assert_eq!(stage_of("e4d5"), Stage::GoodCaptures);
+        assert_eq!(stage_of("d3d5"), Stage::LosingCaptures);
+        assert_eq!(stage_of("c3b5"), Stage::Killers);
+        assert_eq!(stage_of("a2a3"), Stage::Quiets);
+    }
+
+    #[test]
+    fn test_quiets_meeting_the_threat_come_first() {
+        // Rxc3 is the threat, which only moving the knight away meets
+        let board = Board::from_str("4k3/8/8/8/8/2N4r/P7/4K3 w - - 0 1").unwrap();
+        let threat = ChessMove::new(chess::Square::H3, chess::Square::C3, None);
+        let history = MoveHistory::new();
+        let mut picker = MovePicker::new(
+            &board,
+            Variant::Standard,
+            None,
+            [None; 2],
+            Some(threat),
+            &history,
+            &[],
+        );
+        let mut quiets = Vec::new();
+        while let Some((mv, _)) = picker.next(&board, &history, &[]) {
+            quiets.push(mv);
+        }
+        let evasions = quiets
+            .iter()
+            .take_while(|&&mv| meets_threat(mv, threat))
+            .count();
+        // All seven knight moves come first
+        assert_eq!(evasions, 7);
+        assert!(quiets[..evasions]
+            .iter()
+            .all(|mv| mv.get_source() == chess::Square::C3));
+    }
+
+    #[test]
+    fn test_evasions_are_scored_up_front() {
+        let board = Board::from_str("4k3/8/8/8/8/8/3q4/4K3 w - - 0 1").unwrap();
+        let picked = all_moves(&board, None, [None, None]);
+        assert_eq!(picked[0].0.to_string(), "e1d2");
+        assert!(picked.iter().all(|&(_, stage)| stage == Stage::AllMoves));
+        assert_eq!(picked.len(), MoveGen::new_legal(&board).len());
+    }
+}
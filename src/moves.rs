@@ -0,0 +1,230 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{Board, ChessMove, File, MoveGen, Piece, Rank, Square};
+use std::fmt;
+
+/// Why a move string could not be turned into a legal move
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveParseError {
+    /// The text is not a move in the expected notation
+    Malformed(String),
+    /// The text is well-formed but no legal move in the position matches it
+    Illegal(String),
+}
+
+impl fmt::Display for MoveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveParseError::Malformed(text) => write!(f, "malformed move '{}'", text),
+            MoveParseError::Illegal(text) => write!(f, "illegal move '{}'", text),
+        }
+    }
+}
+
+impl std::error::Error for MoveParseError {}
+
+/// Map a file ('a'..'h') or rank ('1'..'8') character to its index
+fn coordinate_index(c: char, first: char) -> Option<usize> {
+    let idx = (c as u32).checked_sub(first as u32)? as usize;
+    (idx < 8).then_some(idx)
+}
+
+/// Parse a UCI format move string (e.g., "e2e4", "e7e8q") into a legal move.
+///
+/// The promotion letter is case-insensitive. If it is missing on a pawn move
+/// to the last rank, the move is taken to be a queen promotion.
+pub fn parse_uci_move(board: &Board, move_str: &str) -> Result<ChessMove, MoveParseError> {
+    let move_str = move_str.trim();
+    let malformed = || MoveParseError::Malformed(move_str.to_string());
+
+    let chars: Vec<char> = move_str.chars().collect();
+    if chars.len() != 4 && chars.len() != 5 {
+        return Err(malformed());
+    }
+
+    let square = |file: char, rank: char| -> Option<Square> {
+        Some(Square::make_square(
+            Rank::from_index(coordinate_index(rank, '1')?),
+            File::from_index(coordinate_index(file, 'a')?),
+        ))
+    };
+    let from = square(chars[0], chars[1]).ok_or_else(malformed)?;
+    let to = square(chars[2], chars[3]).ok_or_else(malformed)?;
+
+    let promotion = match chars.get(4).map(|c| c.to_ascii_lowercase()) {
+        None => None,
+        Some('q') => Some(Piece::Queen),
+        Some('r') => Some(Piece::Rook),
+        Some('b') => Some(Piece::Bishop),
+        Some('n') => Some(Piece::Knight),
+        Some(_) => return Err(malformed()),
+    };
+
+    // Chess960 GUIs write castling as the king capturing its own rook
+    let to = king_takes_rook_destination(board, from, to).unwrap_or(to);
+
+    let candidates: Vec<ChessMove> = MoveGen::new_legal(board)
+        .filter(|mv| mv.get_source() == from && mv.get_dest() == to)
+        .collect();
+
+    candidates
+        .iter()
+        .find(|mv| mv.get_promotion() == promotion)
+        .or_else(|| {
+            // Promotion letter omitted: default to a queen
+            promotion
+                .is_none()
+                .then(|| {
+                    candidates
+                        .iter()
+                        .find(|mv| mv.get_promotion() == Some(Piece::Queen))
+                })
+                .flatten()
+        })
+        .copied()
+        .ok_or_else(|| MoveParseError::Illegal(move_str.to_string()))
+}
+
+/// Where the king really goes when `from`-`to` is a king capturing its own
+/// rook on the back rank, i.e. a castling move in Chess960 notation. Only
+/// castling the side still has the right to counts, with the king on e1/e8
+/// and the rook in its corner; any other king-takes-rook move is illegal.
+fn king_takes_rook_destination(board: &Board, from: Square, to: Square) -> Option<Square> {
+    let color = board.color_on(from)?;
+    let is_own_rook = board.piece_on(to) == Some(Piece::Rook) && board.color_on(to) == Some(color);
+    let back_rank = color.to_my_backrank();
+    if board.piece_on(from) != Some(Piece::King)
+        || !is_own_rook
+        || from != Square::make_square(back_rank, File::E)
+        || to.get_rank() != back_rank
+    {
+        return None;
+    }
+    let rights = board.castle_rights(color);
+    match to.get_file() {
+        File::H if rights.has_kingside() => Some(Square::make_square(back_rank, File::G)),
+        File::A if rights.has_queenside() => Some(Square::make_square(back_rank, File::C)),
+        _ => None,
+    }
+}
+
+/// Write a move in UCI notation. With `chess960` set, castling is written as
+/// the king capturing its own rook (e1h1 rather than e1g1), as the
+/// `UCI_Chess960` option requires.
+pub fn format_uci_move(board: &Board, mv: ChessMove, chess960: bool) -> String {
+    let (from, to) = (mv.get_source(), mv.get_dest());
+    let is_castling = board.piece_on(from) == Some(Piece::King)
+        && from
+            .get_file()
+            .to_index()
+            .abs_diff(to.get_file().to_index())
+            == 2;
+    if !chess960 || !is_castling {
+        return mv.to_string();
+    }
+    let rook_file = if to.get_file() == File::G {
+        File::H
+    } else {
+        File::A
+    };
+    format!(
+        "{}{}",
+        from,
+        Square::make_square(from.get_rank(), rook_file)
+    )
+}
+
+/// Parse a move in Standard Algebraic Notation (e.g., "Nf3", "exd5", "O-O", "e8=Q+")
+pub fn parse_san_move(board: &Board, move_str: &str) -> Result<ChessMove, MoveParseError> {
+    let move_str = move_str.trim();
+    let mv = ChessMove::from_san(board, move_str)
+        .map_err(|_| MoveParseError::Malformed(move_str.to_string()))?;
+    if board.legal(mv) {
+        Ok(mv)
+    } else {
+        Err(MoveParseError::Illegal(move_str.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse_uci_move() {
+        let board = Board::default();
+        let mv = parse_uci_move(&board, "e2e4").unwrap();
+        assert_eq!(format!("{}", mv), "e2e4");
+        assert_eq!(
+            parse_uci_move(&board, " g1f3 ").unwrap().to_string(),
+            "g1f3"
+        );
+    }
+
+    #[test]
+    fn test_parse_uci_promotion() {
+        // Position where a pawn can promote
+        let board = Board::from_str("8/P7/8/8/8/8/8/4K2k w - - 0 1").unwrap();
+        let queen = parse_uci_move(&board, "a7a8q").unwrap();
+        assert_eq!(queen.get_promotion(), Some(Piece::Queen));
+        assert_eq!(
+            parse_uci_move(&board, "a7a8N").unwrap().get_promotion(),
+            Some(Piece::Knight)
+        );
+        assert_eq!(parse_uci_move(&board, "a7a8").unwrap(), queen);
+    }
+
+    #[test]
+    fn test_parse_uci_errors() {
+        let board = Board::default();
+        for text in ["", "e2", "e2e4e5", "i2i4", "e0e4", "é2é4", "e2e4x"] {
+            assert_eq!(
+                parse_uci_move(&board, text),
+                Err(MoveParseError::Malformed(text.to_string()))
+            );
+        }
+        assert_eq!(
+            parse_uci_move(&board, "e2e5"),
+            Err(MoveParseError::Illegal("e2e5".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_san_move() {
+        let board = Board::default();
+        assert_eq!(parse_san_move(&board, "Nf3").unwrap().to_string(), "g1f3");
+        assert!(parse_san_move(&board, "Ke2").is_err());
+        assert!(parse_san_move(&board, "xyz").is_err());
+    }
+
+    #[test]
+    fn test_chess960_castling_notation() {
+        let board = Board::from_str("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1").unwrap();
+        let short = parse_uci_move(&board, "e1g1").unwrap();
+        assert_eq!(parse_uci_move(&board, "e1h1").unwrap(), short);
+        assert_eq!(parse_uci_move(&board, "e1a1").unwrap().to_string(), "e1c1");
+        assert_eq!(format_uci_move(&board, short, true), "e1h1");
+        assert_eq!(format_uci_move(&board, short, false), "e1g1");
+
+        let long = parse_uci_move(&board, "e1c1").unwrap();
+        assert_eq!(format_uci_move(&board, long, true), "e1a1");
+        let quiet = parse_uci_move(&board, "e1f1").unwrap();
+        assert_eq!(format_uci_move(&board, quiet, true), "e1f1");
+
+        // Only a king that may still castle takes its own rook
+        let board = Board::from_str("4k3/8/8/8/8/8/8/5K1R w - - 0 1").unwrap();
+        assert_eq!(
+            parse_uci_move(&board, "f1h1"),
+            Err(MoveParseError::Illegal("f1h1".to_string()))
+        );
+        let board = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w Qkq - 0 1").unwrap();
+        assert_eq!(
+            parse_uci_move(&board, "e1h1"),
+            Err(MoveParseError::Illegal("e1h1".to_string()))
+        );
+        assert_eq!(parse_uci_move(&board, "e1a1").unwrap().to_string(), "e1c1");
+    }
+}
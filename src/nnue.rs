@@ -0,0 +1,336 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{Board, ChessMove, Color, File, Piece, Square};
+
+/// Neurons of the hidden layer, per perspective
+pub const HIDDEN: usize = 256;
+
+/// Buckets a perspective's inputs are split into by where its king stands:
+/// on the first two ranks or further up, on the queenside or the kingside
+const KING_BUCKETS: usize = 4;
+
+/// Inputs per perspective: one per piece type, color and square, in each
+/// king bucket
+const INPUTS: usize = KING_BUCKETS * 2 * 6 * 64;
+
+/// Quantization of the hidden layer (the clipping ceiling of its
+/// activations) and of the output weights
+const QA: i32 = 255;
+const QB: i32 = 64;
+
+/// Centipawns per unit of network output
+const EVAL_SCALE: i32 = 400;
+
+/// Number of `i16` values in a network file
+const FILE_VALUES: usize = INPUTS * HIDDEN + HIDDEN + 2 * HIDDEN + 1;
+
+/// Size of a network file in bytes, before any padding
+pub const FILE_BYTES: usize = FILE_VALUES * 2;
+
+/// Files may be padded with zeros up to a multiple of this many bytes
+const FILE_ALIGNMENT: usize = 64;
+
+/// An efficiently updatable neural network: king-bucketed piece-square
+/// inputs seen from both sides, a hidden layer per side clipped to
+/// `[0, QA]`, and one output
+pub struct Network {
+    /// Hidden layer weights, one row per input
+    feature_weights: Vec<[i16; HIDDEN]>,
+    feature_bias: [i16; HIDDEN],
+    /// Output weights of the side to move's hidden layer, then the other's
+    output_weights: [[i16; HIDDEN]; 2],
+    /// Output bias, quantized by `QA * QB`
+    output_bias: i16,
+}
+
+impl Network {
+    /// Read a network file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Parse a network: little-endian `i16` values, the hidden layer weights
+    /// input by input, the hidden layer biases, the output weights and the
+    /// output bias
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < FILE_BYTES || bytes.len() >= FILE_BYTES + FILE_ALIGNMENT {
+            return Err(format!(
+                "network file is {} bytes, expected {}",
+                bytes.len(),
+                FILE_BYTES
+            ));
+        }
+        let mut values = bytes[..FILE_BYTES]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]));
+        let mut row = || {
+            let mut row = [0; HIDDEN];
+            row.iter_mut()
+                .zip(&mut values)
+                .for_each(|(v, value)| *v = value);
+            row
+        };
+
+        let feature_weights = (0..INPUTS).map(|_| row()).collect();
+        let feature_bias = row();
+        let output_weights = [row(), row()];
+        let output_bias = values.next().expect("size checked above");
+        Ok(Network {
+            feature_weights,
+            feature_bias,
+            output_weights,
+            output_bias,
+        })
+    }
+
+    /// Evaluation of `board` in centipawns from White's point of view, from
+    /// its hidden layer values in `accumulator`
+    pub fn evaluate(&self, board: &Board, accumulator: &Accumulator) -> i32 {
+        let us = board.side_to_move();
+        let hidden = [
+            &accumulator.values[us.to_index()],
+            &accumulator.values[(!us).to_index()],
+        ];
+        let mut sum = 0i64;
+        for (values, weights) in hidden.into_iter().zip(&self.output_weights) {
+            for (&v, &w) in values.iter().zip(weights) {
+                sum += (i32::from(v).clamp(0, QA) * i32::from(w)) as i64;
+            }
+        }
+        let eval = ((sum + i64::from(self.output_bias)) * i64::from(EVAL_SCALE)
+            / i64::from(QA * QB)) as i32;
+        if us == Color::White {
+            eval
+        } else {
+            -eval
+        }
+    }
+}
+
+/// Square as `perspective` sees it: Black's inputs are flipped vertically,
+/// so both sides see their own pieces from the bottom of the board
+fn orient(perspective: Color, sq: Square) -> usize {
+    match perspective {
+        Color::White => sq.to_index(),
+        Color::Black => sq.to_index() ^ 56,
+    }
+}
+
+/// Bucket of `perspective`'s inputs with its king on `king`
+fn king_bucket(perspective: Color, king: Square) -> usize {
+    let sq = orient(perspective, king);
+    usize::from(sq / 8 >= 2) * 2 + usize::from(sq % 8 >= 4)
+}
+
+/// Input of `perspective` for a `color` `piece` on `sq`, with its king in
+/// `bucket`
+fn input(perspective: Color, bucket: usize, piece: Piece, color: Color, sq: Square) -> usize {
+    bucket * 768
+        + usize::from(color != perspective) * 384
+        + piece.to_index() * 64
+        + orient(perspective, sq)
+}
+
+/// Hidden layer values of both perspectives, indexed by `Color::to_index`,
+/// kept up to date move by move during the search as `evaluation::Accumulator`
+/// is: a move changes a few inputs, so only their weights are added or
+/// taken away. A king moving into another bucket changes all its side's
+/// inputs, and that side's values are recomputed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Accumulator {
+    values: [[i16; HIDDEN]; 2],
+}
+
+impl Accumulator {
+    /// Hidden layer values for `board`, computed from scratch
+    pub fn new(network: &Network, board: &Board) -> Self {
+        let mut accumulator = Accumulator {
+            values: [[0; HIDDEN]; 2],
+        };
+        for perspective in [Color::White, Color::Black] {
+            accumulator.refresh(network, board, perspective);
+        }
+        accumulator
+    }
+
+    fn refresh(&mut self, network: &Network, board: &Board, perspective: Color) {
+        let bucket = king_bucket(perspective, board.king_square(perspective));
+        let values = &mut self.values[perspective.to_index()];
+        *values = network.feature_bias;
+        for sq in *board.combined() {
+            if let (Some(piece), Some(color)) = (board.piece_on(sq), board.color_on(sq)) {
+                add(
+                    values,
+                    network,
+                    input(perspective, bucket, piece, color, sq),
+                );
+            }
+        }
+    }
+
+    /// Values after `mv` is made on `board`, which they are the values of
+    pub fn after_move(&self, network: &Network, board: &Board, mv: ChessMove) -> Self {
+        let mut next = *self;
+        let (source, dest) = (mv.get_source(), mv.get_dest());
+        let us = board.side_to_move();
+        let Some(piece) = board.piece_on(source) else {
+            return next;
+        };
+        let king_files = dest.get_file().to_index() as i32 - source.get_file().to_index() as i32;
+        let castling = piece == Piece::King && king_files.abs() == 2;
+
+        for perspective in [Color::White, Color::Black] {
+            let king = board.king_square(perspective);
+            let bucket = king_bucket(perspective, king);
+            if piece == Piece::King && perspective == us && king_bucket(us, dest) != bucket {
+                next.refresh(network, &board.make_move_new(mv), perspective);
+                continue;
+            }
+
+            let values = &mut next.values[perspective.to_index()];
+            let feature = |piece, color, sq| input(perspective, bucket, piece, color, sq);
+            sub(values, network, feature(piece, us, source));
+            if let Some(captured) = board.piece_on(dest) {
+                sub(values, network, feature(captured, !us, dest));
+            } else if piece == Piece::Pawn && source.get_file() != dest.get_file() {
+                // En passant: the captured pawn stands beside the source square
+                let captured = Square::make_square(source.get_rank(), dest.get_file());
+                sub(values, network, feature(Piece::Pawn, !us, captured));
+            }
+            add(
+                values,
+                network,
+                feature(mv.get_promotion().unwrap_or(piece), us, dest),
+            );
+
+            // Castling is a two-square king move, which brings the rook along
+            if castling {
+                let (rook_from, rook_to) = if king_files > 0 {
+                    (File::H, File::F)
+                } else {
+                    (File::A, File::D)
+                };
+                let rank = source.get_rank();
+                sub(
+                    values,
+                    network,
+                    feature(Piece::Rook, us, Square::make_square(rank, rook_from)),
+                );
+                add(
+                    values,
+                    network,
+                    feature(Piece::Rook, us, Square::make_square(rank, rook_to)),
+                );
+            }
+        }
+        next
+    }
+}
+
+fn add(values: &mut [i16; HIDDEN], network: &Network, input: usize) {
+    for (v, &w) in values.iter_mut().zip(&network.feature_weights[input]) {
+        *v = v.wrapping_add(w);
+    }
+}
+
+fn sub(values: &mut [i16; HIDDEN], network: &Network, input: usize) {
+    for (v, &w) in values.iter_mut().zip(&network.feature_weights[input]) {
+        *v = v.wrapping_sub(w);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::str::FromStr;
+
+    /// A network file of small random values
+    fn random_network_bytes(seed: u64) -> Vec<u8> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..FILE_VALUES)
+            .flat_map(|_| rng.gen_range(-64i16..=64).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_file_size_is_checked() {
+        let mut bytes = random_network_bytes(1);
+        assert!(Network::from_bytes(&bytes).is_ok());
+        // Zero padding up to the alignment is accepted
+        bytes.resize(bytes.len() + FILE_ALIGNMENT - 2, 0);
+        assert!(Network::from_bytes(&bytes).is_ok());
+        bytes.resize(bytes.len() + 2, 0);
+        assert!(Network::from_bytes(&bytes).is_err());
+        assert!(Network::from_bytes(&bytes[..100]).is_err());
+        assert!(Network::load("/nonexistent/net.nnue").is_err());
+    }
+
+    #[test]
+    fn test_king_buckets() {
+        assert_eq!(king_bucket(Color::White, Square::E1), 1);
+        assert_eq!(king_bucket(Color::White, Square::B2), 0);
+        assert_eq!(king_bucket(Color::White, Square::G5), 3);
+        // Black's buckets are seen from Black's side of the board
+        assert_eq!(king_bucket(Color::Black, Square::E8), 1);
+        assert_eq!(king_bucket(Color::Black, Square::C3), 2);
+    }
+
+    #[test]
+    fn test_incremental_update_matches_refresh() {
+        let network = Network::from_bytes(&random_network_bytes(2)).unwrap();
+        // Quiet moves, captures, en passant, castling on both wings,
+        // promotion, and king moves within and across buckets
+        for (fen, moves) in [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "e2e4 d7d5 e4d5 d8d5 b1c3",
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                "e5f6",
+            ),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1 e8c8"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1c1 e8g8"),
+            ("1n5k/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q h8g7"),
+            (
+                "8/8/8/8/8/8/8/K6k w - - 0 1",
+                "a1b1 h1g2 b1c2 g2f3 c2d3 f3f4",
+            ),
+        ] {
+            let mut board = Board::from_str(fen).unwrap();
+            let mut accumulator = Accumulator::new(&network, &board);
+            for mv in moves.split_whitespace() {
+                let mv = ChessMove::from_str(mv).unwrap();
+                assert!(board.legal(mv), "{} in {}", mv, fen);
+                accumulator = accumulator.after_move(&network, &board, mv);
+                board = board.make_move_new(mv);
+                assert!(
+                    accumulator == Accumulator::new(&network, &board),
+                    "{} in {}",
+                    mv,
+                    fen
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_evaluation_is_color_symmetric() {
+        let network = Network::from_bytes(&random_network_bytes(3)).unwrap();
+        // The same position with colors swapped, from the other side to move
+        let board =
+            Board::from_str("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        let mirrored =
+            Board::from_str("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3")
+                .unwrap();
+        let eval = network.evaluate(&board, &Accumulator::new(&network, &board));
+        let mirrored_eval = network.evaluate(&mirrored, &Accumulator::new(&network, &mirrored));
+        assert_eq!(eval, -mirrored_eval);
+    }
+}
@@ -0,0 +1,65 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{Board, ChessMove, MoveGen};
+
+/// Count the leaf nodes of the legal move tree to the given depth
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    match depth {
+        0 => 1,
+        // Bulk count: the number of legal moves is the number of leaves
+        1 => MoveGen::new_legal(board).len() as u64,
+        _ => MoveGen::new_legal(board)
+            .map(|mv| perft(&board.make_move_new(mv), depth - 1))
+            .sum(),
+    }
+}
+
+/// Perft count of the subtree below every root move, sorted by move text so
+/// the output lines up with other engines' `divide`
+pub fn divide(board: &Board, depth: u32) -> Vec<(ChessMove, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let mut counts: Vec<(ChessMove, u64)> = MoveGen::new_legal(board)
+        .map(|mv| (mv, perft(&board.make_move_new(mv), depth - 1)))
+        .collect();
+    counts.sort_by_key(|(mv, _)| mv.to_string());
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_perft_start_position() {
+        let board = Board::default();
+        assert_eq!(perft(&board, 0), 1);
+        assert_eq!(perft(&board, 1), 20);
+        assert_eq!(perft(&board, 2), 400);
+        assert_eq!(perft(&board, 3), 8902);
+    }
+
+    #[test]
+    fn test_perft_kiwipete() {
+        let board =
+            Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        assert_eq!(perft(&board, 1), 48);
+        assert_eq!(perft(&board, 2), 2039);
+    }
+
+    #[test]
+    fn test_divide_sums_to_perft() {
+        let board = Board::default();
+        let counts = divide(&board, 3);
+        assert_eq!(counts.len(), 20);
+        assert_eq!(counts.iter().map(|(_, n)| n).sum::<u64>(), perft(&board, 3));
+        let e2e4 = counts.iter().find(|(mv, _)| mv.to_string() == "e2e4");
+        assert_eq!(e2e4.map(|(_, n)| *n), Some(600));
+        assert!(divide(&board, 0).is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{Board, Color, MoveGen, EMPTY};
+
+use crate::evaluation;
+use crate::uci::GoParams;
+
+/// Moves the clock is expected to have to last in a sudden-death game at the
+/// start, shrinking by one every other move down to `MIN_MOVES_TO_GO`
+const MAX_MOVES_TO_GO: i64 = 40;
+
+/// Fewest moves the clock is expected to have to last in a sudden-death game
+const MIN_MOVES_TO_GO: i64 = 20;
+
+/// Multiple of its allocation (the soft limit) a move may take at most
+const TIME_EXTENSION_FACTOR: f64 = 3.0;
+
+/// Largest share of the remaining clock a move may take, so a reserve is
+/// always left however large the increment
+const MAX_CLOCK_FRACTION: f64 = 0.5;
+
+/// Remaining clock in milliseconds below which the engine moves instantly,
+/// playing the best move of its first iteration
+const INSTANT_MOVE_CLOCK_MS: i64 = 250;
+
+/// Time allocated without a clock or a move time, in seconds
+const DEFAULT_MOVE_TIME: f64 = 1.0;
+
+/// Time the search may take for one move, in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeBudget {
+    /// Soft limit, past which no new iteration is started
+    pub soft: f64,
+    /// Hard limit, at which the search is aborted; None when the soft limit
+    /// is fixed (a move time) and must not be exceeded
+    pub hard: Option<f64>,
+}
+
+impl TimeBudget {
+    /// The budget less `overhead` seconds lost to GUI/network latency
+    pub fn minus_overhead(self, overhead: f64) -> TimeBudget {
+        TimeBudget {
+            soft: (self.soft - overhead).max(0.0),
+            hard: self.hard.map(|hard| (hard - overhead).max(0.0)),
+        }
+    }
+}
+
+/// Time the "go" command allows for the move in `board`, the `move_number`th
+/// of the game (as in a FEN). A move time is used as is. Time from the clock
+/// is spread over the moves left until the next control, or as many as a
+/// sudden-death game is expected to last, and scaled by the game phase and
+/// the number of legal moves.
+pub fn allocate(go: &GoParams, board: &Board, move_number: u32) -> TimeBudget {
+    // go movetime X — takes priority
+    if let Some(time_ms) = go.movetime {
+        return TimeBudget {
+            soft: time_ms.max(0) as f64 / 1000.0,
+            hard: None,
+        };
+    }
+
+    let (remaining, inc) = if board.side_to_move() == Color::White {
+        (go.wtime, go.winc)
+    } else {
+        (go.btime, go.binc)
+    };
+    let Some(remaining_ms) = remaining else {
+        return TimeBudget {
+            soft: DEFAULT_MOVE_TIME,
+            hard: None,
+        };
+    };
+
+    // GUIs may report a negative clock once the flag has fallen
+    let remaining_ms = remaining_ms.max(0);
+    if remaining_ms < INSTANT_MOVE_CLOCK_MS {
+        return TimeBudget {
+            soft: 0.0,
+            hard: Some(0.0),
+        };
+    }
+    let inc_ms = inc.unwrap_or(0).max(0);
+    // With a known number of moves until the next control, spread the clock
+    // over them (keeping one move in reserve)
+    let moves_left = expected_moves_left(move_number);
+    let moves_left = go
+        .movestogo
+        .filter(|&n| n > 0)
+        .map_or(moves_left, |n| (n + 1).min(moves_left));
+    let base_ms = (remaining_ms / moves_left + inc_ms) as f64;
+    let scale = phase_scale(game_phase(board)) * complexity_scale(board);
+
+    let max_ms = remaining_ms as f64 * MAX_CLOCK_FRACTION;
+    let soft_ms = (base_ms * scale).min(max_ms);
+    let hard_ms = (soft_ms * TIME_EXTENSION_FACTOR).min(max_ms).max(soft_ms);
+    TimeBudget {
+        soft: soft_ms / 1000.0,
+        hard: Some(hard_ms / 1000.0),
+    }
+}
+
+/// Moves a sudden-death clock is expected to have to last from the
+/// `move_number`th move of the game
+fn expected_moves_left(move_number: u32) -> i64 {
+    (MAX_MOVES_TO_GO - i64::from(move_number) / 2).max(MIN_MOVES_TO_GO)
+}
+
+/// How far from the endgame `board` is, from 1.0 with all pieces on the board
+/// down to 0.0 with only kings and pawns
+pub fn game_phase(board: &Board) -> f64 {
+    evaluation::game_phase(board) as f64 / evaluation::MAX_PHASE as f64
+}
+
+/// Share of the allocation spent at `phase`: the most in the middlegame,
+/// where plans are made, less in the opening and the endgame
+fn phase_scale(phase: f64) -> f64 {
+    0.85 + 0.6 * phase * (1.0 - phase)
+}
+
+/// Share of the allocation spent on a position with its number of legal
+/// moves: more with many to choose from, less when in check
+fn complexity_scale(board: &Board) -> f64 {
+    let legal_moves = MoveGen::new_legal(board).len() as f64;
+    let scale = (0.8 + legal_moves / 100.0).min(1.2);
+    if *board.checkers() != EMPTY {
+        scale * 0.9
+    } else {
+        scale
+    }
+}
+
+/// Full move number field of a FEN, 1 if missing or malformed
+pub fn fen_fullmove_number(fen: &str) -> u32 {
+    fen.split_whitespace()
+        .nth(5)
+        .and_then(|n| n.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(1)
+}
+
+/// Clock of the engine's side under `nodestime`, counted in nodes: filled
+/// from the GUI's clock at the first `go` of a game, and from then on kept
+/// by the engine itself, which charges each search the nodes it took and
+/// credits the increment. The time it has left so depends only on the nodes
+/// searched, not on how fast the machine searched them.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct NodeClock {
+    /// Nodes left, once the clock is set
+    available: Option<i64>,
+    /// Increment in nodes of the `go` being searched on this clock, credited
+    /// when its search is charged
+    pending_increment: Option<i64>,
+}
+
+impl NodeClock {
+    /// Replace the remaining time of `side` in `go` by the nodes left on this
+    /// clock, converted to milliseconds at `nodes_per_ms`, setting the clock
+    /// from `go` first if it isn't yet. A `go` without a clock for `side` is
+    /// left as it is, and its search won't be charged.
+    pub fn apply(&mut self, go: &mut GoParams, side: Color, nodes_per_ms: u64) {
+        let nodes_per_ms = nodes_per_ms.max(1) as i64;
+        let (time, inc) = if side == Color::White {
+            (&mut go.wtime, go.winc)
+        } else {
+            (&mut go.btime, go.binc)
+        };
+        self.pending_increment = None;
+        let Some(remaining) = *time else {
+            return;
+        };
+        let available = *self
+            .available
+            .get_or_insert(remaining.max(0) * nodes_per_ms);
+        *time = Some(available / nodes_per_ms);
+        self.pending_increment = Some(inc.unwrap_or(0).max(0) * nodes_per_ms);
+    }
+
+    /// Charge the search of the last `go` with `nodes`, if it ran on this
+    /// clock, and credit its increment
+    pub fn charge(&mut self, nodes: u64) {
+        if let (Some(available), Some(increment)) =
+            (&mut self.available, self.pending_increment.take())
+        {
+            *available = (*available - nodes as i64 + increment).max(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn clock(wtime: i64) -> GoParams {
+        GoParams {
+            wtime: Some(wtime),
+            ..GoParams::default()
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn test_game_phase() {
+        assert_eq!(game_phase(&Board::default()), 1.0);
+        let board = Board::from_str("4k3/8/8/8/8/8/4P3/3QK3 w - - 0 1").unwrap();
+        assert_close(game_phase(&board), 4.0 / 24.0);
+        let board = Board::from_str("4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        assert_eq!(game_phase(&board), 0.0);
+    }
+
+    #[test]
+    fn test_sudden_death() {
+        // 60000 / 40 moves = 1.5 seconds, less 15% in the opening, with the
+        // start position's 20 legal moves as the neutral complexity
+        let budget = allocate(&clock(60000), &Board::default(), 1);
+        assert_close(budget.soft, 1.275);
+        assert_close(budget.hard.unwrap(), 3.825);
+        // Later in the game the clock is spread over fewer moves
+        let budget = allocate(&clock(60000), &Board::default(), 40);
+        assert_close(budget.soft, 2.55);
+        // An increment adds to the base
+        let go = GoParams {
+            winc: Some(1000),
+            ..clock(60000)
+        };
+        assert_close(allocate(&go, &Board::default(), 1).soft, 2.125);
+    }
+
+    #[test]
+    fn test_movestogo() {
+        // 39000 / (12 + 1) = 3 seconds, less 15% in the opening
+        let go = GoParams {
+            movestogo: Some(12),
+            ..clock(39000)
+        };
+        assert_close(allocate(&go, &Board::default(), 1).soft, 2.55);
+        // Last move before the control: keep a move in reserve
+        let go = GoParams {
+            movestogo: Some(1),
+            ..clock(10000)
+        };
+        let budget = allocate(&go, &Board::default(), 1);
+        assert_close(budget.soft, 4.25);
+        // and never take more than half the clock
+        assert_close(budget.hard.unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_phase_and_complexity_scaling() {
+        let middlegame =
+            Board::from_str("r2q1rk1/pp2bppp/2n1pn2/3p4/3P4/2NBPN2/PP3PPP/R2Q1RK1 w - - 0 10")
+                .unwrap();
+        let endgame = Board::from_str("8/5k2/8/4p3/4P3/5K2/8/8 w - - 0 50").unwrap();
+        let in_check =
+            Board::from_str("r2q1rk1/pp2bppp/2n1pn2/3p4/1b1P4/3BPN2/PP3PPP/R2QK2R w KQ - 0 10")
+                .unwrap();
+        let soft = |board: &Board| allocate(&clock(60000), board, 10).soft;
+        assert!(soft(&middlegame) > soft(&Board::default()));
+        assert!(soft(&middlegame) > soft(&endgame));
+        assert!(soft(&middlegame) > soft(&in_check));
+    }
+
+    #[test]
+    fn test_low_clock() {
+        // A big increment never allocates more than half of what's on the clock
+        let go = GoParams {
+            winc: Some(2000),
+            ..clock(500)
+        };
+        let budget = allocate(&go, &Board::default(), 1);
+        assert_close(budget.soft, 0.25);
+        assert_close(budget.hard.unwrap(), 0.25);
+        // and with almost nothing left the engine moves instantly
+        let go = GoParams {
+            winc: Some(2000),
+            ..clock(200)
+        };
+        let budget = allocate(&go, &Board::default(), 1);
+        assert_eq!(budget.soft, 0.0);
+        assert_eq!(budget.hard, Some(0.0));
+        // A flag that has fallen counts as an empty clock
+        assert_eq!(allocate(&clock(-100), &Board::default(), 1).soft, 0.0);
+    }
+
+    #[test]
+    fn test_fixed_move_time() {
+        let go = GoParams {
+            movetime: Some(1000),
+            ..clock(60000)
+        };
+        let budget = allocate(&go, &Board::default(), 1);
+        assert_eq!(
+            budget,
+            TimeBudget {
+                soft: 1.0,
+                hard: None
+            }
+        );
+        // Only the side to move's clock counts
+        let go = GoParams {
+            btime: Some(1000),
+            ..GoParams::default()
+        };
+        assert_eq!(allocate(&go, &Board::default(), 1).hard, None);
+    }
+
+    #[test]
+    fn test_fen_fullmove_number() {
+        assert_eq!(fen_fullmove_number("8/8/8/8/8/8/8/K6k w - - 3 42"), 42);
+        assert_eq!(fen_fullmove_number("8/8/8/8/8/8/8/K6k w - -"), 1);
+        assert_eq!(fen_fullmove_number("8/8/8/8/8/8/8/K6k w - - 0 x"), 1);
+    }
+
+    #[test]
+    fn test_node_clock() {
+        let go = |wtime: i64| GoParams {
+            wtime: Some(wtime),
+            winc: Some(100),
+            ..GoParams::default()
+        };
+        let mut node_clock = NodeClock::default();
+        // The first clock sets 10 s at 50 nodes per ms: 500000 nodes
+        let mut first = go(10_000);
+        node_clock.apply(&mut first, Color::White, 50);
+        assert_eq!(first.wtime, Some(10_000));
+        // 100000 nodes searched, 5000 credited for the increment
+        node_clock.charge(100_000);
+        let mut second = go(60_000);
+        node_clock.apply(&mut second, Color::White, 50);
+        assert_eq!(second.wtime, Some(8_100));
+
+        // A search without a clock isn't charged, nor is Black's clock
+        // touched on White's
+        node_clock.charge(100_000);
+        let mut depth = GoParams {
+            depth: Some(5),
+            ..GoParams::default()
+        };
+        node_clock.apply(&mut depth, Color::White, 50);
+        assert_eq!(depth.wtime, None);
+        node_clock.charge(1_000_000);
+        let mut third = go(60_000);
+        third.btime = Some(60_000);
+        node_clock.apply(&mut third, Color::White, 50);
+        assert_eq!(third.wtime, Some(6_200));
+        assert_eq!(third.btime, Some(60_000));
+    }
+}
@@ -0,0 +1,433 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use crate::engine::SearchLimits;
+use crate::game::{self, GamePlayer, Player};
+use crate::uci_println;
+
+/// Openings the games start from, as (name, moves from the start position).
+/// Every pair plays each opening once with either color.
+const TOURNAMENT_OPENINGS: [(&str, &str); 8] = [
+    ("Ruy Lopez", "e2e4 e7e5 g1f3 b8c6 f1b5"),
+    ("Sicilian", "e2e4 c7c5 g1f3 d7d6"),
+    ("French", "e2e4 e7e6 d2d4 d7d5"),
+    ("Caro-Kann", "e2e4 c7c6 d2d4 d7d5"),
+    ("Queen's Gambit Declined", "d2d4 d7d5 c2c4 e7e6"),
+    ("King's Indian", "d2d4 g8f6 c2c4 g7g6"),
+    ("English", "c2c4 e7e5"),
+    ("Reti", "g1f3 d7d5 g2g3"),
+];
+
+/// Parsed `tournament` command
+pub struct TournamentSpec {
+    pub limits: SearchLimits,
+    /// Number of openings played by every pair, each with both colors
+    pub rounds: usize,
+    pub players: Vec<Player>,
+}
+
+/// Parse the arguments of
+/// `tournament [depth D] [movetime MS] [rounds N] config NAME [OPTION=VALUE ...] config ...`
+pub fn parse_tournament_command(tokens: &[&str]) -> Result<TournamentSpec, String> {
+    let mut depth = None;
+    let mut movetime = None;
+    let mut rounds = 1;
+    let mut players: Vec<Player> = Vec::new();
+
+    let mut iter = tokens.iter().skip(1);
+    while let Some(&token) = iter.next() {
+        let mut number = |what: &str| -> Result<u64, String> {
+            iter.next()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| format!("{} needs a number", what))
+        };
+        match token {
+            "depth" => depth = Some(number("depth")?.clamp(1, 64) as i32),
+            "movetime" => movetime = Some(number("movetime")?),
+            "rounds" => rounds = number("rounds")?.max(1) as usize,
+            "config" => {
+                let name = iter.next().ok_or("config needs a name")?;
+                players.push(Player::new(name));
+            }
+            setting => {
+                let player = players
+                    .last_mut()
+                    .ok_or_else(|| format!("'{}' given before any config", setting))?;
+                let (name, value) = setting
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected OPTION=VALUE, got '{}'", setting))?;
+                if !player.set_option(name, value) {
+                    return Err(format!("unknown option '{}'", name));
+                }
+            }
+        }
+    }
+
+    if players.len() < 2 {
+        return Err("a tournament needs at least two configs".to_string());
+    }
+
+    Ok(TournamentSpec {
+        limits: game::game_limits(depth, movetime),
+        rounds,
+        players,
+    })
+}
+
+/// Scores of a finished round robin
+pub struct TournamentResult {
+    pub names: Vec<String>,
+    /// `points[i][j]`: points player i scored against player j
+    pub points: Vec<Vec<f64>>,
+    /// `games[i][j]`: games played between players i and j
+    pub games: Vec<Vec<u32>>,
+}
+
+impl TournamentResult {
+    pub fn total_points(&self, player: usize) -> f64 {
+        self.points[player].iter().sum()
+    }
+
+    pub fn total_games(&self, player: usize) -> u32 {
+        self.games[player].iter().sum()
+    }
+}
+
+/// Elo difference implied by scoring the fraction `score` of the points.
+/// None for a perfect or zero score, where it is unbounded.
+pub fn elo_difference(score: f64) -> Option<f64> {
+    (score > 0.0 && score < 1.0).then(|| 400.0 * (score / (1.0 - score)).log10())
+}
+
+/// Play a round robin in which every pair meets once per round and color,
+/// printing each game result as it finishes
+pub fn run_tournament(spec: &mut TournamentSpec) -> TournamentResult {
+    let n = spec.players.len();
+    let mut points = vec![vec![0.0; n]; n];
+    let mut games = vec![vec![0; n]; n];
+    let mut game_number = 0;
+
+    for round in 0..spec.rounds {
+        let (opening_name, opening_moves) = TOURNAMENT_OPENINGS[round % TOURNAMENT_OPENINGS.len()];
+        let start = game::opening_board(opening_moves).expect("tournament openings are legal");
+
+        for i in 0..n {
+            for j in (i + 1)..n {
+                for (w, b) in [(i, j), (j, i)] {
+                    let (white, black) = pair_mut(&mut spec.players, w, b);
+                    let record = game::play_game(&start, white, black, &spec.limits);
+                    let white_points = game::white_points(record.result);
+
+                    points[w][b] += white_points;
+                    points[b][w] += 1.0 - white_points;
+                    games[w][b] += 1;
+                    games[b][w] += 1;
+                    game_number += 1;
+
+                    uci_println!(
+                        "Game {} ({}): {} - {} {} ({}, {} plies)",
+                        game_number,
+                        opening_name,
+                        white.name,
+                        black.name,
+                        game::result_text(record.result),
+                        record.reason,
+                        record.moves.len()
+                    );
+                }
+            }
+        }
+    }
+
+    TournamentResult {
+        names: spec.players.iter().map(|p| p.name.clone()).collect(),
+        points,
+        games,
+    }
+}
+
+/// Parsed `match` command
+pub struct MatchSpec {
+    /// Path of the opponent's binary
+    pub engine_path: String,
+    pub limits: SearchLimits,
+    /// Number of games; consecutive games share an opening with colors swapped
+    pub games: usize,
+}
+
+/// Parse the arguments of `match PATH [games N] [depth D] [movetime MS]`
+pub fn parse_match_command(tokens: &[&str]) -> Result<MatchSpec, String> {
+    let engine_path = tokens.get(1).ok_or("match needs an engine path")?;
+    let mut depth = None;
+    let mut movetime = None;
+    let mut games = 2;
+
+    let mut iter = tokens.iter().skip(2);
+    while let Some(&token) = iter.next() {
+        let value: u64 = iter
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| format!("{} needs a number", token))?;
+        match token {
+            "depth" => depth = Some(value.clamp(1, 64) as i32),
+            "movetime" => movetime = Some(value),
+            "games" => games = value.max(1) as usize,
+            other => return Err(format!("unknown match setting '{}'", other)),
+        }
+    }
+
+    Ok(MatchSpec {
+        engine_path: engine_path.to_string(),
+        limits: game::game_limits(depth, movetime),
+        games,
+    })
+}
+
+/// Score of a match, from the first player's point of view
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl MatchResult {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + 0.5 * self.draws as f64
+    }
+}
+
+/// Play `games` games between `player` and `opponent`, alternating colors
+/// and moving to the next opening every two games, printing each result as
+/// it finishes
+pub fn run_match(
+    player: &mut dyn GamePlayer,
+    opponent: &mut dyn GamePlayer,
+    games: usize,
+    limits: &SearchLimits,
+) -> MatchResult {
+    let mut result = MatchResult::default();
+
+    for game_index in 0..games {
+        let (opening_name, opening_moves) =
+            TOURNAMENT_OPENINGS[(game_index / 2) % TOURNAMENT_OPENINGS.len()];
+        let start = game::opening_board(opening_moves).expect("tournament openings are legal");
+        let player_is_white = game_index % 2 == 0;
+
+        let (record, white_name, black_name) = if player_is_white {
+            let record = game::play_game(&start, player, opponent, limits);
+            (record, player.name(), opponent.name())
+        } else {
+            let record = game::play_game(&start, opponent, player, limits);
+            (record, opponent.name(), player.name())
+        };
+
+        let white_points = game::white_points(record.result);
+        let points = if player_is_white {
+            white_points
+        } else {
+            1.0 - white_points
+        };
+        if points == 1.0 {
+            result.wins += 1;
+        } else if points == 0.0 {
+            result.losses += 1;
+        } else {
+            result.draws += 1;
+        }
+
+        uci_println!(
+            "Game {} ({}): {} - {} {} ({}, {} plies)",
+            game_index + 1,
+            opening_name,
+            white_name,
+            black_name,
+            game::result_text(record.result),
+            record.reason,
+            record.moves.len()
+        );
+    }
+
+    result
+}
+
+/// Print the final score of a match and the Elo difference it implies
+pub fn print_match_result(player: &str, opponent: &str, result: &MatchResult) {
+    let games = result.games();
+    let elo = match (games > 0)
+        .then(|| elo_difference(result.points() / games as f64))
+        .flatten()
+    {
+        Some(elo) => format!("{:+.0}", elo),
+        None => "n/a".to_string(),
+    };
+    uci_println!(
+        "Score of {} vs {}: {} - {} - {} [{}/{}], Elo {}",
+        player,
+        opponent,
+        result.wins,
+        result.losses,
+        result.draws,
+        result.points(),
+        games,
+        elo
+    );
+}
+
+/// Mutable references to two different players
+fn pair_mut(players: &mut [Player], a: usize, b: usize) -> (&mut Player, &mut Player) {
+    assert_ne!(a, b);
+    if a < b {
+        let (left, right) = players.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = players.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
+}
+
+/// Print the crosstable, best total score first, followed by the score and
+/// Elo estimate of every pair
+pub fn print_crosstable(result: &TournamentResult) {
+    let n = result.names.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| result.total_points(b).total_cmp(&result.total_points(a)));
+    let width = result
+        .names
+        .iter()
+        .map(|s| s.len())
+        .max()
+        .unwrap_or(0)
+        .max(6)
+        + 2;
+
+    let mut header = format!("{:<width$}", "Name");
+    for &j in &order {
+        header += &format!("{:>width$}", result.names[j]);
+    }
+    uci_println!("{}{:>8}{:>7}", header, "Score", "Games");
+    for &i in &order {
+        let mut row = format!("{:<width$}", result.names[i]);
+        for &j in &order {
+            if i == j {
+                row += &format!("{:>width$}", "-");
+            } else {
+                row += &format!("{:>width$}", result.points[i][j]);
+            }
+        }
+        uci_println!(
+            "{}{:>8}{:>7}",
+            row,
+            result.total_points(i),
+            result.total_games(i)
+        );
+    }
+
+    uci_println!();
+    for (rank, &i) in order.iter().enumerate() {
+        for &j in &order[rank + 1..] {
+            let games = result.games[i][j];
+            if games == 0 {
+                continue;
+            }
+            let elo = match elo_difference(result.points[i][j] / games as f64) {
+                Some(elo) => format!("{:+.0}", elo),
+                None => "n/a".to_string(),
+            };
+            uci_println!(
+                "{} vs {}: {}/{}, Elo {}",
+                result.names[i],
+                result.names[j],
+                result.points[i][j],
+                games,
+                elo
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tournament_command() {
+        let tokens: Vec<&str> =
+            "tournament depth 2 rounds 3 config base config nmr3 NullMoveR=3 Contempt=10"
+                .split_whitespace()
+                .collect();
+        let spec = parse_tournament_command(&tokens).unwrap();
+        assert_eq!(spec.limits, SearchLimits::depth(2));
+        assert_eq!(spec.rounds, 3);
+        assert_eq!(spec.players.len(), 2);
+        assert_eq!(spec.players[1].name, "nmr3");
+        assert_eq!(spec.players[1].engine_state.params.null_move_r, 3);
+        assert_eq!(spec.players[1].options.contempt, 10);
+
+        let bad = |line: &str| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            parse_tournament_command(&tokens).is_err()
+        };
+        assert!(bad("tournament config only"));
+        assert!(bad("tournament NullMoveR=3 config a config b"));
+        assert!(bad("tournament config a config b Bogus=1"));
+        assert!(bad("tournament depth x config a config b"));
+    }
+
+    #[test]
+    fn test_tournament_openings_are_legal() {
+        for (_, moves) in TOURNAMENT_OPENINGS {
+            assert!(game::opening_board(moves).is_ok(), "{}", moves);
+        }
+    }
+
+    #[test]
+    fn test_elo_difference() {
+        assert_eq!(elo_difference(0.5), Some(0.0));
+        assert!(elo_difference(0.75).unwrap() > 190.0);
+        assert!(elo_difference(0.25).unwrap() < -190.0);
+        assert_eq!(elo_difference(1.0), None);
+        assert_eq!(elo_difference(0.0), None);
+    }
+
+    #[test]
+    fn test_run_tournament_scores_add_up() {
+        let tokens: Vec<&str> = "tournament depth 1 config a config b Contempt=50"
+            .split_whitespace()
+            .collect();
+        let mut spec = parse_tournament_command(&tokens).unwrap();
+        let result = run_tournament(&mut spec);
+        assert_eq!(result.games[0][1], 2);
+        assert_eq!(result.total_points(0) + result.total_points(1), 2.0);
+    }
+
+    #[test]
+    fn test_parse_match_command() {
+        let tokens: Vec<&str> = "match ./stockfish games 10 movetime 50"
+            .split_whitespace()
+            .collect();
+        let spec = parse_match_command(&tokens).unwrap();
+        assert_eq!(spec.engine_path, "./stockfish");
+        assert_eq!(spec.games, 10);
+        assert_eq!(spec.limits, SearchLimits::time(0.05));
+
+        assert!(parse_match_command(&["match"]).is_err());
+        assert!(parse_match_command(&["match", "x", "games"]).is_err());
+        assert!(parse_match_command(&["match", "x", "speed", "3"]).is_err());
+    }
+
+    #[test]
+    fn test_run_match_alternates_colors() {
+        let mut player = Player::new("a");
+        let mut opponent = Player::new("b");
+        let result = run_match(&mut player, &mut opponent, 2, &SearchLimits::depth(1));
+        assert_eq!(result.games(), 2);
+        // Identical configurations score the same with either color
+        assert_eq!(result.wins, result.losses);
+    }
+}
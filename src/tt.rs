@@ -0,0 +1,413 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use chess::{ChessMove, ALL_PIECES, ALL_SQUARES};
+
+use crate::evaluation::MATE_BOUND;
+
+/// Entries sharing one index of the table, unless calibrated otherwise
+pub(crate) const DEFAULT_BUCKET_SIZE: usize = 4;
+
+/// Bucket sizes a table may be made with
+pub(crate) const BUCKET_SIZES: [usize; 3] = [2, 4, 8];
+const MAX_BUCKET_SIZE: usize = 8;
+
+/// Transposition table bound type
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum TTFlag {
+    Exact,
+    LowerBound,
+    UpperBound,
+}
+
+/// Transposition table entry
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct TTEntry {
+    pub depth: i32,
+    pub eval: i32,
+    pub flag: TTFlag,
+    pub best_move: Option<ChessMove>,
+}
+
+/// Bit layout of a packed entry: the best move in the low 16 bits, then the
+/// score, depth, bound and generation. A zero word is an empty slot, which
+/// the bound (never 0) tells apart from a real entry.
+const EVAL_SHIFT: u32 = 16;
+const EVAL_BITS: u32 = 24;
+const DEPTH_SHIFT: u32 = EVAL_SHIFT + EVAL_BITS;
+const FLAG_SHIFT: u32 = DEPTH_SHIFT + 8;
+const GENERATION_SHIFT: u32 = FLAG_SHIFT + 2;
+
+/// Marks a stored best move, so that a1a1 without it means none
+const MOVE_PRESENT: u64 = 1 << 15;
+
+fn pack_move(mv: Option<ChessMove>) -> u64 {
+    mv.map_or(0, |mv| {
+        let promotion = mv.get_promotion().map_or(0, |p| p.to_index() as u64 + 1);
+        MOVE_PRESENT
+            | promotion << 12
+            | (mv.get_source().to_index() as u64) << 6
+            | mv.get_dest().to_index() as u64
+    })
+}
+
+fn unpack_move(bits: u64) -> Option<ChessMove> {
+    if bits & MOVE_PRESENT == 0 {
+        return None;
+    }
+    let promotion = match (bits >> 12) & 7 {
+        0 => None,
+        p => Some(ALL_PIECES[p as usize - 1]),
+    };
+    Some(ChessMove::new(
+        ALL_SQUARES[((bits >> 6) & 63) as usize],
+        ALL_SQUARES[(bits & 63) as usize],
+        promotion,
+    ))
+}
+
+/// Pack an entry and the generation of the search storing it into one word.
+/// Scores fit in 24 bits (mates are about a million) and depths in 8.
+fn pack(entry: &TTEntry, generation: u8) -> u64 {
+    let flag = match entry.flag {
+        TTFlag::Exact => 1,
+        TTFlag::LowerBound => 2,
+        TTFlag::UpperBound => 3,
+    };
+    let eval = entry.eval as u64 & ((1 << EVAL_BITS) - 1);
+    let depth = entry.depth.clamp(i8::MIN as i32, i8::MAX as i32) as i8 as u8 as u64;
+    pack_move(entry.best_move)
+        | eval << EVAL_SHIFT
+        | depth << DEPTH_SHIFT
+        | flag << FLAG_SHIFT
+        | (generation as u64) << GENERATION_SHIFT
+}
+
+/// Entry and generation of a packed word, None for an empty slot
+fn unpack(data: u64) -> Option<(TTEntry, u8)> {
+    let flag = match (data >> FLAG_SHIFT) & 3 {
+        0 => return None,
+        1 => TTFlag::Exact,
+        2 => TTFlag::LowerBound,
+        _ => TTFlag::UpperBound,
+    };
+    // Shift the score up to the sign bit and back to sign-extend it
+    let eval = ((data >> EVAL_SHIFT) as u32 as i32) << (32 - EVAL_BITS) >> (32 - EVAL_BITS);
+    let entry = TTEntry {
+        depth: (data >> DEPTH_SHIFT) as u8 as i8 as i32,
+        eval,
+        flag,
+        best_move: unpack_move(data),
+    };
+    Some((entry, (data >> GENERATION_SHIFT) as u8))
+}
+
+/// One entry, stored without a lock as two words: the packed entry, and the
+/// position's key XORed with it. A read pairing words from two different
+/// writes fails the key check and is treated as a miss, so search threads
+/// can share the table with plain relaxed atomics.
+#[derive(Default)]
+struct Slot {
+    key_xor_data: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    /// Key and packed entry, None if the slot is empty
+    fn load(&self) -> Option<(u64, u64)> {
+        let data = self.data.load(Ordering::Relaxed);
+        if data == 0 {
+            return None;
+        }
+        Some((self.key_xor_data.load(Ordering::Relaxed) ^ data, data))
+    }
+
+    fn store(&self, key: u64, data: u64) {
+        self.key_xor_data.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+/// Fixed-size transposition table of buckets indexed by the position hash.
+/// A new entry replaces one for the same position, fills an empty slot, or
+/// else evicts the bucket's least valuable entry: left over from an earlier
+/// search, then the shallowest. Probing and storing take a shared reference,
+/// so several search threads can use one table.
+pub(crate) struct TranspositionTable {
+    slots: Vec<Slot>,
+    /// Occupied slots
+    used: AtomicUsize,
+    /// Counts searches, to tell current entries from stale ones
+    generation: u8,
+    size_mb: usize,
+    bucket_size: usize,
+}
+
+impl TranspositionTable {
+    /// A table taking about `mb` megabytes, allocated up front
+    pub fn new(mb: usize) -> Self {
+        Self::with_bucket_size(mb, DEFAULT_BUCKET_SIZE)
+    }
+
+    /// A table taking about `mb` megabytes in buckets of `bucket_size`
+    /// entries, one of `BUCKET_SIZES`
+    pub fn with_bucket_size(mb: usize, bucket_size: usize) -> Self {
+        assert!(
+            BUCKET_SIZES.contains(&bucket_size),
+            "unsupported bucket size {}",
+            bucket_size
+        );
+        let slot_bytes = std::mem::size_of::<Slot>();
+        let buckets = (mb * 1024 * 1024 / (slot_bytes * bucket_size)).max(1);
+        TranspositionTable {
+            slots: (0..buckets * bucket_size)
+                .map(|_| Slot::default())
+                .collect(),
+            used: AtomicUsize::new(0),
+            generation: 0,
+            size_mb: mb,
+            bucket_size,
+        }
+    }
+
+    /// A table of no entries standing in for one of `mb` megabytes in
+    /// buckets of `bucket_size` while it is away, reporting its settings.
+    /// It must not be probed or stored to.
+    pub fn unallocated(mb: usize, bucket_size: usize) -> Self {
+        TranspositionTable {
+            slots: Vec::new(),
+            used: AtomicUsize::new(0),
+            generation: 0,
+            size_mb: mb,
+            bucket_size,
+        }
+    }
+
+    /// Size in megabytes, as requested when the table was made
+    pub fn size_mb(&self) -> usize {
+        self.size_mb
+    }
+
+    /// Entries sharing one index of the table
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Number of entries stored
+    pub fn len(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            *slot = Slot::default();
+        }
+        self.used = AtomicUsize::new(0);
+    }
+
+    /// Mark the entries stored so far as belonging to earlier searches
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    fn bucket(&self, key: u64) -> &[Slot] {
+        let buckets = self.slots.len() / self.bucket_size;
+        let start = (key % buckets as u64) as usize * self.bucket_size;
+        &self.slots[start..start + self.bucket_size]
+    }
+
+    pub fn get(&self, key: u64) -> Option<TTEntry> {
+        self.bucket(key)
+            .iter()
+            .filter_map(Slot::load)
+            .find(|&(slot_key, _)| slot_key == key)
+            .and_then(|(_, data)| unpack(data))
+            .map(|(entry, _)| entry)
+    }
+
+    pub fn insert(&self, key: u64, entry: TTEntry) {
+        let generation = self.generation;
+        let bucket = self.bucket(key);
+        let mut loaded = [None; MAX_BUCKET_SIZE];
+        for (entry, slot) in loaded.iter_mut().zip(bucket) {
+            *entry = slot.load();
+        }
+        let loaded = &loaded[..bucket.len()];
+
+        let index = loaded
+            .iter()
+            .position(|slot| slot.is_some_and(|(slot_key, _)| slot_key == key))
+            .or_else(|| loaded.iter().position(Option::is_none))
+            .unwrap_or_else(|| {
+                // Bucket full of other positions: evict stale entries first,
+                // then the shallowest
+                (0..loaded.len())
+                    .min_by_key(|&i| {
+                        let (_, data) = loaded[i].expect("bucket is full");
+                        unpack(data)
+                            .map(|(old, old_generation)| (old_generation == generation, old.depth))
+                    })
+                    .expect("buckets are not empty")
+            });
+
+        if loaded[index].is_none() {
+            self.used.fetch_add(1, Ordering::Relaxed);
+        }
+        bucket[index].store(key, pack(&entry, generation));
+    }
+}
+
+/// Convert a score found `ply` plies from the root for storage. Mate scores
+/// count plies from the root, so they are stored counting from the position
+/// itself instead, to stay right when it is reached again at another ply.
+pub(crate) fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply
+    } else if score <= -MATE_BOUND {
+        score - ply
+    } else {
+        score
+    }
+}
+
+/// Inverse of `score_to_tt`, for an entry probed `ply` plies from the root
+pub(crate) fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply
+    } else if score <= -MATE_BOUND {
+        score + ply
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(depth: i32) -> TTEntry {
+        TTEntry {
+            depth,
+            eval: depth,
+            flag: TTFlag::Exact,
+            best_move: None,
+        }
+    }
+
+    #[test]
+    fn test_store_and_probe() {
+        let mut table = TranspositionTable::new(1);
+        assert!(table.capacity() > 1000);
+        assert_eq!(table.len(), 0);
+
+        table.insert(42, entry(3));
+        assert_eq!(table.get(42).map(|e| e.depth), Some(3));
+        assert!(table.get(43).is_none());
+
+        // The same position overwrites its entry
+        table.insert(42, entry(1));
+        assert_eq!(table.get(42).map(|e| e.depth), Some(1));
+        assert_eq!(table.len(), 1);
+
+        table.clear();
+        assert!(table.get(42).is_none());
+        assert_eq!(table.len(), 0);
+    }
+
+    #[test]
+    fn test_replacement() {
+        for bucket_size in BUCKET_SIZES {
+            let mut table = TranspositionTable::with_bucket_size(1, bucket_size);
+            let buckets = (table.capacity() / bucket_size) as u64;
+            // Keys in the same bucket
+            let key = |i: u64| 7 + i * buckets;
+
+            for i in 0..bucket_size as u64 {
+                table.insert(key(i), entry(5 + i as i32));
+            }
+            // A full bucket loses its shallowest entry
+            table.insert(key(10), entry(1));
+            assert!(table.get(key(0)).is_none());
+            assert!(table.get(key(10)).is_some());
+            assert_eq!(table.len(), bucket_size);
+
+            // Entries from an earlier search go before deeper current ones
+            table.new_search();
+            table.insert(key(11), entry(2));
+            table.insert(key(12), entry(1));
+            assert!(table.get(key(11)).is_some());
+            assert!(table.get(key(12)).is_some());
+            assert!(table.get(key(10)).is_none());
+            assert!(table.get(key(1)).is_none());
+        }
+    }
+
+    #[test]
+    fn test_packing_round_trips() {
+        use crate::evaluation::MATE_EVAL;
+        use chess::{Piece, Square};
+
+        let entries = [
+            TTEntry {
+                depth: 12,
+                eval: -(MATE_EVAL + 1),
+                flag: TTFlag::UpperBound,
+                best_move: Some(ChessMove::new(Square::B2, Square::A1, Some(Piece::Knight))),
+            },
+            TTEntry {
+                depth: 1,
+                eval: 731,
+                flag: TTFlag::LowerBound,
+                best_move: Some(ChessMove::new(Square::A1, Square::H8, None)),
+            },
+            entry(0),
+        ];
+        for entry in entries {
+            assert_eq!(unpack(pack(&entry, 200)), Some((entry, 200)));
+        }
+        assert_eq!(unpack(0), None);
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let table = TranspositionTable::new(1);
+        std::thread::scope(|scope| {
+            for thread in 0..4u64 {
+                let table = &table;
+                scope.spawn(move || {
+                    for i in 0..1000 {
+                        let key = i * 4 + thread + 1;
+                        table.insert(key, entry(thread as i32));
+                        if let Some(found) = table.get(key) {
+                            assert_eq!(found.depth, thread as i32);
+                        }
+                    }
+                });
+            }
+        });
+        assert!(table.len() > 0);
+        assert_eq!(table.get(5).map(|e| e.depth), Some(0));
+    }
+
+    #[test]
+    fn test_mate_scores_are_stored_relative_to_the_node() {
+        use crate::evaluation::MATE_EVAL;
+
+        // Mate in 3 plies from a node 4 plies deep, probed again at ply 2
+        let stored = score_to_tt(MATE_EVAL - 7, 4);
+        assert_eq!(stored, MATE_EVAL - 3);
+        assert_eq!(score_from_tt(stored, 2), MATE_EVAL - 5);
+        assert_eq!(
+            score_from_tt(score_to_tt(-(MATE_EVAL - 6), 4), 2),
+            -(MATE_EVAL - 4)
+        );
+        assert_eq!(score_to_tt(250, 4), 250);
+    }
+}
@@ -0,0 +1,820 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{Board, BoardStatus, Color, MoveGen, Piece};
+
+use crate::engine;
+use crate::evaluation::{
+    self, EvalBreakdown, BISHOP_VAL, BISHOP_VAL_EG, KNIGHT_VAL, KNIGHT_VAL_EG, MAX_PHASE, PAWN_VAL,
+    PAWN_VAL_EG, QUEEN_VAL, QUEEN_VAL_EG, ROOK_VAL, ROOK_VAL_EG, SCALE_NORMAL,
+};
+use crate::moves;
+use crate::uci::START_POSITION;
+use crate::uci_println;
+use Constant::{Eg, EgArray, Mg, MgArray, Mobility, Pair};
+
+/// File `tune` writes the tuned values to when none is given
+pub const DEFAULT_TUNE_OUTPUT: &str = "./tuned.txt";
+
+/// Optimizer steps `tune` takes when not told otherwise
+pub const DEFAULT_TUNE_ITERATIONS: usize = 1000;
+
+/// Adam's step size and decay rates. The tuned values are all multipliers
+/// of the current ones, so a step of 0.002 moves each by at most 0.2%.
+const LEARNING_RATE: f64 = 0.002;
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+
+/// Plies at the start of each PGN game left out, as they mostly come from
+/// opening books rather than the players
+const OPENING_PLIES: usize = 8;
+
+/// Iterations between progress reports
+const REPORT_INTERVAL: usize = 100;
+
+/// Material values in the middlegame and in the endgame, tuned directly; the
+/// endgame one is named after the middlegame one with `_EG` appended
+const PIECES: [(Piece, &str, i32, i32); 5] = [
+    (Piece::Pawn, "PAWN_VAL", PAWN_VAL, PAWN_VAL_EG),
+    (Piece::Knight, "KNIGHT_VAL", KNIGHT_VAL, KNIGHT_VAL_EG),
+    (Piece::Bishop, "BISHOP_VAL", BISHOP_VAL, BISHOP_VAL_EG),
+    (Piece::Rook, "ROOK_VAL", ROOK_VAL, ROOK_VAL_EG),
+    (Piece::Queen, "QUEEN_VAL", QUEEN_VAL, QUEEN_VAL_EG),
+];
+
+/// A constant of `evaluation.rs`, by name and declared type, which the
+/// tuned values are written out as, scaled by the multipliers of its term
+enum Constant {
+    /// An `i32` counting in the middlegame only, or in the endgame only
+    Mg(&'static str, i32),
+    Eg(&'static str, i32),
+    /// An `(i32, i32)` of a middlegame and an endgame value
+    Pair(&'static str, (i32, i32)),
+    /// An `[i32; N]` of middlegame values, or of endgame values
+    MgArray(&'static str, &'static [i32]),
+    EgArray(&'static str, &'static [i32]),
+    /// The mobility weights: a piece type, its middlegame and endgame
+    /// weights and the squares that score zero, which are left as they are
+    Mobility(&'static str, &'static [(Piece, i32, i32, i32)]),
+}
+
+/// `value` scaled by `weight`, rounded to a whole centipawn
+fn scaled(value: i32, weight: f64) -> i32 {
+    (value as f64 * weight).round() as i32
+}
+
+impl Constant {
+    /// The declaration of the constant, scaled by the multipliers `mg` and `eg`
+    fn declaration(&self, mg: f64, eg: f64) -> String {
+        let array = |name: &str, values: &[i32], weight: f64| {
+            let values: Vec<String> = values
+                .iter()
+                .map(|&v| scaled(v, weight).to_string())
+                .collect();
+            format!(
+                "pub(crate) const {}: [i32; {}] = [{}];",
+                name,
+                values.len(),
+                values.join(", ")
+            )
+        };
+        match *self {
+            Constant::Mg(name, value) => {
+                format!("pub(crate) const {}: i32 = {};", name, scaled(value, mg))
+            }
+            Constant::Eg(name, value) => {
+                format!("pub(crate) const {}: i32 = {};", name, scaled(value, eg))
+            }
+            Constant::Pair(name, (mg_value, eg_value)) => format!(
+                "pub(crate) const {}: (i32, i32) = ({}, {});",
+                name,
+                scaled(mg_value, mg),
+                scaled(eg_value, eg)
+            ),
+            Constant::MgArray(name, values) => array(name, values, mg),
+            Constant::EgArray(name, values) => array(name, values, eg),
+            Constant::Mobility(name, weights) => {
+                let weights: Vec<String> = weights
+                    .iter()
+                    .map(|&(piece, mg_weight, eg_weight, baseline)| {
+                        format!(
+                            "(Piece::{:?}, {}, {}, {})",
+                            piece,
+                            scaled(mg_weight, mg),
+                            scaled(eg_weight, eg),
+                            baseline
+                        )
+                    })
+                    .collect();
+                format!(
+                    "pub(crate) const {}: [(Piece, i32, i32, i32); {}] = [{}];",
+                    name,
+                    weights.len(),
+                    weights.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// An evaluation term tuned as a whole
+struct Term {
+    name: &'static str,
+    value: fn(&EvalBreakdown) -> [i32; 2],
+    /// Whether the term blends a middlegame and an endgame value by phase,
+    /// each tuned by a multiplier of its own. The others, whose constants
+    /// count in both phases alike or fade out along a curve of their own,
+    /// are tuned by one multiplier for all their constants.
+    tapered: bool,
+    /// What the term is built from, written out scaled once tuned
+    constants: &'static [Constant],
+}
+
+/// Evaluation terms tuned as a whole, each by one multiplier for its
+/// middlegame values and one for its endgame values
+const TERMS: [Term; 13] = [
+    Term {
+        name: "pst",
+        value: |b| b.pst,
+        tapered: true,
+        constants: &[
+            MgArray("WHITE_PAWN_MG_TABLE", &evaluation::WHITE_PAWN_MG_TABLE),
+            MgArray("BLACK_PAWN_MG_TABLE", &evaluation::BLACK_PAWN_MG_TABLE),
+            EgArray("WHITE_PAWN_EG_TABLE", &evaluation::WHITE_PAWN_EG_TABLE),
+            EgArray("BLACK_PAWN_EG_TABLE", &evaluation::BLACK_PAWN_EG_TABLE),
+            MgArray("WHITE_KNIGHT_MG_TABLE", &evaluation::WHITE_KNIGHT_MG_TABLE),
+            MgArray("BLACK_KNIGHT_MG_TABLE", &evaluation::BLACK_KNIGHT_MG_TABLE),
+            EgArray("WHITE_KNIGHT_EG_TABLE", &evaluation::WHITE_KNIGHT_EG_TABLE),
+            EgArray("BLACK_KNIGHT_EG_TABLE", &evaluation::BLACK_KNIGHT_EG_TABLE),
+            MgArray("WHITE_BISHOP_MG_TABLE", &evaluation::WHITE_BISHOP_MG_TABLE),
+            MgArray("BLACK_BISHOP_MG_TABLE", &evaluation::BLACK_BISHOP_MG_TABLE),
+            EgArray("WHITE_BISHOP_EG_TABLE", &evaluation::WHITE_BISHOP_EG_TABLE),
+            EgArray("BLACK_BISHOP_EG_TABLE", &evaluation::BLACK_BISHOP_EG_TABLE),
+            MgArray("WHITE_ROOK_MG_TABLE", &evaluation::WHITE_ROOK_MG_TABLE),
+            MgArray("BLACK_ROOK_MG_TABLE", &evaluation::BLACK_ROOK_MG_TABLE),
+            EgArray("WHITE_ROOK_EG_TABLE", &evaluation::WHITE_ROOK_EG_TABLE),
+            EgArray("BLACK_ROOK_EG_TABLE", &evaluation::BLACK_ROOK_EG_TABLE),
+            MgArray("WHITE_QUEEN_MG_TABLE", &evaluation::WHITE_QUEEN_MG_TABLE),
+            MgArray("BLACK_QUEEN_MG_TABLE", &evaluation::BLACK_QUEEN_MG_TABLE),
+            EgArray("WHITE_QUEEN_EG_TABLE", &evaluation::WHITE_QUEEN_EG_TABLE),
+            EgArray("BLACK_QUEEN_EG_TABLE", &evaluation::BLACK_QUEEN_EG_TABLE),
+            MgArray("WHITE_KING_MG_TABLE", &evaluation::WHITE_KING_MG_TABLE),
+            MgArray("BLACK_KING_MG_TABLE", &evaluation::BLACK_KING_MG_TABLE),
+            EgArray("WHITE_KING_EG_TABLE", &evaluation::WHITE_KING_EG_TABLE),
+            EgArray("BLACK_KING_EG_TABLE", &evaluation::BLACK_KING_EG_TABLE),
+        ],
+    },
+    Term {
+        name: "mobility",
+        value: |b| b.mobility,
+        tapered: true,
+        constants: &[
+            Mobility("MOBILITY", &evaluation::MOBILITY),
+            Pair("PINNED_PIECE", evaluation::PINNED_PIECE),
+        ],
+    },
+    Term {
+        name: "king_safety",
+        value: |b| b.king_safety,
+        tapered: true,
+        constants: &[
+            Mg("SHELTER_MISSING_PAWN", evaluation::SHELTER_MISSING_PAWN),
+            Mg("SHELTER_OPEN_FILE", evaluation::SHELTER_OPEN_FILE),
+            Mg("SHIELD_PAWN", evaluation::SHIELD_PAWN),
+            Mg("SHIELD_PAWN_ADVANCED", evaluation::SHIELD_PAWN_ADVANCED),
+            MgArray("KING_DANGER", &evaluation::KING_DANGER),
+            Mg("PINNED_DEFENDER", evaluation::PINNED_DEFENDER),
+        ],
+    },
+    Term {
+        name: "castling",
+        value: |b| b.castling,
+        tapered: true,
+        constants: &[
+            Mg("CASTLED", evaluation::CASTLED),
+            Mg("CASTLING_RIGHTS", evaluation::CASTLING_RIGHTS),
+            Mg("STUCK_KING", evaluation::STUCK_KING),
+        ],
+    },
+    Term {
+        name: "development",
+        value: |b| b.development,
+        tapered: false,
+        constants: &[Mg("UNDEVELOPED_MINOR", evaluation::UNDEVELOPED_MINOR)],
+    },
+    Term {
+        name: "center",
+        value: |b| b.center,
+        tapered: true,
+        constants: &[
+            Mg("CENTER_PAWN", evaluation::CENTER_PAWN),
+            Mg("CENTER_PIECE", evaluation::CENTER_PIECE),
+            Mg("EXTENDED_CENTER_PAWN", evaluation::EXTENDED_CENTER_PAWN),
+            Mg("EXTENDED_CENTER_PIECE", evaluation::EXTENDED_CENTER_PIECE),
+        ],
+    },
+    Term {
+        name: "weak_squares",
+        value: |b| b.weak_squares,
+        tapered: true,
+        constants: &[
+            Pair("HOLE", evaluation::HOLE),
+            Pair("KING_HOLE", evaluation::KING_HOLE),
+        ],
+    },
+    Term {
+        name: "passed_pawns",
+        value: |b| b.passed_pawns,
+        tapered: false,
+        constants: &[
+            MgArray("PASSED_PAWN_BONUS", &evaluation::PASSED_PAWN_BONUS),
+            Eg("PASSED_PAWN_THEIR_KING", evaluation::PASSED_PAWN_THEIR_KING),
+            Eg("PASSED_PAWN_OUR_KING", evaluation::PASSED_PAWN_OUR_KING),
+        ],
+    },
+    Term {
+        name: "pawn_majorities",
+        value: |b| b.pawn_majorities,
+        tapered: false,
+        constants: &[
+            Mg("MAJORITY_BONUS", evaluation::MAJORITY_BONUS),
+            Mg("OUTSIDE_MAJORITY_BONUS", evaluation::OUTSIDE_MAJORITY_BONUS),
+            Mg("MINORITY_ATTACK_BONUS", evaluation::MINORITY_ATTACK_BONUS),
+        ],
+    },
+    Term {
+        name: "bishop_pair",
+        value: |b| b.bishop_pair,
+        tapered: true,
+        constants: &[
+            Mg("BISHOP_PAIR_MG", evaluation::BISHOP_PAIR_MG),
+            Eg("BISHOP_PAIR_EG", evaluation::BISHOP_PAIR_EG),
+        ],
+    },
+    Term {
+        name: "rooks",
+        value: |b| b.rooks,
+        tapered: true,
+        constants: &[
+            Pair("ROOK_BEHIND_PASSER", evaluation::ROOK_BEHIND_PASSER),
+            Pair("CONNECTED_ROOKS", evaluation::CONNECTED_ROOKS),
+            Pair(
+                "DOUBLED_ROOKS_OPEN_FILE",
+                evaluation::DOUBLED_ROOKS_OPEN_FILE,
+            ),
+        ],
+    },
+    Term {
+        name: "threats",
+        value: |b| b.threats,
+        tapered: true,
+        constants: &[
+            Pair("THREAT_BY_PAWN", evaluation::THREAT_BY_PAWN),
+            Pair("THREAT_BY_MINOR", evaluation::THREAT_BY_MINOR),
+        ],
+    },
+    Term {
+        name: "hanging",
+        value: |b| b.hanging,
+        tapered: true,
+        constants: &[
+            Pair("HANGING_PIECE", evaluation::HANGING_PIECE),
+            Pair("UNDEFENDED_PIECE", evaluation::UNDEFENDED_PIECE),
+        ],
+    },
+];
+
+/// Number of tuned values: a middlegame and an endgame one per piece and
+/// per term
+const PARAMS: usize = 2 * (PIECES.len() + TERMS.len());
+
+/// What `tune` is run on: `tune PATH [iterations N] [output FILE]`
+#[derive(Debug, PartialEq)]
+pub struct TuneSpec {
+    pub dataset_path: String,
+    pub iterations: usize,
+    pub output_path: String,
+}
+
+/// Parse the arguments of `tune PATH [iterations N] [output FILE]`
+pub fn parse_tune_command(tokens: &[&str]) -> Result<TuneSpec, String> {
+    let dataset_path = tokens.get(1).ok_or("tune needs a dataset path")?;
+    let mut iterations = DEFAULT_TUNE_ITERATIONS;
+    let mut output_path = DEFAULT_TUNE_OUTPUT.to_string();
+
+    let mut iter = tokens.iter().skip(2);
+    while let Some(&token) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("{} needs a value", token))?;
+        match token {
+            "iterations" => {
+                iterations = value
+                    .parse()
+                    .map_err(|_| format!("{} needs a number", token))?
+            }
+            "output" => output_path = value.to_string(),
+            other => return Err(format!("unknown tune setting '{}'", other)),
+        }
+    }
+
+    Ok(TuneSpec {
+        dataset_path: dataset_path.to_string(),
+        iterations,
+        output_path,
+    })
+}
+
+/// A position's evaluation, White's terms minus Black's, split into the
+/// parts the tuned values multiply, and the result of its game
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    features: [f64; PARAMS],
+    /// What the features leave out: mop-up, which is left as it is, and
+    /// what blending each term by phase rounds off
+    fixed: f64,
+    /// Scale factor, out of `SCALE_NORMAL`
+    scale: f64,
+    /// Points White scored
+    result: f64,
+}
+
+/// White's value of `term` in `breakdown` minus Black's
+fn term_diff(breakdown: &EvalBreakdown, term: &Term) -> f64 {
+    let [white, black] = (term.value)(breakdown);
+    (white - black) as f64
+}
+
+impl Sample {
+    fn new(board: &Board, result: f64) -> Self {
+        let breakdown = evaluation::eval_breakdown(board);
+        // Each blended term's middlegame and endgame values, weighted by
+        // how much of each the game phase takes
+        let middlegame = evaluation::eval_breakdown_at(board, MAX_PHASE);
+        let endgame = evaluation::eval_breakdown_at(board, 0);
+        let mg = breakdown.phase as f64 / MAX_PHASE as f64;
+
+        let mut features = [0.0; PARAMS];
+        for (i, &(piece, _, mg_value, eg_value)) in PIECES.iter().enumerate() {
+            let count = |color: Color| {
+                (*board.pieces(piece) & *board.color_combined(color)).popcnt() as f64
+            };
+            let diff = count(Color::White) - count(Color::Black);
+            features[2 * i] = diff * mg_value as f64 * mg;
+            features[2 * i + 1] = diff * eg_value as f64 * (1.0 - mg);
+        }
+        for (i, term) in TERMS.iter().enumerate() {
+            let index = 2 * (PIECES.len() + i);
+            if term.tapered {
+                features[index] = term_diff(&middlegame, term) * mg;
+                features[index + 1] = term_diff(&endgame, term) * (1.0 - mg);
+            } else {
+                features[index] = term_diff(&breakdown, term);
+            }
+        }
+
+        let total = (breakdown.material[0] - breakdown.material[1]) as f64
+            + TERMS
+                .iter()
+                .map(|term| term_diff(&breakdown, term))
+                .sum::<f64>()
+            + (breakdown.mop_up[0] - breakdown.mop_up[1]) as f64;
+        Sample {
+            features,
+            fixed: total - features.iter().sum::<f64>(),
+            scale: breakdown.scale as f64 / SCALE_NORMAL as f64,
+            result,
+        }
+    }
+
+    /// Evaluation from White's point of view with the given values
+    fn eval(&self, weights: &[f64; PARAMS]) -> f64 {
+        let sum: f64 = self.features.iter().zip(weights).map(|(f, w)| f * w).sum();
+        (sum + self.fixed) * self.scale
+    }
+}
+
+/// Expected score for White of a position evaluated at `eval` centipawns
+fn sigmoid(eval: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval / 400.0))
+}
+
+/// Mean squared difference between the game results and the scores the
+/// evaluation predicts
+fn error(samples: &[Sample], weights: &[f64; PARAMS], k: f64) -> f64 {
+    let total: f64 = samples
+        .iter()
+        .map(|s| (s.result - sigmoid(s.eval(weights), k)).powi(2))
+        .sum();
+    total / samples.len().max(1) as f64
+}
+
+/// The `k` for which the untuned evaluation predicts the results best,
+/// found by narrowing down the step around the best value so far
+fn fit_k(samples: &[Sample], weights: &[f64; PARAMS]) -> f64 {
+    let mut best = 1.0;
+    let mut step = 0.5;
+    for _ in 0..20 {
+        best = [best - step, best, best + step]
+            .into_iter()
+            .filter(|&k| k > 0.0)
+            .min_by(|&a, &b| error(samples, weights, a).total_cmp(&error(samples, weights, b)))
+            .expect("best is positive");
+        step /= 2.0;
+    }
+    best
+}
+
+/// Gradient of `error` with respect to each tuned value
+fn gradient(samples: &[Sample], weights: &[f64; PARAMS], k: f64) -> [f64; PARAMS] {
+    let mut grad = [0.0; PARAMS];
+    let slope = k * std::f64::consts::LN_10 / 400.0;
+    for sample in samples {
+        let s = sigmoid(sample.eval(weights), k);
+        let d = -2.0 * (sample.result - s) * s * (1.0 - s) * slope * sample.scale;
+        for (g, f) in grad.iter_mut().zip(&sample.features) {
+            *g += d * f;
+        }
+    }
+    let n = samples.len().max(1) as f64;
+    grad.map(|g| g / n)
+}
+
+/// Outcome of a tuning run
+pub struct TuneResult {
+    pub positions: usize,
+    pub k: f64,
+    pub initial_error: f64,
+    pub final_error: f64,
+    weights: [f64; PARAMS],
+}
+
+impl TuneResult {
+    /// The tuned piece values and the constants of every other term scaled
+    /// by its multipliers, as declarations to paste into `evaluation.rs`
+    pub fn to_constants(&self) -> String {
+        let mut out = format!(
+            "// Tuned on {} positions, K = {:.3}, error {:.6} -> {:.6}\n",
+            self.positions, self.k, self.initial_error, self.final_error
+        );
+        for (i, &(_, name, mg_value, eg_value)) in PIECES.iter().enumerate() {
+            out += &format!(
+                "pub const {}: i32 = {};\n",
+                name,
+                scaled(mg_value, self.weights[2 * i])
+            );
+            out += &format!(
+                "pub const {}_EG: i32 = {};\n",
+                name,
+                scaled(eg_value, self.weights[2 * i + 1])
+            );
+        }
+        for (i, term) in TERMS.iter().enumerate() {
+            let index = 2 * (PIECES.len() + i);
+            let mg = self.weights[index];
+            // A term tuned by one multiplier scales all its constants by it
+            let eg = if term.tapered {
+                self.weights[index + 1]
+            } else {
+                mg
+            };
+            out += &format!(
+                "\n// {}: middlegame x{:.3}, endgame x{:.3}\n",
+                term.name, mg, eg
+            );
+            for constant in term.constants {
+                out += &constant.declaration(mg, eg);
+                out += "\n";
+            }
+        }
+        out
+    }
+}
+
+/// Texel tuning: fit `k` to the current evaluation, then minimize the
+/// prediction error over every tuned value at once with Adam
+fn tune(samples: &[Sample], iterations: usize) -> TuneResult {
+    let mut weights = [1.0; PARAMS];
+    let k = fit_k(samples, &weights);
+    let initial_error = error(samples, &weights, k);
+
+    let mut m = [0.0; PARAMS];
+    let mut v = [0.0; PARAMS];
+    for t in 1..=iterations {
+        let grad = gradient(samples, &weights, k);
+        for (i, weight) in weights.iter_mut().enumerate() {
+            m[i] = BETA1 * m[i] + (1.0 - BETA1) * grad[i];
+            v[i] = BETA2 * v[i] + (1.0 - BETA2) * grad[i] * grad[i];
+            let m_hat = m[i] / (1.0 - BETA1.powi(t as i32));
+            let v_hat = v[i] / (1.0 - BETA2.powi(t as i32));
+            *weight -= LEARNING_RATE * m_hat / (v_hat.sqrt() + EPSILON);
+        }
+        if t % REPORT_INTERVAL == 0 {
+            uci_println!(
+                "info string tune iteration {}/{} error {:.6}",
+                t,
+                iterations,
+                error(samples, &weights, k)
+            );
+        }
+    }
+
+    TuneResult {
+        positions: samples.len(),
+        k,
+        initial_error,
+        final_error: error(samples, &weights, k),
+        weights,
+    }
+}
+
+/// Points White scored, from a result written as in PGN ("1-0"), or as a
+/// number in brackets ("[0.5]")
+fn parse_result(text: &str) -> Option<f64> {
+    let text = text.trim_matches(|c| c == '"' || c == ';');
+    match text {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        _ => text
+            .strip_prefix('[')
+            .and_then(|t| t.strip_suffix(']'))
+            .and_then(|t| t.parse().ok())
+            .filter(|r| (0.0..=1.0).contains(r)),
+    }
+}
+
+/// A position worth tuning on: one where the static evaluation is what the
+/// search would return, so with the game still going, not in check, and
+/// with no capture winning material nor a promotion for the side to move,
+/// which quiescence would play first
+fn is_settled(board: &Board) -> bool {
+    board.status() == BoardStatus::Ongoing
+        && board.checkers().popcnt() == 0
+        && MoveGen::new_legal(board).all(|mv| {
+            mv.get_promotion().is_none()
+                && !(engine::is_capture(board, mv) && engine::wins_exchange(board, mv))
+        })
+}
+
+/// Positions of an EPD file, each line the four FEN fields followed by the
+/// result anywhere after them, e.g. `c9 "1-0";` or `[0.5]`
+fn parse_epd(text: &str) -> Vec<(Board, f64)> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 5 {
+                return None;
+            }
+            let result = fields[4..].iter().find_map(|f| parse_result(f))?;
+            let fen = format!("{} 0 1", fields[..4].join(" "));
+            let board = engine::position_boards(&fen, &[]).ok()?.pop()?;
+            Some((board, result))
+        })
+        .collect()
+}
+
+/// Moves of a PGN game's movetext in SAN, without move numbers, comments,
+/// variations, annotations or the result
+fn movetext_moves(movetext: &str) -> Vec<String> {
+    let mut moves = Vec::new();
+    let mut depth = 0;
+    let mut cleaned = String::new();
+    for line in movetext.lines() {
+        let mut in_comment = false;
+        for c in line.chars() {
+            match c {
+                '{' => in_comment = true,
+                '}' => in_comment = false,
+                ';' if !in_comment && depth == 0 => break,
+                '(' if !in_comment => depth += 1,
+                ')' if !in_comment => depth -= 1,
+                _ if !in_comment && depth == 0 => cleaned.push(c),
+                _ => {}
+            }
+        }
+        cleaned.push(' ');
+    }
+    for token in cleaned.split_whitespace() {
+        if token == "*" || parse_result(token).is_some() {
+            continue;
+        }
+        // "12." and "12..." come before moves, sometimes without a space
+        let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        let token = token.trim_end_matches(['+', '#', '!', '?']);
+        if token.is_empty() || token.starts_with('$') {
+            continue;
+        }
+        moves.push(token.to_string());
+    }
+    moves
+}
+
+/// Positions of the games in a PGN file, each labeled with its game's
+/// result. Games without a decisive or drawn result are left out, as are
+/// the opening plies.
+fn parse_pgn(text: &str) -> Vec<(Board, f64)> {
+    let mut positions = Vec::new();
+    let mut result = None;
+    let mut fen = START_POSITION.to_string();
+    let mut movetext = String::new();
+
+    let mut finish_game = |result: Option<f64>, fen: &str, movetext: &str| {
+        let Some(result) = result else { return };
+        let Some(mut board) = engine::position_boards(fen, &[])
+            .ok()
+            .and_then(|mut b| b.pop())
+        else {
+            return;
+        };
+        for (ply, san) in movetext_moves(movetext).iter().enumerate() {
+            let Ok(mv) = moves::parse_san_move(&board, san) else {
+                return;
+            };
+            board = board.make_move_new(mv);
+            if ply + 1 >= OPENING_PLIES {
+                positions.push((board, result));
+            }
+        }
+    };
+
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(tag) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            // A tag after movetext starts the next game
+            if !movetext.trim().is_empty() {
+                finish_game(result, &fen, &movetext);
+                result = None;
+                fen = START_POSITION.to_string();
+                movetext.clear();
+            }
+            let (name, value) = tag.split_once(' ').unwrap_or((tag, ""));
+            let value = value.trim().trim_matches('"');
+            match name {
+                "Result" => result = parse_result(value),
+                "FEN" => fen = value.to_string(),
+                _ => {}
+            }
+        } else {
+            movetext += line;
+            movetext.push('\n');
+        }
+    }
+    if !movetext.trim().is_empty() {
+        finish_game(result, &fen, &movetext);
+    }
+    positions
+}
+
+/// Labeled positions of a dataset: PGN games if it starts with a tag,
+/// otherwise EPD lines. Positions that aren't settled (see `is_settled`)
+/// are left out.
+pub fn load_dataset(text: &str) -> Vec<(Board, f64)> {
+    let positions = if text.trim_start().starts_with('[') {
+        parse_pgn(text)
+    } else {
+        parse_epd(text)
+    };
+    positions
+        .into_iter()
+        .filter(|(board, _)| is_settled(board))
+        .collect()
+}
+
+/// Tune the evaluation on the dataset at `spec.dataset_path` and write the
+/// tuned values to `spec.output_path`
+pub fn run_tune(spec: &TuneSpec) -> Result<TuneResult, String> {
+    let text = std::fs::read_to_string(&spec.dataset_path)
+        .map_err(|e| format!("cannot read {}: {}", spec.dataset_path, e))?;
+    let samples: Vec<Sample> = load_dataset(&text)
+        .iter()
+        .map(|(board, result)| Sample::new(board, *result))
+        .collect();
+    if samples.is_empty() {
+        return Err(format!("no labeled positions in {}", spec.dataset_path));
+    }
+    uci_println!("info string tune: {} positions", samples.len());
+
+    let result = tune(&samples, spec.iterations);
+    std::fs::write(&spec.output_path, result.to_constants())
+        .map_err(|e| format!("cannot write {}: {}", spec.output_path, e))?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse_tune_command() {
+        assert_eq!(
+            parse_tune_command(&["tune", "data.epd", "iterations", "50", "output", "out.txt"]),
+            Ok(TuneSpec {
+                dataset_path: "data.epd".to_string(),
+                iterations: 50,
+                output_path: "out.txt".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_tune_command(&["tune", "data.epd"])
+                .unwrap()
+                .iterations,
+            DEFAULT_TUNE_ITERATIONS
+        );
+        assert!(parse_tune_command(&["tune"]).is_err());
+        assert!(parse_tune_command(&["tune", "data.epd", "iterations", "many"]).is_err());
+        assert!(parse_tune_command(&["tune", "data.epd", "speed", "1"]).is_err());
+    }
+
+    #[test]
+    fn test_load_epd() {
+        let text = "\
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - c9 \"1/2-1/2\";
+4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 [1.0]
+r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4 [0.5]
+r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4 1-0
+r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3 [0.5]
+not a position [1.0]
+4k3/8/8/8/8/8/4P3/4K3 w - - 0 1
+";
+        let positions = load_dataset(text);
+        // The mated position, the one where Qxf7 wins a pawn and the lines
+        // without a board or result are left out
+        let results: Vec<f64> = positions.iter().map(|&(_, r)| r).collect();
+        assert_eq!(results, vec![0.5, 1.0, 0.5]);
+        assert_eq!(positions[0].0.side_to_move(), Color::Black);
+    }
+
+    #[test]
+    fn test_load_pgn() {
+        let text = "\
+[Event \"Test\"]
+[Result \"0-1\"]
+
+1. f3 e5 2. g4?? {A blunder} (2. Kf2 d5) 2... Qh4# 0-1
+
+[Event \"Test\"]
+[Result \"1-0\"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 1-0
+
+[Event \"Unfinished\"]
+[Result \"*\"]
+
+1. d4 d5 2. c4 e6 3. Nc3 Nf6 4. Bg5 Be7 *
+";
+        let positions = load_dataset(text);
+        // The first game ends in mate before the opening is over, and the
+        // last has no result: the second game gives plies 8 to 10, but for
+        // ply 9, where Nxe4 wins a pawn
+        assert_eq!(positions.len(), 2);
+        assert!(positions.iter().all(|&(_, r)| r == 1.0));
+        let last =
+            Board::from_str("r1bqk2r/1pppbppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 w kq - 4 6")
+                .unwrap();
+        assert_eq!(positions[1].0.get_hash(), last.get_hash());
+    }
+
+    #[test]
+    fn test_untuned_sample_matches_eval() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+            "8/8/4k3/8/8/3BB3/8/4K3 w - - 0 1",
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            let sample = Sample::new(&board, 0.5);
+            let total = evaluation::eval_breakdown(&board).total() as f64;
+            assert!(
+                (sample.eval(&[1.0; PARAMS]) - total).abs() <= 1.0,
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn test_tuning_reduces_error() {
+        // White always wins with the extra pawn, so the pawn is worth more
+        let samples: Vec<Sample> = [
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/3P4/4K3 b - - 0 1",
+            "3k4/8/8/8/8/2P5/8/3K4 w - - 0 1",
+        ]
+        .iter()
+        .map(|fen| Sample::new(&Board::from_str(fen).unwrap(), 1.0))
+        .collect();
+        let result = tune(&samples, 200);
+        assert!(result.final_error < result.initial_error);
+        // Only the endgame pawn value, with no pieces left
+        assert!(result.weights[1] > 1.0);
+        assert_eq!(result.weights[0], 1.0);
+        let constants = result.to_constants();
+        assert!(constants.contains("pub const PAWN_VAL: i32 = 100;"));
+        assert!(constants.contains("pub const PAWN_VAL_EG: i32 = "));
+        assert!(constants.contains("pub(crate) const HOLE: (i32, i32) = "));
+    }
+}
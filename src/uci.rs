@@ -0,0 +1,1597 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{Board, ChessMove, Color, Piece, Square, ALL_FILES, ALL_RANKS};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::adjudication::{self, MercyRule};
+use crate::bench::{self, DEFAULT_BENCH_DEPTH};
+use crate::book::{self, Book};
+use crate::debug_log;
+use crate::engine::{
+    self, EngineOptions, EngineState, OutputFormat, SearchLimits, DEFAULT_HASH_MB,
+    DEFAULT_MOVE_OVERHEAD_MS, DEFAULT_TIME_CHECK_INTERVAL, MAX_HASH_MB,
+};
+use crate::evaluation::{self, GameResult};
+use crate::external::ExternalEngine;
+use crate::game::{self, GamePlayer, Player};
+use crate::kpk;
+use crate::moves;
+use crate::nnue;
+use crate::perft;
+use crate::timeman::{self, NodeClock, TimeBudget};
+use crate::tournament;
+use crate::tt;
+use crate::tune;
+use crate::uci_println;
+use crate::variant::Variant;
+
+/// The starting position FEN
+pub const START_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Search parameter file, loaded at startup if present
+pub const PARAMS_FILE: &str = "./params.txt";
+
+/// Option settings saved while `PersistOptions` is on, replayed at startup
+pub const OPTIONS_FILE: &str = "./options.txt";
+
+/// Default directory written by `export-config`
+const CONFIG_EXPORT_DIR: &str = "./xewali-config";
+
+/// Stack size of the search thread; the search recurses once per ply
+const SEARCH_THREAD_STACK_SIZE: usize = 16 * 1024 * 1024;
+
+/// What a finished background search hands back to the session
+struct SearchOutcome {
+    engine_state: EngineState,
+    mercy_rule: MercyRule,
+    eval: i32,
+    best_move: Option<ChessMove>,
+    nodes: u64,
+}
+
+/// A UCI session: the current game and all engine settings
+pub struct Uci {
+    book: Arc<Book>,
+    board: Board,
+    position_history: Vec<u64>,
+    /// Positions before each move of the game, most recent last, for `undo`
+    board_stack: Vec<Board>,
+    /// Halfmove clock of the first position in `board_stack`
+    start_halfmove_clock: u32,
+    /// Full move number of the first position in `board_stack`
+    start_fullmove_number: u32,
+    current_evaluation: i32,
+    /// Best move reported by the last search
+    last_best_move: Option<ChessMove>,
+    options: EngineOptions,
+    engine_state: EngineState,
+    mercy_rule: MercyRule,
+    /// Whether the hash size follows the machine's available memory
+    auto_hash: bool,
+    /// Whether the time check interval was calibrated for this machine
+    calibrate: bool,
+    /// Whether option changes are saved to `options_file`
+    persist_options: bool,
+    /// File named by the `Debug Log File` option, empty when not logging
+    debug_log_file: String,
+    options_file: String,
+    /// Search started by `go` that is still running in the background
+    search: Option<JoinHandle<SearchOutcome>>,
+    /// Makes the running search return its best move (`stop`)
+    stop_signal: Arc<AtomicBool>,
+    /// Commands that change the session, held back until the running search
+    /// ends
+    pending_commands: Vec<String>,
+    /// The engine's own clock under `nodestime`
+    node_clock: NodeClock,
+}
+
+impl Uci {
+    pub fn new(book: Book) -> Self {
+        let board = Board::default();
+        let engine_state = EngineState::new();
+        Uci {
+            book: Arc::new(book),
+            board,
+            position_history: vec![board.get_hash()],
+            board_stack: Vec::new(),
+            start_halfmove_clock: 0,
+            start_fullmove_number: 1,
+            current_evaluation: 0,
+            last_best_move: None,
+            options: EngineOptions::default(),
+            stop_signal: engine_state.stop_signal(),
+            engine_state,
+            mercy_rule: MercyRule::default(),
+            auto_hash: false,
+            calibrate: false,
+            persist_options: false,
+            debug_log_file: String::new(),
+            options_file: OPTIONS_FILE.to_string(),
+            search: None,
+            pending_commands: Vec::new(),
+            node_clock: NodeClock::default(),
+        }
+    }
+
+    /// Wait for the background search, if any, to finish and take back the
+    /// state it was using, then run the commands held back meanwhile
+    pub fn wait_for_search(&mut self) {
+        if let Some(handle) = self.search.take() {
+            let outcome = handle.join().expect("search thread panicked");
+            self.engine_state = outcome.engine_state;
+            self.mercy_rule = outcome.mercy_rule;
+            self.current_evaluation = outcome.eval;
+            self.last_best_move = outcome.best_move;
+            self.node_clock.charge(outcome.nodes);
+            for line in std::mem::take(&mut self.pending_commands) {
+                self.execute_command(&line);
+            }
+        }
+    }
+
+    /// Stop the background search, if any; it still reports its best move
+    pub fn stop_search(&mut self) {
+        if self.search.is_some() {
+            self.stop_signal.store(true, Ordering::Relaxed);
+            self.wait_for_search();
+        }
+    }
+
+    /// Halfmove clock of the current position, for the fifty-move rule
+    fn halfmove_clock(&self) -> u32 {
+        let mut boards = self.board_stack.clone();
+        boards.push(self.board);
+        adjudication::halfmove_clock(self.start_halfmove_clock, &boards)
+    }
+
+    /// Full move number of the current position, as in a FEN
+    fn move_number(&self) -> u32 {
+        let start = self.board_stack.first().unwrap_or(&self.board);
+        // The number goes up after each of Black's moves
+        let plies = self.board_stack.len() as u32 + u32::from(start.side_to_move() == Color::Black);
+        self.start_fullmove_number + plies / 2
+    }
+
+    /// Announce when the game set up by `position` is over by the rules
+    fn report_game_over(&self) {
+        if let Some((result, reason)) = adjudication::rules_result(
+            &self.board,
+            &self.position_history,
+            self.halfmove_clock(),
+            self.options.variant,
+        ) {
+            uci_println!(
+                "info string game over: {} by {}",
+                game::result_text(result),
+                reason
+            );
+            let _ = io::stdout().flush();
+        }
+    }
+
+    /// Size the transposition table from the memory available on this machine
+    fn apply_auto_hash(&mut self) {
+        match engine::available_memory_mb() {
+            Some(available) => {
+                self.engine_state
+                    .set_hash_mb(engine::auto_hash_mb(available));
+                uci_println!(
+                    "info string hash set to {} MB ({} MB available)",
+                    self.engine_state.hash_mb(),
+                    available
+                );
+            }
+            None => uci_println!("info string available memory unknown, keeping hash size"),
+        }
+    }
+
+    /// Load search parameters from a parameter file, if it exists
+    pub fn load_params_file(&mut self, path: &str) {
+        if let Ok(text) = std::fs::read_to_string(path) {
+            self.engine_state.params.load_param_string(&text);
+        }
+    }
+
+    /// Current value of every UCI option, as (name, value)
+    fn option_settings(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Hash", self.engine_state.hash_mb().to_string()),
+            ("AutoHash", self.auto_hash.to_string()),
+            ("Move Overhead", self.options.move_overhead_ms.to_string()),
+            ("nodestime", self.options.nodes_time.to_string()),
+            ("Contempt", self.options.contempt.to_string()),
+            ("Handicap", self.options.handicap.to_string()),
+            ("MercyThreshold", self.mercy_rule.threshold.to_string()),
+            ("MercyPlies", self.mercy_rule.plies.to_string()),
+            ("OwnBook", self.options.own_book.to_string()),
+            ("UCI_AnalyseMode", self.options.analyse_mode.to_string()),
+            ("UCI_Variant", self.options.variant.name().to_string()),
+            ("UCI_Chess960", self.options.chess960.to_string()),
+            (
+                "OutputFormat",
+                self.options.output_format.name().to_string(),
+            ),
+            ("Calibrate", self.calibrate.to_string()),
+            ("EvalFile", or_empty(self.engine_state.eval_file())),
+            ("EvalSymmetryCheck", self.options.check_symmetry.to_string()),
+            ("Debug Log File", or_empty(&self.debug_log_file)),
+            // Last, so replaying a saved file does not rewrite it midway
+            ("PersistOptions", self.persist_options.to_string()),
+        ]
+    }
+
+    /// Option settings as `setoption` commands, one per line
+    fn option_commands(&self) -> String {
+        self.option_settings()
+            .iter()
+            .map(|(name, value)| format!("setoption name {} value {}\n", name, value))
+            .collect()
+    }
+
+    /// Apply the `setoption` lines of a saved option file. Only option
+    /// settings are replayed, never arbitrary commands.
+    fn replay_options(&mut self, text: &str) {
+        for line in text.lines().filter(|l| l.starts_with("setoption ")) {
+            self.handle_command(line);
+        }
+    }
+
+    /// Restore the options saved by `PersistOptions` in `path`, if it exists,
+    /// and keep saving there from now on
+    pub fn load_options_file(&mut self, path: &str) {
+        self.options_file = path.to_string();
+        if let Ok(text) = std::fs::read_to_string(path) {
+            self.replay_options(&text);
+        }
+    }
+
+    /// Save the current option settings if `PersistOptions` is on
+    fn persist_option_settings(&self) {
+        if self.persist_options {
+            if let Err(e) = std::fs::write(&self.options_file, self.option_commands()) {
+                uci_println!("info string could not write {}: {}", self.options_file, e);
+            }
+        }
+    }
+
+    /// Write the loaded book, the search parameters and all option settings
+    /// into `dir`, along with a manifest describing them
+    fn export_config(&self, dir: &str) -> io::Result<()> {
+        let dir = std::path::Path::new(dir);
+        std::fs::create_dir_all(dir)?;
+
+        book::save_binary(&self.book, &dir.join("book.bin").to_string_lossy())?;
+        std::fs::write(
+            dir.join("params.txt"),
+            self.engine_state.params.to_param_string(),
+        )?;
+
+        std::fs::write(dir.join("options.txt"), self.option_commands())?;
+
+        let manifest = format!(
+            "engine Xewali 1.0\n\
+             book book.bin ({} positions)\n\
+             params params.txt\n\
+             options options.txt\n\
+             # Load on another machine with: import-config <dir>\n",
+            self.book.len()
+        );
+        std::fs::write(dir.join("manifest.txt"), manifest)
+    }
+
+    /// Load a configuration written by `export_config`. Missing files are skipped.
+    fn import_config(&mut self, dir: &str) -> io::Result<()> {
+        let dir = std::path::Path::new(dir);
+        if !dir.join("manifest.txt").is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no manifest.txt in configuration directory",
+            ));
+        }
+
+        let book_path = dir.join("book.bin");
+        if book_path.is_file() {
+            self.book = Arc::new(book::load_binary(&book_path.to_string_lossy())?);
+        }
+        self.load_params_file(&dir.join("params.txt").to_string_lossy());
+        if let Ok(options) = std::fs::read_to_string(dir.join("options.txt")) {
+            self.replay_options(&options);
+        }
+        Ok(())
+    }
+
+    /// Handle one line of input. Returns false once the session should end.
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        if !line.trim().is_empty() {
+            debug_log::received(line);
+        }
+        self.execute_command(line)
+    }
+
+    /// Carry out one command, already logged by `handle_command`
+    fn execute_command(&mut self, line: &str) -> bool {
+        let mut stdout = io::stdout();
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        if tokens.is_empty() {
+            return true;
+        }
+
+        // A command arriving during a search: a new game, position or search
+        // replaces the running one, which still reports its best move so every
+        // `go` gets exactly one `bestmove`. Commands that only look at the
+        // session are answered at once. Any other is held back until the
+        // search ends, as waiting for it here would leave `stop` unread
+        // behind a `go infinite`.
+        match tokens[0] {
+            "isready" | "stop" | "quit" | "debug" | "d" | "display" | "eval" | "moves" => {}
+            "go" | "position" | "ucinewgame" => {
+                if self.search.is_some() && self.options.debug {
+                    uci_println!(
+                        "info string debug '{}' interrupts the running search",
+                        tokens[0]
+                    );
+                }
+                self.stop_search();
+            }
+            _ => {
+                if self.search.as_ref().is_some_and(|h| h.is_finished()) {
+                    self.wait_for_search();
+                }
+                if self.search.is_some() {
+                    if self.options.debug {
+                        uci_println!(
+                            "info string debug '{}' waits for the running search",
+                            tokens[0]
+                        );
+                    }
+                    self.pending_commands.push(line.to_string());
+                    return true;
+                }
+            }
+        }
+
+        match tokens[0] {
+            "uci" => {
+                uci_println!("id name Xewali 1.0");
+                uci_println!("id author Himangshu Saikia");
+                uci_println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_MB,
+                    MAX_HASH_MB
+                );
+                uci_println!("option name AutoHash type check default false");
+                uci_println!(
+                    "option name Move Overhead type spin default {} min 0 max 5000",
+                    DEFAULT_MOVE_OVERHEAD_MS
+                );
+                uci_println!("option name nodestime type spin default 0 min 0 max 100000");
+                uci_println!("option name Contempt type spin default 0 min -500 max 500");
+                uci_println!("option name Handicap type spin default 0 min 0 max 100");
+                uci_println!("option name MercyThreshold type spin default 0 min 0 max 100000");
+                uci_println!("option name MercyPlies type spin default 10 min 1 max 1000");
+                uci_println!("option name OwnBook type check default true");
+                uci_println!("option name Debug Log File type string default <empty>");
+                uci_println!("option name EvalFile type string default <empty>");
+                uci_println!("option name PersistOptions type check default false");
+                uci_println!("option name Calibrate type check default false");
+                let formats: Vec<String> = OutputFormat::ALL
+                    .iter()
+                    .map(|f| format!("var {}", f.name()))
+                    .collect();
+                uci_println!(
+                    "option name OutputFormat type combo default {} {}",
+                    OutputFormat::Uci.name(),
+                    formats.join(" ")
+                );
+                uci_println!("option name UCI_AnalyseMode type check default false");
+                uci_println!("option name UCI_Chess960 type check default false");
+                uci_println!("option name EvalSymmetryCheck type check default false");
+                let variants: Vec<String> = Variant::ALL
+                    .iter()
+                    .map(|v| format!("var {}", v.name()))
+                    .collect();
+                uci_println!(
+                    "option name UCI_Variant type combo default {} {}",
+                    Variant::Standard.name(),
+                    variants.join(" ")
+                );
+                for option in engine::SearchParams::uci_options() {
+                    uci_println!("{}", option);
+                }
+                uci_println!("uciok");
+                let _ = stdout.flush();
+            }
+
+            "ucinewgame" => {
+                self.board = Board::default();
+                self.position_history = vec![self.board.get_hash()];
+                self.board_stack.clear();
+                self.start_halfmove_clock = 0;
+                self.start_fullmove_number = 1;
+                self.current_evaluation = 0;
+                self.engine_state.clear();
+                self.mercy_rule.reset();
+                self.node_clock = NodeClock::default();
+            }
+
+            "debug" => {
+                // debug [ on | off ]
+                self.options.debug = tokens.get(1).is_none_or(|&t| t == "on");
+            }
+
+            "setoption" => {
+                if let Some((name, value)) = parse_setoption_command(&tokens) {
+                    if name.eq_ignore_ascii_case("AutoHash") {
+                        self.auto_hash = value == "true";
+                        if self.auto_hash {
+                            self.apply_auto_hash();
+                        }
+                    } else if name.eq_ignore_ascii_case("MercyThreshold") {
+                        if let Ok(cp) = value.parse::<i32>() {
+                            self.mercy_rule.threshold = cp.clamp(0, 100000);
+                        }
+                    } else if name.eq_ignore_ascii_case("MercyPlies") {
+                        if let Ok(plies) = value.parse::<u32>() {
+                            self.mercy_rule.plies = plies.clamp(1, 1000);
+                        }
+                    } else if name.eq_ignore_ascii_case("Calibrate") {
+                        self.calibrate = value == "true";
+                        let (interval, bucket_size) = if self.calibrate {
+                            let calibration = bench::calibrate(&self.engine_state.params);
+                            uci_println!(
+                                "info string calibrated: {} nps, time check every {} nodes",
+                                calibration.nps,
+                                calibration.time_check_interval
+                            );
+                            uci_println!(
+                                "info string bucket reads {:?} ns, hash buckets of {} entries",
+                                calibration.bucket_latency_ns,
+                                calibration.tt_bucket_size
+                            );
+                            (calibration.time_check_interval, calibration.tt_bucket_size)
+                        } else {
+                            (DEFAULT_TIME_CHECK_INTERVAL, tt::DEFAULT_BUCKET_SIZE)
+                        };
+                        self.engine_state.set_time_check_interval(interval);
+                        self.engine_state.set_tt_bucket_size(bucket_size);
+                    } else if name.eq_ignore_ascii_case("PersistOptions") {
+                        let persist = value == "true";
+                        if self.persist_options && !persist {
+                            // Start from the defaults again next time
+                            let _ = std::fs::remove_file(&self.options_file);
+                        }
+                        self.persist_options = persist;
+                    } else if name.eq_ignore_ascii_case("Debug Log File") {
+                        // A saved setting replayed unchanged leaves the log
+                        // as it is
+                        let path = value.trim();
+                        let path = if path == "<empty>" { "" } else { path };
+                        if path != self.debug_log_file {
+                            match debug_log::open(path) {
+                                Ok(()) => self.debug_log_file = path.to_string(),
+                                Err(e) => {
+                                    uci_println!("info string could not open {}: {}", path, e)
+                                }
+                            }
+                        }
+                    } else {
+                        engine::set_engine_option(
+                            &mut self.options,
+                            &mut self.engine_state,
+                            &name,
+                            &value,
+                        );
+                        if name.eq_ignore_ascii_case("nodestime") {
+                            // The clock in nodes is set again at the new rate
+                            self.node_clock = NodeClock::default();
+                        }
+                    }
+                    self.persist_option_settings();
+                }
+            }
+
+            "saveparams" => {
+                // Custom command: write the search parameters to the parameter file
+                let path = tokens.get(1).copied().unwrap_or(PARAMS_FILE);
+                let text = self.engine_state.params.to_param_string();
+                if let Err(e) = std::fs::write(path, text) {
+                    uci_println!("info string could not write {}: {}", path, e);
+                }
+                let _ = stdout.flush();
+            }
+
+            "export-config" => {
+                // Custom command: bundle book, parameters and options into a directory
+                let dir = tokens.get(1).copied().unwrap_or(CONFIG_EXPORT_DIR);
+                match self.export_config(dir) {
+                    Ok(()) => uci_println!("info string exported configuration to {}", dir),
+                    Err(e) => uci_println!("info string could not export to {}: {}", dir, e),
+                }
+                let _ = stdout.flush();
+            }
+
+            "import-config" => {
+                // Custom command: load a bundle written by export-config
+                let dir = tokens.get(1).copied().unwrap_or(CONFIG_EXPORT_DIR);
+                match self.import_config(dir) {
+                    Ok(()) => uci_println!("info string imported configuration from {}", dir),
+                    Err(e) => uci_println!("info string could not import {}: {}", dir, e),
+                }
+                let _ = stdout.flush();
+            }
+
+            "bench" => {
+                // Custom command: fixed-depth search over a built-in position set
+                let depth = tokens
+                    .get(1)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_BENCH_DEPTH)
+                    .max(1);
+                let result = bench::run_bench(depth, &self.engine_state.params);
+                uci_println!("===========================");
+                uci_println!("Positions      : {}", result.positions);
+                uci_println!("Depth          : {}", depth);
+                uci_println!("Total time (ms): {}", result.elapsed_ms);
+                uci_println!("Nodes searched : {}", result.nodes);
+                uci_println!("Nodes/second   : {}", result.nps());
+                let _ = stdout.flush();
+            }
+
+            "perft" | "divide" => {
+                // Custom commands: count legal move tree leaves from the
+                // current position, per root move for `divide`
+                let depth = tokens.get(1).and_then(|s| s.parse().ok()).unwrap_or(1);
+                let start = std::time::Instant::now();
+                // At depth 0 both count the current position alone
+                let nodes = if tokens[0] == "divide" && depth > 0 {
+                    let counts = perft::divide(&self.board, depth);
+                    for (mv, count) in &counts {
+                        uci_println!("{}: {}", mv, count);
+                    }
+                    uci_println!();
+                    counts.iter().map(|(_, count)| count).sum()
+                } else {
+                    perft::perft(&self.board, depth)
+                };
+                uci_println!("Nodes searched: {}", nodes);
+                uci_println!("Time (ms): {}", start.elapsed().as_millis());
+                let _ = stdout.flush();
+            }
+
+            "selfplay" => {
+                // Custom command: selfplay [depth D] [movetime MS], the engine
+                // plays both sides from the current position
+                let go = parse_go_params(&tokens);
+                let limits = game::game_limits(
+                    go.depth.map(|d| d.clamp(1, 64) as i32),
+                    go.movetime.map(|ms| ms.max(0) as u64),
+                );
+                let mut white = Player::with_settings("white", &self.options, &self.engine_state);
+                let mut black = Player::with_settings("black", &self.options, &self.engine_state);
+                let record = game::play_game(&self.board, &mut white, &mut black, &limits);
+
+                // Moves are numbered from the start of the self-play game
+                let mut board = self.board;
+                let black_first = board.side_to_move() == Color::Black;
+                let mut line = String::new();
+                for (i, &mv) in record.moves.iter().enumerate() {
+                    let number = (i + black_first as usize) / 2 + 1;
+                    if board.side_to_move() == Color::White {
+                        line += &format!("{}. ", number);
+                    } else if i == 0 {
+                        line += &format!("{}... ", number);
+                    }
+                    line += &moves::format_uci_move(&board, mv, self.options.chess960);
+                    line.push(' ');
+                    board = board.make_move_new(mv);
+                }
+                uci_println!("{}", line.trim_end());
+                uci_println!(
+                    "Result: {} ({}, {} plies)",
+                    game::result_text(record.result),
+                    record.reason,
+                    record.moves.len()
+                );
+                let _ = stdout.flush();
+            }
+
+            "match" => {
+                // Custom command: match PATH [games N] [depth D] [movetime MS]
+                // against another UCI engine, with the current settings
+                match tournament::parse_match_command(&tokens) {
+                    Ok(spec) => match ExternalEngine::start(&spec.engine_path) {
+                        Ok(mut opponent) => {
+                            let mut player =
+                                Player::with_settings("xewali", &self.options, &self.engine_state);
+                            let result = tournament::run_match(
+                                &mut player,
+                                &mut opponent,
+                                spec.games,
+                                &spec.limits,
+                            );
+                            tournament::print_match_result(&player.name, opponent.name(), &result);
+                        }
+                        Err(e) => uci_println!(
+                            "info string match: cannot start {}: {}",
+                            spec.engine_path,
+                            e
+                        ),
+                    },
+                    Err(e) => uci_println!("info string match: {}", e),
+                }
+                let _ = stdout.flush();
+            }
+
+            "tune" => {
+                // Custom command: tune PATH [iterations N] [output FILE]
+                // fits the evaluation to the results of labeled positions
+                match tune::parse_tune_command(&tokens)
+                    .and_then(|spec| tune::run_tune(&spec).map(|result| (spec, result)))
+                {
+                    Ok((spec, result)) => {
+                        uci_println!("===========================");
+                        uci_println!("Positions      : {}", result.positions);
+                        uci_println!("K              : {:.3}", result.k);
+                        uci_println!("Initial error  : {:.6}", result.initial_error);
+                        uci_println!("Final error    : {:.6}", result.final_error);
+                        uci_println!("Written to     : {}", spec.output_path);
+                    }
+                    Err(e) => uci_println!("info string tune: {}", e),
+                }
+                let _ = stdout.flush();
+            }
+
+            "tournament" => {
+                // Custom command: round robin between option/parameter sets
+                match tournament::parse_tournament_command(&tokens) {
+                    Ok(mut spec) => {
+                        let result = tournament::run_tournament(&mut spec);
+                        uci_println!("===========================");
+                        tournament::print_crosstable(&result);
+                    }
+                    Err(e) => uci_println!("info string tournament: {}", e),
+                }
+                let _ = stdout.flush();
+            }
+
+            "isready" => {
+                // The GUI waits for readyok, so the KPK bitbase is built here
+                // rather than in the first search that reaches the ending
+                kpk::init();
+                uci_println!("readyok");
+                let _ = stdout.flush();
+            }
+
+            "position" => {
+                let (fen, moves) = parse_position_command(&tokens);
+                match engine::position_boards(&fen, &moves) {
+                    Ok(mut boards) => {
+                        self.board = boards.pop().expect("at least the start position");
+                        self.position_history = boards
+                            .iter()
+                            .chain([&self.board])
+                            .map(|b| b.get_hash())
+                            .collect();
+                        self.board_stack = boards;
+                        self.start_halfmove_clock = adjudication::fen_halfmove_clock(&fen);
+                        self.start_fullmove_number = timeman::fen_fullmove_number(&fen);
+                        self.report_game_over();
+                    }
+                    Err(e) => {
+                        // Keep the previous position rather than guessing
+                        uci_println!("info string {}", e);
+                        let _ = stdout.flush();
+                    }
+                }
+            }
+
+            "go" => {
+                let mut go = parse_go_params(&tokens);
+                if self.options.nodes_time > 0 {
+                    // Time is allocated from the engine's clock in nodes
+                    // rather than the GUI's
+                    self.node_clock.apply(
+                        &mut go,
+                        self.board.side_to_move(),
+                        self.options.nodes_time,
+                    );
+                }
+                let budget = go_budget(
+                    &go,
+                    &self.board,
+                    self.move_number(),
+                    self.options.move_overhead_ms,
+                );
+                let limits = SearchLimits {
+                    // A plain "go depth N" or "go nodes N" searches to that
+                    // depth or node count however long it takes, and
+                    // "go infinite" until `stop`
+                    time: (!go.infinite
+                        && (go.depth.is_none() && go.nodes.is_none()
+                            || has_time_control(&go, self.board.side_to_move())))
+                    .then_some(budget.soft),
+                    depth: go.depth.map(|d| d.clamp(1, i32::MAX as i64) as i32),
+                    // Only time from the clock may be saved on an easy move
+                    flexible_time: !go.infinite
+                        && go.movetime.is_none()
+                        && has_time_control(&go, self.board.side_to_move()),
+                    max_time: budget.hard.filter(|_| !go.infinite),
+                    nodes: go.nodes.map(|n| n.max(1) as u64),
+                };
+
+                if self.options.output_format == OutputFormat::Uci {
+                    uci_println!("info Thinking...");
+                }
+                if self.options.debug {
+                    uci_println!(
+                        "info string debug allocated {:.0} ms from '{}'",
+                        budget.soft * 1000.0,
+                        tokens[1..].join(" ")
+                    );
+                }
+                let _ = stdout.flush();
+
+                // Search in the background so `stop` and `isready` are answered
+                // meanwhile. The thread reports the best move when it finishes.
+                self.stop_signal.store(false, Ordering::Relaxed);
+                let board = self.board;
+                let book = Arc::clone(&self.book);
+                let history = self.position_history.clone();
+                let halfmove_clock = self.halfmove_clock();
+                let options = self.options.clone();
+                // Left behind for the commands answered during the search
+                let placeholder = self.engine_state.placeholder();
+                let mut engine_state = std::mem::replace(&mut self.engine_state, placeholder);
+                let mut mercy_rule = self.mercy_rule.clone();
+                let stop_signal = Arc::clone(&self.stop_signal);
+                let handle = std::thread::Builder::new()
+                    .name("search".to_string())
+                    .stack_size(SEARCH_THREAD_STACK_SIZE)
+                    .spawn(move || {
+                        let result = engine::play_move(
+                            &board,
+                            &book,
+                            &limits,
+                            &history,
+                            halfmove_clock,
+                            &mut engine_state,
+                            &options,
+                        );
+
+                        // In infinite mode the GUI expects no bestmove before `stop`
+                        while go.infinite && !stop_signal.load(Ordering::Relaxed) {
+                            std::thread::sleep(Duration::from_millis(1));
+                        }
+
+                        if let Some(winner) = mercy_rule.update(result.score) {
+                            uci_println!("info string game over: {} by mercy rule", winner);
+                        }
+
+                        let best_move = result
+                            .best_move
+                            .map(|mv| moves::format_uci_move(&board, mv, options.chess960));
+                        let ponder = result.best_move.zip(result.ponder).map(|(mv, reply)| {
+                            moves::format_uci_move(
+                                &board.make_move_new(mv),
+                                reply,
+                                options.chess960,
+                            )
+                        });
+                        uci_println!(
+                            "{}",
+                            bestmove_line(
+                                best_move.as_deref(),
+                                ponder.as_deref(),
+                                options.output_format
+                            )
+                        );
+                        let _ = io::stdout().flush();
+
+                        SearchOutcome {
+                            engine_state,
+                            mercy_rule,
+                            eval: result.score,
+                            best_move: result.best_move,
+                            nodes: result.nodes,
+                        }
+                    })
+                    .expect("failed to spawn search thread");
+                self.search = Some(handle);
+            }
+
+            "stop" => {
+                self.stop_search();
+            }
+
+            "quit" => {
+                self.stop_search();
+                return false;
+            }
+
+            "eval" => {
+                // Custom command: static evaluation of the current position
+                // term by term, plus the score of the last search
+                print_eval_breakdown(&self.board);
+                if let Some(network) = self.engine_state.network() {
+                    let accumulator = nnue::Accumulator::new(network, &self.board);
+                    uci_println!(
+                        "NNUE          : {}",
+                        network.evaluate(&self.board, &accumulator)
+                    );
+                }
+                uci_println!("Last search   : {}", self.current_evaluation);
+                let _ = stdout.flush();
+            }
+
+            "moves" => {
+                // Custom command: legal moves in search order with their
+                // move-ordering scores
+                let moves =
+                    engine::ordered_moves(&self.board, &self.engine_state, self.options.variant);
+                for (mv, score, source) in &moves {
+                    uci_println!("{:<7}{:>8}  {}", mv.to_string(), score, source);
+                }
+                uci_println!("{} legal moves", moves.len());
+                let _ = stdout.flush();
+            }
+
+            "undo" => {
+                // Custom command: undo [N], take back the last N moves (default 1)
+                let count = tokens.get(1).and_then(|s| s.parse().ok()).unwrap_or(1);
+                for _ in 0..count {
+                    let Some(board) = self.board_stack.pop() else {
+                        uci_println!("info string nothing to undo");
+                        break;
+                    };
+                    self.board = board;
+                    self.position_history.pop();
+                }
+                self.mercy_rule.reset();
+                let _ = stdout.flush();
+            }
+
+            "flip" => {
+                // Custom command: hand the move to the other side
+                match self.board.null_move() {
+                    Some(board) => {
+                        // Repetitions only count with the same side to move,
+                        // so the earlier history no longer applies
+                        self.start_halfmove_clock = self.halfmove_clock();
+                        self.start_fullmove_number = self.move_number();
+                        self.board = board;
+                        self.position_history = vec![board.get_hash()];
+                        self.board_stack.clear();
+                        self.mercy_rule.reset();
+                    }
+                    None => uci_println!("info string cannot flip: side to move is in check"),
+                }
+                let _ = stdout.flush();
+            }
+
+            "d" | "display" => {
+                // Debug: display the current board, `d unicode` with piece glyphs
+                let unicode = tokens.get(1) == Some(&"unicode");
+                uci_println!("{}", board_diagram(&self.board, unicode));
+                uci_println!(
+                    "Static eval : {} cp (White's point of view)",
+                    self.engine_state
+                        .static_eval(&self.board, self.options.variant)
+                );
+                let _ = stdout.flush();
+            }
+
+            _ => {
+                // Unknown command, ignore
+            }
+        }
+
+        true
+    }
+}
+
+/// Parse the "position" command and return (fen, moves)
+pub fn parse_position_command(tokens: &[&str]) -> (String, Vec<String>) {
+    if tokens.len() < 2 {
+        return (START_POSITION.to_string(), vec![]);
+    }
+
+    let mut fen = String::new();
+    let mut moves = Vec::new();
+    let mut reading_fen = true;
+
+    if tokens[1] == "startpos" {
+        fen = START_POSITION.to_string();
+        reading_fen = false;
+    } else if tokens[1] == "fen" {
+        // FEN will be constructed from subsequent tokens
+    }
+
+    let start_idx = if tokens[1] == "startpos" || tokens[1] == "fen" {
+        2
+    } else {
+        1
+    };
+
+    for token in tokens.iter().skip(start_idx) {
+        if *token == "moves" {
+            reading_fen = false;
+            continue;
+        }
+
+        if reading_fen {
+            if !fen.is_empty() {
+                fen.push(' ');
+            }
+            fen.push_str(token);
+        } else {
+            moves.push(token.to_string());
+        }
+    }
+
+    // If no FEN was provided (shouldn't happen), use start position
+    if fen.is_empty() {
+        fen = START_POSITION.to_string();
+    }
+
+    (fen, moves)
+}
+
+/// The board as an 8x8 grid seen from White's side, with ranks and files
+/// labelled and pieces drawn as FEN letters or Unicode glyphs, followed by the
+/// FEN, side to move, castling rights and en passant square
+fn board_diagram(board: &Board, unicode: bool) -> String {
+    const SEPARATOR: &str = "  +---+---+---+---+---+---+---+---+";
+    let mut text = String::new();
+    for rank in ALL_RANKS.iter().rev() {
+        text += SEPARATOR;
+        text += &format!("\n{} |", rank.to_index() + 1);
+        for file in ALL_FILES {
+            let sq = Square::make_square(*rank, file);
+            let symbol = match (board.piece_on(sq), board.color_on(sq)) {
+                (Some(piece), Some(color)) => piece_symbol(piece, color, unicode),
+                _ => ' ',
+            };
+            text += &format!(" {} |", symbol);
+        }
+        text.push('\n');
+    }
+    text += SEPARATOR;
+    text += "\n    a   b   c   d   e   f   g   h\n\n";
+
+    let fen = board.to_string();
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    text += &format!("Fen         : {}\n", fen);
+    text += &format!(
+        "Side to move: {}\n",
+        if board.side_to_move() == Color::White {
+            "white"
+        } else {
+            "black"
+        }
+    );
+    text += &format!("Castling    : {}\n", fields.get(2).unwrap_or(&"-"));
+    text += &format!("En passant  : {}", fields.get(3).unwrap_or(&"-"));
+    text
+}
+
+/// FEN letter (uppercase for White) or Unicode glyph of a piece
+fn piece_symbol(piece: Piece, color: Color, unicode: bool) -> char {
+    const GLYPHS: [[char; 6]; 2] = [
+        ['♙', '♘', '♗', '♖', '♕', '♔'],
+        ['♟', '♞', '♝', '♜', '♛', '♚'],
+    ];
+    if unicode {
+        GLYPHS[color.to_index()][piece.to_index()]
+    } else {
+        piece.to_string(color).chars().next().unwrap_or('?')
+    }
+}
+
+/// Print each evaluation term for both colors, in centipawns from White's
+/// point of view
+fn print_eval_breakdown(board: &Board) {
+    let result = evaluation::has_game_ended(board);
+    if result != GameResult::Ongoing {
+        uci_println!("Game over     : {}", result);
+        return;
+    }
+
+    let b = evaluation::eval_breakdown(board);
+    uci_println!("Term              White     Black     Total");
+    let row = |name: &str, [white, black]: [i32; 2]| {
+        uci_println!("{:<14}{:>9}{:>10}{:>10}", name, white, black, white - black);
+    };
+    row("Material", b.material);
+    row("PST", b.pst);
+    row("Mobility", b.mobility);
+    row("King safety", b.king_safety);
+    row("Castling", b.castling);
+    row("Development", b.development);
+    row("Centre", b.center);
+    row("Weak squares", b.weak_squares);
+    row("Passed pawns", b.passed_pawns);
+    row("Pawn majority", b.pawn_majorities);
+    row("Bishop pair", b.bishop_pair);
+    row("Rooks", b.rooks);
+    row("Threats", b.threats);
+    row("Hanging", b.hanging);
+    row("Mop-up", b.mop_up);
+    uci_println!(
+        "{:<14}{:>29}",
+        "Scale",
+        format!("{}/{}", b.scale, evaluation::SCALE_NORMAL)
+    );
+    uci_println!("{:<14}{:>29}", "Total", b.total());
+    uci_println!(
+        "Phase         : {}/{} (0 = endgame)",
+        b.phase,
+        evaluation::MAX_PHASE
+    );
+}
+
+/// A string option's value, with the `<empty>` placeholder for no value
+fn or_empty(value: &str) -> String {
+    if value.is_empty() {
+        "<empty>".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Parse the "setoption" command and return (name, value).
+/// Both the name and the value may contain spaces.
+pub fn parse_setoption_command(tokens: &[&str]) -> Option<(String, String)> {
+    let name_idx = tokens.iter().position(|&t| t == "name")?;
+    let value_idx = tokens.iter().position(|&t| t == "value");
+
+    let name_end = value_idx.unwrap_or(tokens.len());
+    if name_end <= name_idx + 1 {
+        return None;
+    }
+    let name = tokens[name_idx + 1..name_end].join(" ");
+    let value = value_idx
+        .map(|i| tokens[i + 1..].join(" "))
+        .unwrap_or_default();
+
+    Some((name, value))
+}
+
+/// Sub-commands of a "go" command. Times are in milliseconds.
+#[derive(Debug, Default, PartialEq)]
+pub struct GoParams {
+    pub wtime: Option<i64>,
+    pub btime: Option<i64>,
+    pub winc: Option<i64>,
+    pub binc: Option<i64>,
+    pub movestogo: Option<i64>,
+    pub movetime: Option<i64>,
+    pub depth: Option<i64>,
+    pub nodes: Option<i64>,
+    pub infinite: bool,
+}
+
+/// The line reporting the search's best move and the reply it expects;
+/// no move means there were no legal moves
+pub fn bestmove_line(
+    best_move: Option<&str>,
+    ponder: Option<&str>,
+    format: OutputFormat,
+) -> String {
+    match format {
+        OutputFormat::Uci => {
+            // UCI's null move
+            let mut line = format!("bestmove {}", best_move.unwrap_or("0000"));
+            if let Some(ponder) = ponder {
+                line += &format!(" ponder {}", ponder);
+            }
+            line
+        }
+        OutputFormat::Json => {
+            let json = |mv: Option<&str>| mv.map_or("null".to_string(), |mv| format!("\"{}\"", mv));
+            format!(
+                "{{\"type\":\"bestmove\",\"move\":{},\"ponder\":{}}}",
+                json(best_move),
+                json(ponder)
+            )
+        }
+    }
+}
+
+/// Parse the sub-commands of a "go" command, in any order. Unknown tokens and
+/// values that fail to parse are skipped.
+pub fn parse_go_params(tokens: &[&str]) -> GoParams {
+    let mut params = GoParams::default();
+    let mut iter = tokens.iter().skip(1).peekable();
+
+    while let Some(&token) = iter.next() {
+        let slot = match token {
+            "wtime" => &mut params.wtime,
+            "btime" => &mut params.btime,
+            "winc" => &mut params.winc,
+            "binc" => &mut params.binc,
+            "movestogo" => &mut params.movestogo,
+            "movetime" => &mut params.movetime,
+            "depth" => &mut params.depth,
+            "nodes" => &mut params.nodes,
+            "infinite" => {
+                params.infinite = true;
+                continue;
+            }
+            _ => continue,
+        };
+        if let Some(value) = iter.peek().and_then(|s| s.parse().ok()) {
+            *slot = Some(value);
+            iter.next();
+        }
+    }
+
+    params
+}
+
+/// Parse the "go" command and return the time it allows for the move in
+/// `board`, the `move_number`th of the game, after subtracting the per-move
+/// overhead
+pub fn parse_go_command(
+    tokens: &[&str],
+    board: &Board,
+    move_number: u32,
+    move_overhead_ms: i64,
+) -> TimeBudget {
+    go_budget(
+        &parse_go_params(tokens),
+        board,
+        move_number,
+        move_overhead_ms,
+    )
+}
+
+/// Time `go` allows for the move in `board`, the `move_number`th of the
+/// game, after subtracting the per-move overhead
+fn go_budget(go: &GoParams, board: &Board, move_number: u32, move_overhead_ms: i64) -> TimeBudget {
+    timeman::allocate(go, board, move_number).minus_overhead(move_overhead_ms as f64 / 1000.0)
+}
+
+/// Whether the "go" command constrains the side to move's thinking time
+fn has_time_control(go: &GoParams, side: Color) -> bool {
+    let remaining = if side == Color::White {
+        go.wtime
+    } else {
+        go.btime
+    };
+    go.movetime.is_some() || remaining.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_position_startpos() {
+        let tokens = vec!["position", "startpos"];
+        let (fen, moves) = parse_position_command(&tokens);
+        assert_eq!(fen, START_POSITION);
+        assert!(moves.is_empty());
+    }
+
+    #[test]
+    fn test_parse_position_startpos_with_moves() {
+        let tokens = vec!["position", "startpos", "moves", "e2e4", "e7e5"];
+        let (fen, moves) = parse_position_command(&tokens);
+        assert_eq!(fen, START_POSITION);
+        assert_eq!(moves, vec!["e2e4", "e7e5"]);
+    }
+
+    #[test]
+    fn test_parse_position_fen() {
+        let tokens = vec![
+            "position",
+            "fen",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR",
+            "b",
+            "KQkq",
+            "-",
+            "0",
+            "1",
+        ];
+        let (fen, moves) = parse_position_command(&tokens);
+        assert_eq!(
+            fen,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+        assert!(moves.is_empty());
+    }
+
+    #[test]
+    fn test_parse_setoption() {
+        let tokens = vec!["setoption", "name", "Move", "Overhead", "value", "100"];
+        assert_eq!(
+            parse_setoption_command(&tokens),
+            Some(("Move Overhead".to_string(), "100".to_string()))
+        );
+
+        let tokens = vec!["setoption", "name", "Clear", "Hash"];
+        assert_eq!(
+            parse_setoption_command(&tokens),
+            Some(("Clear Hash".to_string(), String::new()))
+        );
+
+        assert_eq!(parse_setoption_command(&["setoption", "value", "1"]), None);
+    }
+
+    #[test]
+    fn test_parse_go_command() {
+        let board = Board::default();
+        let tokens = vec![
+            "go", "wtime", "300000", "btime", "300000", "winc", "3000", "binc", "3000",
+        ];
+        let budget = parse_go_command(&tokens, &board, 1, 0);
+        // (300000 / 40 + 3000) * 0.85 in the opening = 8925 ms
+        assert!((budget.soft - 8.925).abs() < 1e-9);
+        assert!(budget.hard.unwrap() > budget.soft);
+    }
+
+    #[test]
+    fn test_parse_go_any_order() {
+        let tokens = vec![
+            "go",
+            "binc",
+            "2000",
+            "movestogo",
+            "12",
+            "btime",
+            "60000",
+            "infinite",
+            "wtime",
+            "x",
+        ];
+        assert_eq!(
+            parse_go_params(&tokens),
+            GoParams {
+                btime: Some(60000),
+                binc: Some(2000),
+                movestogo: Some(12),
+                infinite: true,
+                ..GoParams::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_go_move_overhead() {
+        let board = Board::default();
+        let budget = parse_go_command(&["go", "movetime", "1000"], &board, 1, 250);
+        assert_eq!(
+            budget,
+            TimeBudget {
+                soft: 0.75,
+                hard: None
+            }
+        );
+        assert_eq!(
+            parse_go_command(&["go", "movetime", "100"], &board, 1, 250).soft,
+            0.0
+        );
+        // Both limits lose the overhead
+        let budget = parse_go_command(&["go", "wtime", "60000"], &board, 1, 500);
+        assert!((budget.soft - 0.775).abs() < 1e-9);
+        assert!((budget.hard.unwrap() - 3.325).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_go_negative_times() {
+        let board = Board::default();
+        assert_eq!(
+            parse_go_command(&["go", "movetime", "-5"], &board, 1, 0).soft,
+            0.0
+        );
+        assert_eq!(
+            parse_go_command(&["go", "wtime", "-100", "winc", "-5"], &board, 1, 0).soft,
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_move_number() {
+        let mut uci = Uci::new(Book::new());
+        assert_eq!(uci.move_number(), 1);
+        uci.handle_command("position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(uci.move_number(), 2);
+        uci.handle_command("position fen 4k3/8/8/8/8/8/8/4K2R b K - 3 30 moves e8d7 e1g1");
+        assert_eq!(uci.move_number(), 31);
+        uci.handle_command("undo");
+        assert_eq!(uci.move_number(), 31);
+        uci.handle_command("undo");
+        assert_eq!(uci.move_number(), 30);
+    }
+
+    #[test]
+    fn test_export_import_config() {
+        let board = Board::default();
+        let mut book = Book::new();
+        let e4 = moves::parse_uci_move(&board, "e2e4").unwrap();
+        book.entry(board.get_hash()).or_default().insert(e4, 3);
+
+        let mut exporter = Uci::new(book.clone());
+        exporter.handle_command("setoption name Contempt value 25");
+        exporter.handle_command("setoption name Move Overhead value 120");
+        exporter.handle_command("setoption name NullMoveR value 3");
+        exporter.handle_command("setoption name EvalSymmetryCheck value true");
+        let network = std::env::temp_dir().join("xewali_test_config.nnue");
+        std::fs::write(&network, vec![0u8; nnue::FILE_BYTES]).unwrap();
+        exporter.handle_command(&format!(
+            "setoption name EvalFile value {}",
+            network.to_str().unwrap()
+        ));
+
+        let dir = std::env::temp_dir().join("xewali_test_config");
+        let dir = dir.to_str().unwrap();
+        exporter.export_config(dir).unwrap();
+
+        let mut importer = Uci::new(Book::new());
+        importer.import_config(dir).unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+        let _ = std::fs::remove_file(&network);
+
+        assert_eq!(*importer.book, book);
+        assert_eq!(importer.option_settings(), exporter.option_settings());
+        assert!(importer.engine_state.network().is_some());
+        assert!(importer.options.check_symmetry);
+        assert_eq!(importer.engine_state.params, exporter.engine_state.params);
+        assert!(importer.import_config("/nonexistent/xewali").is_err());
+    }
+
+    #[test]
+    fn test_persist_options() {
+        let path = std::env::temp_dir().join("xewali_test_options.txt");
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let mut first = Uci::new(Book::new());
+        first.load_options_file(path);
+        first.handle_command("setoption name Contempt value 15");
+        assert!(!std::path::Path::new(path).exists());
+        first.handle_command("setoption name PersistOptions value true");
+        first.handle_command("setoption name Move Overhead value 80");
+
+        let mut second = Uci::new(Book::new());
+        second.load_options_file(path);
+        assert_eq!(second.option_settings(), first.option_settings());
+
+        // Turning persistence off forgets the saved settings
+        second.handle_command("setoption name PersistOptions value false");
+        assert!(!std::path::Path::new(path).exists());
+    }
+
+    #[test]
+    fn test_bestmove_line() {
+        assert_eq!(
+            bestmove_line(Some("e2e4"), None, OutputFormat::Uci),
+            "bestmove e2e4"
+        );
+        assert_eq!(
+            bestmove_line(Some("e2e4"), Some("e7e5"), OutputFormat::Uci),
+            "bestmove e2e4 ponder e7e5"
+        );
+        assert_eq!(
+            bestmove_line(None, None, OutputFormat::Uci),
+            "bestmove 0000"
+        );
+        assert_eq!(
+            bestmove_line(Some("e2e4"), Some("e7e5"), OutputFormat::Json),
+            r#"{"type":"bestmove","move":"e2e4","ponder":"e7e5"}"#
+        );
+        assert_eq!(
+            bestmove_line(None, None, OutputFormat::Json),
+            r#"{"type":"bestmove","move":null,"ponder":null}"#
+        );
+
+        let mut uci = Uci::new(Book::new());
+        uci.handle_command("setoption name OutputFormat value JSON");
+        assert_eq!(uci.options.output_format, OutputFormat::Json);
+    }
+
+    #[test]
+    fn test_board_diagram() {
+        let board = Board::default();
+        let ascii = board_diagram(&board, false);
+        assert!(ascii.contains("8 | r | n | b | q | k | b | n | r |"));
+        assert!(ascii.contains("1 | R | N | B | Q | K | B | N | R |"));
+        assert!(ascii.contains("4 |   |   |   |   |   |   |   |   |"));
+        assert!(ascii.contains("Castling    : KQkq"));
+        assert!(ascii.contains("Side to move: white"));
+
+        let unicode = board_diagram(&board, true);
+        assert!(unicode.contains("8 | ♜ | ♞ | ♝ | ♛ | ♚ | ♝ | ♞ | ♜ |"));
+        assert!(unicode.contains("2 | ♙ | ♙ | ♙ | ♙ | ♙ | ♙ | ♙ | ♙ |"));
+    }
+
+    #[test]
+    fn test_set_variant() {
+        let mut uci = Uci::new(Book::new());
+        uci.handle_command("setoption name UCI_Variant value antichess");
+        assert_eq!(uci.options.variant, Variant::Antichess);
+        uci.handle_command("setoption name UCI_Variant value crazyhouse");
+        assert_eq!(uci.options.variant, Variant::Antichess);
+        uci.handle_command("setoption name UCI_Variant value chess");
+        assert_eq!(uci.options.variant, Variant::Standard);
+    }
+
+    #[test]
+    fn test_stop_ends_infinite_search() {
+        let mut uci = Uci::new(Book::new());
+        uci.handle_command("setoption name OwnBook value false");
+        uci.handle_command("position startpos moves e2e4");
+        uci.handle_command("go infinite");
+        assert!(uci.search.is_some());
+        std::thread::sleep(Duration::from_millis(50));
+        uci.handle_command("stop");
+        assert!(uci.search.is_none());
+        assert!(uci.engine_state.nodes > 0);
+
+        // The next search isn't stopped by the earlier `stop`
+        uci.handle_command("go depth 2");
+        uci.wait_for_search();
+        assert!(uci.engine_state.nodes > 0);
+    }
+
+    #[test]
+    fn test_commands_during_infinite_search() {
+        let mut uci = Uci::new(Book::new());
+        uci.handle_command("setoption name OwnBook value false");
+        uci.handle_command("position startpos");
+        uci.handle_command("go infinite");
+        std::thread::sleep(Duration::from_millis(20));
+
+        // Neither waits for the search, which only ends on `stop`
+        uci.handle_command("setoption name Hash value 16");
+        uci.handle_command("d");
+        assert!(uci.search.is_some());
+
+        // The search reports its best move, then the option is applied
+        uci.handle_command("stop");
+        assert!(uci.search.is_none());
+        assert!(uci.last_best_move.is_some());
+        assert_eq!(uci.engine_state.hash_mb(), 16);
+    }
+
+    #[test]
+    fn test_new_position_interrupts_search() {
+        let mut uci = Uci::new(Book::new());
+        uci.handle_command("setoption name OwnBook value false");
+        uci.handle_command("position startpos");
+        uci.handle_command("go infinite");
+        std::thread::sleep(Duration::from_millis(20));
+
+        // Would never return if it waited for the infinite search
+        uci.handle_command("position startpos moves e2e4");
+        assert!(uci.search.is_none());
+        assert_ne!(uci.board, Board::default());
+
+        uci.handle_command("go infinite");
+        uci.handle_command("go depth 1");
+        uci.wait_for_search();
+        assert!(uci.engine_state.nodes > 0);
+    }
+
+    #[test]
+    fn test_selfplay_leaves_position_unchanged() {
+        let mut uci = Uci::new(Book::new());
+        uci.handle_command("position fen k7/3Q4/1K6/8/8/8/8/8 w - - 0 1");
+        let before = uci.board;
+        uci.handle_command("selfplay depth 2");
+        assert_eq!(uci.board, before);
+    }
+
+    #[test]
+    fn test_halfmove_clock_follows_position() {
+        let mut uci = Uci::new(Book::new());
+        uci.handle_command("position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 97 80 moves e1d1 e8d8");
+        assert_eq!(uci.halfmove_clock(), 99);
+        uci.handle_command("undo");
+        assert_eq!(uci.halfmove_clock(), 98);
+        uci.handle_command("position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 97 80 moves e2e4 e8d8");
+        assert_eq!(uci.halfmove_clock(), 1);
+    }
+
+    #[test]
+    fn test_undo_takes_back_moves() {
+        let mut uci = Uci::new(Book::new());
+        uci.handle_command("position startpos moves e2e4 e7e5 g1f3");
+        uci.handle_command("undo");
+        let (board, history, _) = engine::set_position(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &["e2e4".to_string(), "e7e5".to_string()],
+        )
+        .unwrap();
+        assert_eq!(uci.board, board);
+        assert_eq!(uci.position_history, history);
+
+        // Taking back more moves than were played stops at the start position
+        uci.handle_command("undo 5");
+        assert_eq!(uci.board, Board::default());
+        assert_eq!(uci.position_history, vec![Board::default().get_hash()]);
+    }
+
+    #[test]
+    fn test_flip_side_to_move() {
+        let mut uci = Uci::new(Book::new());
+        uci.handle_command("position startpos moves e2e4");
+        uci.handle_command("flip");
+        assert_eq!(uci.board.side_to_move(), Color::White);
+        assert_eq!(uci.board.en_passant(), None);
+        assert_eq!(uci.position_history, vec![uci.board.get_hash()]);
+
+        // Black is in check after 1. e4 f6 2. Qh5+
+        uci.handle_command("position startpos moves e2e4 f7f6 d1h5");
+        let before = uci.board;
+        uci.handle_command("flip");
+        assert_eq!(uci.board, before);
+    }
+
+    #[test]
+    fn test_garbage_commands_do_not_panic() {
+        use rand::rngs::StdRng;
+        use rand::seq::SliceRandom;
+        use rand::{Rng, SeedableRng};
+
+        let words = [
+            "uci",
+            "isready",
+            "position",
+            "startpos",
+            "fen",
+            "moves",
+            "go",
+            "movetime",
+            "wtime",
+            "btime",
+            "winc",
+            "binc",
+            "setoption",
+            "name",
+            "value",
+            "debug",
+            "on",
+            "e2e4",
+            "e7e8q",
+            "a7a8Q",
+            "e1g1",
+            "zz9",
+            "é3é4",
+            "-1",
+            "0",
+            "8/8/8/8",
+            "K7/8/k7",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
+            "w",
+            "b",
+            "KQkq",
+            "-",
+            "9",
+            "8/8/8/8/8/8/8/8",
+            "display",
+            "eval",
+            "ucinewgame",
+        ];
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let mut uci = Uci::new(Book::new());
+
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..10);
+            let mut line: Vec<&str> = (0..len).map(|_| *words.choose(&mut rng).unwrap()).collect();
+            // Searches are covered elsewhere; keep them instant here
+            if line.first() == Some(&"go") {
+                line = vec!["go", "movetime", "0"];
+            }
+            uci.handle_command(&line.join(" "));
+        }
+    }
+}
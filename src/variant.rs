@@ -0,0 +1,151 @@
+// author: Himangshu Saikia, 2018-2021 (original C++)
+// Rust port: 2024
+// email: himangshu.saikia.iitg@gmail.com
+
+use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, EMPTY};
+
+use crate::evaluation::{self, eval_accumulated, Accumulator, GameResult, MATE_EVAL, MAX_PHASE};
+use crate::nnue::{self, Network};
+
+/// Rules the engine plays by. Everything that differs between variants
+/// (move generation, game end and evaluation) goes through here so the
+/// search itself stays variant-agnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// Losing chess: captures are compulsory and the side left without a
+    /// move wins. This is a stub built on the standard move generator, so the
+    /// king stays royal and cannot be captured.
+    Antichess,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 2] = [Variant::Standard, Variant::Antichess];
+
+    /// Name used for the `UCI_Variant` option
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Standard => "chess",
+            Variant::Antichess => "antichess",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Variant> {
+        Variant::ALL
+            .into_iter()
+            .find(|v| v.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Legal moves in the position under this variant's rules
+    pub fn legal_moves(self, board: &Board) -> Vec<ChessMove> {
+        match self {
+            Variant::Standard => MoveGen::new_legal(board).collect(),
+            Variant::Antichess => {
+                let mut movegen = MoveGen::new_legal(board);
+                movegen.set_iterator_mask(*board.color_combined(!board.side_to_move()));
+                let captures: Vec<ChessMove> = movegen.by_ref().collect();
+                if !captures.is_empty() {
+                    return captures;
+                }
+                movegen.set_iterator_mask(!EMPTY);
+                movegen.collect()
+            }
+        }
+    }
+
+    /// Whether the game is over, and who won
+    pub fn game_result(self, board: &Board) -> GameResult {
+        match self {
+            Variant::Standard => evaluation::has_game_ended(board),
+            Variant::Antichess => {
+                if board.status() == BoardStatus::Ongoing {
+                    GameResult::Ongoing
+                } else if board.side_to_move() == Color::White {
+                    GameResult::WhiteWins
+                } else {
+                    GameResult::BlackWins
+                }
+            }
+        }
+    }
+
+    /// Evaluate the position from White's point of view, scoring draws as
+    /// `draw_score`
+    pub fn eval(self, board: &Board, draw_score: i32) -> i32 {
+        self.eval_accumulated(board, &Accumulator::new(board), draw_score)
+    }
+
+    /// Evaluate the position as `eval` does, with the material and
+    /// piece-square sums of `accumulator`
+    pub fn eval_accumulated(
+        self,
+        board: &Board,
+        accumulator: &Accumulator,
+        draw_score: i32,
+    ) -> i32 {
+        match self {
+            Variant::Standard => eval_accumulated(board, accumulator, draw_score),
+            Variant::Antichess => match self.game_result(board) {
+                GameResult::WhiteWins => MATE_EVAL,
+                GameResult::BlackWins => -MATE_EVAL,
+                GameResult::Draw => draw_score,
+                GameResult::Ongoing => {
+                    // Shedding material is the goal, counted at middlegame values
+                    let material = accumulator.material(MAX_PHASE);
+                    material[1] - material[0]
+                }
+            },
+        }
+    }
+
+    /// Evaluate the position with `network` and its hidden layer values in
+    /// `nnue`. Networks are trained on standard chess, so other variants
+    /// keep their own evaluation, from `accumulator`.
+    pub fn eval_network(
+        self,
+        board: &Board,
+        network: &Network,
+        nnue: &nnue::Accumulator,
+        accumulator: &Accumulator,
+        draw_score: i32,
+    ) -> i32 {
+        match self {
+            Variant::Standard => evaluation::eval_network(board, network, nnue, draw_score),
+            Variant::Antichess => self.eval_accumulated(board, accumulator, draw_score),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_variant_names() {
+        for variant in Variant::ALL {
+            assert_eq!(Variant::from_name(variant.name()), Some(variant));
+        }
+        assert_eq!(Variant::from_name("Antichess"), Some(Variant::Antichess));
+        assert_eq!(Variant::from_name("crazyhouse"), None);
+    }
+
+    #[test]
+    fn test_standard_matches_plain_rules() {
+        let board = Board::default();
+        assert_eq!(Variant::Standard.legal_moves(&board).len(), 20);
+        assert_eq!(Variant::Standard.eval(&board, 0), evaluation::eval(&board));
+    }
+
+    #[test]
+    fn test_antichess_captures_are_compulsory() {
+        // After 1. e3 b5 White must take on b5
+        let board = Board::from_str("rnbqkbnr/p1pppppp/8/1p6/8/4P3/PPPP1PPP/RNBQKBNR w KQkq - 0 2")
+            .unwrap();
+        let moves = Variant::Antichess.legal_moves(&board);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].to_string(), "f1b5");
+        assert!(Variant::Standard.legal_moves(&board).len() > 1);
+    }
+}